@@ -192,6 +192,7 @@ fn main() {
     let mut remote_id: Option<PublicKey> = None;
     let mut remote_socket: InetSocketAddr;
     let connect: bool;
+    let mut reconnect: Option<peerd::Reconnect> = None;
     let connection = match peer_socket {
         PeerSocket::Listen(RemoteSocketAddr::Ftcp(inet_addr)) => {
             debug!("Running in LISTEN mode");
@@ -247,8 +248,13 @@ fn main() {
             remote_socket = remote_node_addr.remote_addr.into();
 
             info!("Connecting to {}", &remote_node_addr.addr());
-            PeerConnection::connect(remote_node_addr, &local_node)
-                .expect("Unable to connect to the remote peer")
+            let connection = PeerConnection::connect(remote_node_addr.clone(), &local_node)
+                .expect("Unable to connect to the remote peer");
+            reconnect = Some(peerd::Reconnect {
+                remote_node_addr,
+                local_node,
+            });
+            connection
         }
         _ => unimplemented!(),
     };
@@ -263,6 +269,7 @@ fn main() {
         local_socket,
         remote_socket,
         connect,
+        reconnect,
     )
     .expect("Error running peerd runtime");
 
@@ -21,7 +21,6 @@ use clap::Clap;
 
 use farcaster_node::cli::Opts;
 use farcaster_node::rpc::Client;
-use farcaster_node::LogStyle;
 use farcaster_node::ServiceConfig;
 use microservices::shell::Exec;
 
@@ -37,9 +36,11 @@ fn main() {
     debug!("CTL RPC socket {}", &service_config.ctl_endpoint);
 
     let mut client = Client::with(service_config).expect("Error initializing client");
+    client.set_format(opts.format);
 
     trace!("Executing command: {:?}", opts.command);
-    opts.command
-        .exec(&mut client)
-        .unwrap_or_else(|err| eprintln!("{} {}", "error:".err(), err.err()));
+    opts.command.exec(&mut client).unwrap_or_else(|err| {
+        client.print_error(&err);
+        std::process::exit(1);
+    });
 }
@@ -31,6 +31,7 @@ extern crate log;
 
 use clap::Clap;
 
+use farcaster_node::config::SwapParams;
 use farcaster_node::swapd::{self, Opts};
 use farcaster_node::ServiceConfig;
 
@@ -57,9 +58,17 @@ fn main() {
     debug!("Starting runtime ...");
     swapd::run(
         service_config,
+        opts.shared.data_dir,
         opts.swap_id,
         opts.public_offer,
         opts.trade_role,
+        SwapParams {
+            btc_finality_thr: opts.btc_finality_thr,
+            race_thr: opts.race_thr,
+            xmr_finality_thr: opts.xmr_finality_thr,
+            sweep_monero_thr: opts.sweep_monero_thr,
+        },
+        opts.restore,
     )
     .expect("Error running swapd runtime");
 
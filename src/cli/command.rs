@@ -33,6 +33,8 @@ use farcaster_core::{
 use strict_encoding::ReadExt;
 
 use super::Command;
+use crate::opts::OutputFormat;
+use crate::rpc::request::Outcome;
 use crate::rpc::{request, Client, Request};
 use crate::{Error, LogStyle, ServiceId};
 
@@ -62,10 +64,10 @@ impl Exec for Command {
                     // subject is none
                     runtime.request(ServiceId::Farcasterd, Request::GetInfo)?;
                 }
-                match runtime.response()? {
-                    Request::NodeInfo(info) => println!("{}", info),
-                    Request::PeerInfo(info) => println!("{}", info),
-                    Request::SwapInfo(info) => println!("{}", info),
+                match runtime.report_failure()? {
+                    resp @ Request::NodeInfo(_)
+                    | resp @ Request::PeerInfo(_)
+                    | resp @ Request::SwapInfo(_) => runtime.print_response(&resp)?,
                     _ => {
                         return Err(Error::Other(
                             "Server returned unrecognizable response".to_string(),
@@ -110,6 +112,10 @@ impl Exec for Command {
                 bind_ip_addr,
                 port,
                 overlay,
+                expiry,
+                max_takes,
+                unlimited_takes,
+                liquidity_cap,
             } => {
                 if network != Network::Testnet && network != Network::Local {
                     eprintln!(
@@ -125,6 +131,19 @@ impl Exec for Command {
                     );
                     return Ok(());
                 }
+                if max_takes == 0 {
+                    eprintln!("Error: the offer must be takeable at least once");
+                    return Ok(());
+                }
+                if let Some(liquidity_cap) = liquidity_cap {
+                    if liquidity_cap < arbitrating_amount {
+                        eprintln!(
+                            "Error: liquidity cap {} lower than the offer amount {}",
+                            liquidity_cap, arbitrating_amount
+                        );
+                        return Ok(());
+                    }
+                }
                 let offer = farcaster_core::negotiation::Offer {
                     network,
                     arbitrating_blockchain,
@@ -145,6 +164,13 @@ impl Exec for Command {
                     peer_secret_key: None,
                     arbitrating_addr,
                     accordant_addr: accordant_addr.to_string(),
+                    expiry,
+                    max_takes: if unlimited_takes {
+                        None
+                    } else {
+                        Some(max_takes)
+                    },
+                    liquidity_cap: liquidity_cap.map(bitcoin::Amount::as_sat),
                 };
                 runtime.request(ServiceId::Farcasterd, Request::MakeOffer(proto_offer))?;
                 // report success of failure of the request to cli
@@ -159,6 +185,14 @@ impl Exec for Command {
                 // hex.bright_yellow_bold());
             }
 
+            Command::RevokeOffer { public_offer } => {
+                runtime.request(
+                    ServiceId::Farcasterd,
+                    Request::RevokeOffer(public_offer.id()),
+                )?;
+                runtime.report_progress()?;
+            }
+
             Command::Take {
                 public_offer,
                 bitcoin_address,
@@ -172,21 +206,28 @@ impl Exec for Command {
                     node_id,
                     peer_address,
                 } = public_offer.clone();
+                // the validation goes to stderr with structured output, keeping stdout parsable
+                let to_stdout = runtime.format() == OutputFormat::Text;
                 if !without_validation {
                     let taker_role = offer.maker_role.other();
                     let arb_amount = offer.arbitrating_amount;
                     let acc_amount = offer.accordant_amount;
-                    println!(
-                        "\nWant to buy {}?\n\nCarefully validate offer!\n",
-                        match taker_role {
-                            SwapRole::Alice => format!("{} for {}", arb_amount, acc_amount),
-                            SwapRole::Bob => format!("{} for {}", acc_amount, arb_amount),
-                        }
+                    prompt(
+                        to_stdout,
+                        format!(
+                            "\nWant to buy {}?\n\nCarefully validate offer!\n\n\
+                             Trade counterparty: {}@{}\n\n{}\n",
+                            match taker_role {
+                                SwapRole::Alice => format!("{} for {}", arb_amount, acc_amount),
+                                SwapRole::Bob => format!("{} for {}", acc_amount, arb_amount),
+                            },
+                            &node_id,
+                            peer_address,
+                            offer
+                        ),
                     );
-                    println!("Trade counterparty: {}@{}\n", &node_id, peer_address);
-                    println!("{}\n", offer);
                 }
-                if without_validation || take_offer() {
+                if without_validation || take_offer(to_stdout) {
                     // pass offer to farcasterd to initiate the swap
                     runtime.request(
                         ServiceId::Farcasterd,
@@ -199,26 +240,185 @@ impl Exec for Command {
                 }
             }
 
-            Command::Progress { swapid } => {
+            Command::Progress {
+                swapid,
+                follow: false,
+            } => {
                 runtime.request(ServiceId::Farcasterd, Request::ReadProgress(swapid))?;
                 runtime.report_progress()?;
             }
+
+            Command::Progress {
+                swapid,
+                follow: true,
+            } => {
+                runtime.request(ServiceId::Farcasterd, Request::FollowProgress(swapid))?;
+                match runtime.follow_progress()? {
+                    Outcome::Buy => {}
+                    Outcome::Refund => std::process::exit(2),
+                    Outcome::Punish => std::process::exit(3),
+                    Outcome::Abort => std::process::exit(4),
+                }
+            }
+
+            Command::Abort { swapid } => {
+                runtime.request(ServiceId::Swap(swapid), Request::AbortSwap(swapid))?;
+                runtime.report_progress()?;
+            }
+
+            Command::BroadcastTx { swapid, tx, force } => {
+                let manual_tx = request::ManualTx {
+                    swap_id: swapid,
+                    tx_label: tx.into(),
+                    force,
+                };
+                runtime.request(ServiceId::Swap(swapid), Request::BroadcastTx(manual_tx))?;
+                runtime.report_progress()?;
+            }
+
+            Command::ExportTx { swapid, tx, force } => {
+                let manual_tx = request::ManualTx {
+                    swap_id: swapid,
+                    tx_label: tx.into(),
+                    force,
+                };
+                runtime.request(ServiceId::Swap(swapid), Request::ExportTx(manual_tx))?;
+                match runtime.report_failure()? {
+                    Request::TxHex(hex) if runtime.format() == OutputFormat::Text => {
+                        println!("{}", hex)
+                    }
+                    resp @ Request::TxHex(_) => runtime.print_response(&resp)?,
+                    _ => {
+                        return Err(Error::Other(
+                            "Server returned unrecognizable response".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            Command::ChangePassphrase => {
+                let current = rpassword::prompt_password_stdout(
+                    "Current passphrase (empty if the key file is not encrypted): ",
+                )?;
+                let new = rpassword::prompt_password_stdout("New passphrase: ")?;
+                if new != rpassword::prompt_password_stdout("Repeat new passphrase: ")? {
+                    return Err(Error::Other(s!("Passphrases do not match")));
+                }
+                runtime.request(
+                    ServiceId::Wallet,
+                    Request::ChangePassphrase(request::ChangePassphrase { current, new }),
+                )?;
+                runtime.report_progress()?;
+            }
+
+            Command::ExportSeed => {
+                let passphrase = rpassword::prompt_password_stdout("Key file passphrase: ")?;
+                runtime.request(
+                    ServiceId::Wallet,
+                    Request::ExportSeed(request::ExportSeed { passphrase }),
+                )?;
+                match runtime.report_failure()? {
+                    resp @ Request::SeedMnemonic(_) if runtime.format() != OutputFormat::Text => {
+                        runtime.print_response(&resp)?
+                    }
+                    Request::SeedMnemonic(request::SeedMnemonic {
+                        mnemonic,
+                        wallet_counter,
+                    }) => {
+                        println!("{}", "Wallet seed mnemonic:".bright_white_bold());
+                        println!("{}", mnemonic.bright_yellow_bold());
+                        println!(
+                            "{} {}",
+                            "Wallet counter:".bright_white_bold(),
+                            wallet_counter.bright_yellow_bold()
+                        );
+                    }
+                    _ => {
+                        return Err(Error::Other(
+                            "Server returned unrecognizable response".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            Command::RestoreSeed { wallet_counter } => {
+                let mnemonic = rpassword::prompt_password_stdout("Mnemonic: ")?;
+                let passphrase = rpassword::prompt_password_stdout("Key file passphrase: ")?;
+                runtime.request(
+                    ServiceId::Wallet,
+                    Request::RestoreSeed(request::RestoreSeed {
+                        passphrase,
+                        mnemonic: mnemonic.trim().to_string(),
+                        wallet_counter,
+                    }),
+                )?;
+                runtime.report_progress()?;
+            }
+
+            Command::Recover {
+                network,
+                from_height,
+            } => {
+                runtime.request(
+                    ServiceId::Farcasterd,
+                    Request::RecoverFunds(request::RecoverFunds {
+                        network,
+                        from_height,
+                    }),
+                )?;
+                runtime.report_progress()?;
+            }
+
+            Command::OrphanWallets { network } => {
+                runtime.request(
+                    ServiceId::Farcasterd,
+                    Request::ListOrphanWallets(request::ListOrphanWallets { network }),
+                )?;
+                runtime.report_response()?;
+            }
+
+            Command::RecoveryInfo => {
+                runtime.request(ServiceId::Wallet, Request::GetRecoveryInfo)?;
+                runtime.report_response()?;
+            }
+
+            Command::SweepRecovered {
+                bitcoin_address,
+                fee_rate,
+            } => {
+                runtime.request(
+                    ServiceId::Wallet,
+                    Request::SweepRecovered(request::SweepRecovered {
+                        address: bitcoin_address,
+                        fee_rate,
+                    }),
+                )?;
+                runtime.report_progress()?;
+            }
         }
 
         Ok(())
     }
 }
 
-fn take_offer() -> bool {
-    println!("Take it? [y/n]");
+fn prompt(to_stdout: bool, msg: String) {
+    if to_stdout {
+        println!("{}", msg);
+    } else {
+        eprintln!("{}", msg);
+    }
+}
+
+fn take_offer(to_stdout: bool) -> bool {
+    prompt(to_stdout, s!("Take it? [y/n]"));
     let mut input = [0u8; 1];
     std::io::stdin().read_exact(&mut input).unwrap_or(());
     match std::str::from_utf8(&input[..]) {
         Ok("y") | Ok("Y") => true,
         Ok("n") | Ok("N") => {
-            println!("Rejecting offer");
+            prompt(to_stdout, s!("Rejecting offer"));
             false
         }
-        _ => take_offer(),
+        _ => take_offer(to_stdout),
     }
 }
@@ -15,6 +15,7 @@
 use bitcoin::Address as BtcAddress;
 use clap::{AppSettings, Clap};
 use monero::Address as XmrAddress;
+use std::io;
 use std::net::IpAddr;
 use std::str::FromStr;
 
@@ -27,6 +28,7 @@ use farcaster_core::{
     negotiation::PublicOffer,
     role::SwapRole,
     swap::{btcxmr::BtcXmr, SwapId},
+    transaction::TxLabel,
 };
 
 /// Command-line tool for working with Farcaster node
@@ -44,6 +46,15 @@ pub struct Opts {
     #[clap(flatten)]
     pub shared: crate::opts::Opts,
 
+    /// Format of the responses: machine-readable `json` or `yaml`, or human-readable `text`
+    #[clap(
+        long,
+        global = true,
+        default_value = "text",
+        possible_values = &["json", "yaml", "text"]
+    )]
+    pub format: crate::opts::OutputFormat,
+
     /// Command to execute
     #[clap(subcommand)]
     pub command: Command,
@@ -168,6 +179,32 @@ pub enum Command {
         /// Use overlay protocol (http, websocket etc).
         #[clap(long, default_value = "tcp")]
         overlay: FramingProtocol,
+
+        /// Number of seconds after which the offer expires and can no longer be taken. The
+        /// offer never expires if absent.
+        #[clap(long)]
+        expiry: Option<u64>,
+
+        /// Number of times the offer can be taken, a new swap is started for each taker.
+        #[clap(long, default_value = "1", conflicts_with = "unlimited-takes")]
+        max_takes: u32,
+
+        /// Let the offer be taken any number of times until the liquidity cap is reached.
+        #[clap(long, requires = "liquidity-cap")]
+        unlimited_takes: bool,
+
+        /// Total amount of arbitrating assets that can be exchanged through the offer.
+        #[clap(long)]
+        liquidity_cap: Option<bitcoin::Amount>,
+    },
+
+    /// Maker revokes a public offer so it can no longer be taken. The listener is stopped once no
+    /// offer remains on it.
+    #[clap(setting = AppSettings::ColoredHelp)]
+    RevokeOffer {
+        /// The encoded public offer to revoke.
+        #[clap(short = 'o', long = "offer")]
+        public_offer: PublicOffer<BtcXmr>,
     },
 
     /// Taker accepts offer and connects to maker's daemon to start the trade.
@@ -196,7 +233,177 @@ pub enum Command {
     Progress {
         /// The swap id requested.
         swapid: SwapId,
+
+        /// Keep printing the progress of the swap until it finishes. Exits with code 0 if the
+        /// swap succeeds, 2 if it is refunded, 3 if it is punished and 4 if it is aborted.
+        #[clap(short, long)]
+        follow: bool,
+    },
+
+    /// Abort a swap. Before the bitcoins are locked the counterparty is notified and the swap
+    /// stops; afterwards the swap stops progressing towards the buy and the cancel and refund
+    /// transactions are broadcast once the cancel timelock expires.
+    #[display("abort<{swapid}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    Abort {
+        /// The swap id to abort.
+        swapid: SwapId,
+    },
+
+    /// Broadcast a transaction of a swap, e.g. when the automatic broadcast failed. Refused
+    /// unless the transaction is safe to broadcast at the current height.
+    #[display("broadcast_tx<{swapid}, {tx}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    BroadcastTx {
+        /// The swap id of the transaction.
+        swapid: SwapId,
+
+        /// The transaction to broadcast.
+        #[clap(possible_values = &["buy", "cancel", "refund", "punish"])]
+        tx: SwapTx,
+
+        /// Skip the temporal safety checks.
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Print the raw hex of a transaction of a swap, to broadcast it by other means. Refused
+    /// unless the transaction is safe to broadcast at the current height.
+    #[display("export_tx<{swapid}, {tx}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    ExportTx {
+        /// The swap id of the transaction.
+        swapid: SwapId,
+
+        /// The transaction to export.
+        #[clap(possible_values = &["buy", "cancel", "refund", "punish"])]
+        tx: SwapTx,
+
+        /// Skip the temporal safety checks.
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Change the passphrase encrypting the node key file. An empty new passphrase stores the
+    /// key file unencrypted.
+    #[clap(setting = AppSettings::ColoredHelp)]
+    ChangePassphrase,
+
+    /// Export the wallet seed as a BIP39 mnemonic, along with the wallet counter needed to
+    /// restore it.
+    #[clap(setting = AppSettings::ColoredHelp)]
+    ExportSeed,
+
+    /// Restore the wallet seed from a BIP39 mnemonic. Only possible while no swap is running.
+    #[display("restore_seed<{wallet_counter}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    RestoreSeed {
+        /// Wallet counter printed on export; swaps keys derive from the following indexes.
+        #[clap(long)]
+        wallet_counter: u32,
     },
+
+    /// Scan the funding addresses of all swap wallets derived from the wallet seed for bitcoins
+    /// left on them, e.g. after restoring the seed or losing swap checkpoints. This is a partial,
+    /// funding only recovery: bitcoins locked in a swap and moneros can only be claimed by a swap
+    /// restored from its checkpoint.
+    #[display("recover<{network}, {from_height}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    Recover {
+        /// Network to scan.
+        #[clap(
+            short,
+            long,
+            default_value = "testnet",
+            possible_values = &["Testnet", "testnet", "Mainnet", "mainnet", "Local", "local"]
+        )]
+        network: Network,
+
+        /// Block height to start scanning from, e.g. the height at which the wallet seed was
+        /// created. Funds received below this height are not found.
+        #[clap(long)]
+        from_height: u64,
+    },
+
+    /// List the Monero watch and sweep wallet files no longer used by the running Monero
+    /// syncer, for garbage collection. The syncer only runs while swaps are running.
+    #[display("orphan_wallets<{network}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    OrphanWallets {
+        /// Network of the Monero syncer.
+        #[clap(
+            short,
+            long,
+            default_value = "testnet",
+            possible_values = &["Testnet", "testnet", "Mainnet", "mainnet", "Local", "local"]
+        )]
+        network: Network,
+    },
+
+    /// List the funding outputs found by the last recovery scan along with their sweep status.
+    #[clap(setting = AppSettings::ColoredHelp)]
+    RecoveryInfo,
+
+    /// Sweep the funding outputs found by the recovery scan to a bitcoin address, one transaction
+    /// per output.
+    #[display("sweep_recovered<{bitcoin_address}>")]
+    #[clap(setting = AppSettings::ColoredHelp)]
+    SweepRecovered {
+        /// Bitcoin address receiving the recovered funds.
+        #[clap(long = "btc-addr")]
+        bitcoin_address: BtcAddress,
+
+        /// Fee rate of the sweep transactions, in satoshi per vByte.
+        #[clap(long, default_value = "1")]
+        fee_rate: u64,
+    },
+}
+
+/// Transaction of a swap that can be broadcasted or exported manually
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display)]
+pub enum SwapTx {
+    #[display("buy")]
+    Buy,
+
+    #[display("cancel")]
+    Cancel,
+
+    #[display("refund")]
+    Refund,
+
+    #[display("punish")]
+    Punish,
+}
+
+impl FromStr for SwapTx {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "buy" => Ok(SwapTx::Buy),
+            "cancel" => Ok(SwapTx::Cancel),
+            "refund" => Ok(SwapTx::Refund),
+            "punish" => Ok(SwapTx::Punish),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown transaction {}, use buy, cancel, refund or punish",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+impl From<SwapTx> for TxLabel {
+    fn from(tx: SwapTx) -> Self {
+        match tx {
+            SwapTx::Buy => TxLabel::Buy,
+            SwapTx::Cancel => TxLabel::Cancel,
+            SwapTx::Refund => TxLabel::Refund,
+            SwapTx::Punish => TxLabel::Punish,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Display, Error, From)]
@@ -12,6 +12,7 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+use crate::syncerd::opts::BitcoinBackend;
 use crate::Error;
 use farcaster_core::blockchain::Network;
 use internet2::NodeAddr;
@@ -20,6 +21,7 @@ use std::io::prelude::*;
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
+use serde_with::DisplayFromStr;
 
 pub const FARCASTER_MAINNET_ELECTRUM_SERVER: &str = "ssl://blockstream.info:700";
 pub const FARCASTER_MAINNET_MONERO_DAEMON: &str = "http://node.monerooutreach.org:18081";
@@ -37,6 +39,12 @@ use crate::opts::Opts;
 pub struct Config {
     /// Syncer configuration
     pub syncers: Option<SyncersConfig>,
+
+    /// Swap temporal safety configuration
+    pub swap: Option<SwapConfig>,
+
+    /// Acceptance policy of the offers made, taken or committed to by a taker
+    pub offer_policy: Option<OfferPolicy>,
 }
 
 impl Config {
@@ -47,12 +55,27 @@ impl Config {
             Network::Local => self.syncers.as_ref()?.local.clone(),
         }
     }
+
+    /// Temporal safety parameters of the swaps on the network, falling back to the network
+    /// defaults when not configured
+    pub fn get_swap_params(&self, network: Network) -> SwapParams {
+        self.swap
+            .as_ref()
+            .and_then(|swap| match network {
+                Network::Mainnet => swap.mainnet,
+                Network::Testnet => swap.testnet,
+                Network::Local => swap.local,
+            })
+            .unwrap_or_else(|| SwapParams::default_for(network))
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             syncers: Some(SyncersConfig::default()),
+            swap: Some(SwapConfig::default()),
+            offer_policy: None,
         }
     }
 }
@@ -70,37 +93,173 @@ pub struct SyncersConfig {
     pub local: Option<SyncerServers>,
 }
 
+#[serde_as]
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
 #[serde(crate = "serde_crate")]
 pub struct SyncerServers {
+    /// Backend of the bitcoin syncer, either `electrum` (default), `bitcoind` or `esplora`
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub bitcoin_backend: Option<BitcoinBackend>,
+
     /// Electrum server to use
-    pub electrum_server: String,
+    pub electrum_server: Option<String>,
+
+    /// Electrum servers to fail over to when the main one is unreachable or lagging behind, by
+    /// decreasing priority
+    pub electrum_fallback_servers: Option<Vec<String>>,
+
+    /// Esplora REST API url to use with the `esplora` backend
+    pub esplora_server: Option<String>,
+
+    /// Bitcoind JSON-RPC url to use with the `bitcoind` backend
+    pub bitcoind_rpc: Option<String>,
+
+    /// Bitcoind JSON-RPC cookie file, takes precedence over user and password
+    pub bitcoind_rpc_cookie: Option<String>,
+
+    /// Bitcoind JSON-RPC user
+    pub bitcoind_rpc_user: Option<String>,
+
+    /// File containing the bitcoind JSON-RPC password
+    pub bitcoind_rpc_password_file: Option<String>,
 
     /// Monero daemon to use
     pub monero_daemon: String,
 
     /// Monero rpc wallet to use
     pub monero_rpc_wallet: String,
+
+    /// Additional Monero rpc wallets, the watch and sweep wallets being spread over all the rpc
+    /// wallets
+    pub monero_rpc_wallet_pool: Option<Vec<String>>,
+
+    /// Wallet directory of the monero rpc wallets (their `--wallet-dir`), the watch and sweep
+    /// wallet files of the finished swaps are deleted from it
+    pub monero_wallet_dir: Option<String>,
+
+    /// Backend watching the monero addresses, either `wallet-rpc` (default) or `scanner` which
+    /// scans the blocks of the monero daemon with the view keys; the sweeps always go through
+    /// the monero rpc wallet
+    pub monero_backend: Option<String>,
 }
 
 impl Default for SyncersConfig {
     fn default() -> Self {
         SyncersConfig {
             mainnet: Some(SyncerServers {
-                electrum_server: FARCASTER_MAINNET_ELECTRUM_SERVER.into(),
+                electrum_server: Some(FARCASTER_MAINNET_ELECTRUM_SERVER.into()),
                 monero_daemon: FARCASTER_MAINNET_MONERO_DAEMON.into(),
                 monero_rpc_wallet: FARCASTER_MAINNET_MONERO_RPC_WALLET.into(),
+                ..Default::default()
             }),
             testnet: Some(SyncerServers {
-                electrum_server: FARCASTER_TESTNET_ELECTRUM_SERVER.into(),
+                electrum_server: Some(FARCASTER_TESTNET_ELECTRUM_SERVER.into()),
                 monero_daemon: FARCASTER_TESTNET_MONERO_DAEMON.into(),
                 monero_rpc_wallet: FARCASTER_TESTNET_MONERO_RPC_WALLET.into(),
+                ..Default::default()
             }),
             local: None,
         }
     }
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+pub struct SwapConfig {
+    /// Mainnet swap parameters
+    pub mainnet: Option<SwapParams>,
+
+    /// Testnet swap parameters
+    pub testnet: Option<SwapParams>,
+
+    /// Local swap parameters
+    pub local: Option<SwapParams>,
+}
+
+/// Local policy on the number of blocks used to decide when swap transactions are safe to
+/// broadcast, checked against the timelocks of every offer made or taken
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "serde_crate")]
+pub struct SwapParams {
+    /// Confirmations after which a Bitcoin transaction is considered final
+    pub btc_finality_thr: u32,
+
+    /// Blocks before a timelock expires during which a transaction may be raced by the
+    /// counterparty and must not be broadcast
+    pub race_thr: u32,
+
+    /// Confirmations after which a Monero transaction is considered final
+    pub xmr_finality_thr: u32,
+
+    /// Confirmations of the Monero lock before sweeping it
+    pub sweep_monero_thr: u32,
+}
+
+impl SwapParams {
+    pub fn default_for(network: Network) -> Self {
+        match network {
+            Network::Mainnet | Network::Testnet => SwapParams {
+                btc_finality_thr: 1,
+                race_thr: 3,
+                xmr_finality_thr: 1,
+                sweep_monero_thr: 10,
+            },
+            Network::Local => SwapParams {
+                btc_finality_thr: 0,
+                race_thr: 3,
+                xmr_finality_thr: 0,
+                sweep_monero_thr: 10,
+            },
+        }
+    }
+}
+
+impl Default for SwapConfig {
+    fn default() -> Self {
+        SwapConfig {
+            mainnet: Some(SwapParams::default_for(Network::Mainnet)),
+            testnet: Some(SwapParams::default_for(Network::Testnet)),
+            local: Some(SwapParams::default_for(Network::Local)),
+        }
+    }
+}
+
+/// Bounds on the offers the node accepts to swap, every bound is optional
+#[serde_as]
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+pub struct OfferPolicy {
+    /// Networks allowed, `mainnet`, `testnet` or `local`
+    #[serde_as(as = "Option<Vec<DisplayFromStr>>")]
+    #[serde(default)]
+    pub networks: Option<Vec<Network>>,
+
+    /// Minimum Bitcoin amount, in satoshis
+    pub min_btc_amount: Option<u64>,
+
+    /// Maximum Bitcoin amount, in satoshis
+    pub max_btc_amount: Option<u64>,
+
+    /// Minimum Monero amount, in piconeros
+    pub min_xmr_amount: Option<u64>,
+
+    /// Maximum Monero amount, in piconeros
+    pub max_xmr_amount: Option<u64>,
+
+    /// Minimum number of blocks between the cancel and the punish timelocks
+    pub min_timelock_gap: Option<u32>,
+
+    /// Maximum fee rate of the offer fee strategy, in sat/vB
+    pub max_fee_rate: Option<u64>,
+
+    /// Minimum price, in XMR per BTC
+    pub min_price: Option<f64>,
+
+    /// Maximum price, in XMR per BTC
+    pub max_price: Option<f64>,
+}
+
 pub fn parse_config(path: &str) -> Result<Config, Error> {
     if Path::new(path).exists() {
         let config_file = path;
@@ -94,6 +94,10 @@ pub enum Error {
     /// BitcoinHashes
     #[display(inner)]
     BitcoinHashes(bitcoin::hashes::Error),
+
+    /// Strict encoding error: {0}
+    #[from]
+    StrictEncoding(strict_encoding::Error),
 }
 
 #[derive(Debug, Display)]
@@ -101,6 +105,12 @@ pub enum SyncerError {
     #[display(inner)]
     Electrum(electrum_client::Error),
 
+    #[display(inner)]
+    BitcoinRpc(bitcoincore_rpc::Error),
+
+    #[display(inner)]
+    Esplora(reqwest::Error),
+
     #[display(inner)]
     NoTxsOnAddress,
 
@@ -180,6 +190,18 @@ impl From<electrum_client::Error> for Error {
     }
 }
 
+impl From<bitcoincore_rpc::Error> for Error {
+    fn from(err: bitcoincore_rpc::Error) -> Self {
+        Error::Syncer(SyncerError::BitcoinRpc(err))
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Syncer(SyncerError::Esplora(err))
+    }
+}
+
 impl From<bitcoin::hashes::Error> for Error {
     fn from(err: bitcoin::hashes::Error) -> Self {
         Error::BitcoinHashes(err)
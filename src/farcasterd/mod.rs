@@ -14,6 +14,7 @@
 
 #[cfg(feature = "shell")]
 mod opts;
+pub(crate) mod policy;
 mod runtime;
 
 #[cfg(feature = "shell")]
@@ -44,6 +44,10 @@ pub struct Opts {
         value_hint = ValueHint::FilePath
     )]
     pub config: String,
+
+    /// Passphrase source for the node key file, forwarded to walletd
+    #[clap(flatten)]
+    pub passphrase_opts: crate::walletd::PassphraseOpts,
 }
 
 impl Opts {
@@ -0,0 +1,231 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Local acceptance policy of the offers, loaded from the `offer_policy` entry of the config and
+//! checked before making or taking an offer and when a taker commits to one of our offers.
+
+use farcaster_core::{blockchain::FeeStrategy, negotiation::Offer, swap::btcxmr::BtcXmr};
+
+use crate::config::OfferPolicy;
+use crate::Error;
+
+/// Check the offer against every bound of the policy, failing with the first bound violated
+pub fn check_offer(policy: &OfferPolicy, offer: &Offer<BtcXmr>) -> Result<(), Error> {
+    if let Some(networks) = &policy.networks {
+        if !networks.contains(&offer.network) {
+            return Err(violation(format!(
+                "network {} is not allowed",
+                offer.network
+            )));
+        }
+    }
+
+    let btc_amount = offer.arbitrating_amount;
+    if let Some(min) = policy.min_btc_amount {
+        if btc_amount.as_sat() < min {
+            return Err(violation(format!(
+                "bitcoin amount {} is below the minimum of {}",
+                btc_amount,
+                bitcoin::Amount::from_sat(min)
+            )));
+        }
+    }
+    if let Some(max) = policy.max_btc_amount {
+        if btc_amount.as_sat() > max {
+            return Err(violation(format!(
+                "bitcoin amount {} is above the maximum of {}",
+                btc_amount,
+                bitcoin::Amount::from_sat(max)
+            )));
+        }
+    }
+
+    let xmr_amount = offer.accordant_amount;
+    if let Some(min) = policy.min_xmr_amount {
+        if xmr_amount.as_pico() < min {
+            return Err(violation(format!(
+                "monero amount {} is below the minimum of {}",
+                xmr_amount,
+                monero::Amount::from_pico(min)
+            )));
+        }
+    }
+    if let Some(max) = policy.max_xmr_amount {
+        if xmr_amount.as_pico() > max {
+            return Err(violation(format!(
+                "monero amount {} is above the maximum of {}",
+                xmr_amount,
+                monero::Amount::from_pico(max)
+            )));
+        }
+    }
+
+    if let Some(min_gap) = policy.min_timelock_gap {
+        let cancel = offer.cancel_timelock.as_u32();
+        let punish = offer.punish_timelock.as_u32();
+        if punish.saturating_sub(cancel) < min_gap {
+            return Err(violation(format!(
+                "cancel timelock {} and punish timelock {} are less than {} blocks apart",
+                cancel, punish, min_gap
+            )));
+        }
+    }
+
+    if let Some(max_fee_rate) = policy.max_fee_rate {
+        let fee_rate = match &offer.fee_strategy {
+            FeeStrategy::Fixed(fee_rate) => fee_rate,
+            FeeStrategy::Range { max_inclusive, .. } => max_inclusive,
+        }
+        .as_native_unit()
+        .as_sat();
+        if fee_rate > max_fee_rate {
+            return Err(violation(format!(
+                "fee rate of {} sat/vB is above the maximum of {} sat/vB",
+                fee_rate, max_fee_rate
+            )));
+        }
+    }
+
+    if policy.min_price.is_some() || policy.max_price.is_some() {
+        // XMR per BTC, infinite for a zero bitcoin amount
+        let price = xmr_amount.as_xmr() / btc_amount.as_btc();
+        if let Some(min_price) = policy.min_price {
+            if price < min_price {
+                return Err(violation(format!(
+                    "price of {:.4} XMR/BTC is below the minimum of {} XMR/BTC",
+                    price, min_price
+                )));
+            }
+        }
+        if let Some(max_price) = policy.max_price {
+            if price > max_price {
+                return Err(violation(format!(
+                    "price of {:.4} XMR/BTC is above the maximum of {} XMR/BTC",
+                    price, max_price
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn violation(reason: String) -> Error {
+    Error::Farcaster(format!("offer rejected by local policy: {}", reason))
+}
+
+#[cfg(test)]
+pub(crate) fn test_offer() -> Offer<BtcXmr> {
+    use farcaster_core::{
+        bitcoin::{fee::SatPerVByte, segwitv0::SegwitV0, timelock::CSVTimelock, Bitcoin},
+        blockchain::Network,
+        monero::Monero,
+        role::SwapRole,
+    };
+    use std::str::FromStr;
+
+    Offer {
+        network: Network::Testnet,
+        arbitrating_blockchain: Bitcoin::<SegwitV0>::from_str("bitcoin").unwrap(),
+        accordant_blockchain: Monero::from_str("monero").unwrap(),
+        arbitrating_amount: bitcoin::Amount::from_sat(1_000_000),
+        accordant_amount: monero::Amount::from_pico(2_000_000_000_000),
+        cancel_timelock: CSVTimelock::from_str("4").unwrap(),
+        punish_timelock: CSVTimelock::from_str("10").unwrap(),
+        fee_strategy: FeeStrategy::<SatPerVByte>::from_str("10 satoshi/vByte").unwrap(),
+        maker_role: SwapRole::Bob,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_offer_accepts_within_bounds() {
+        use farcaster_core::blockchain::Network;
+
+        let offer = test_offer();
+        assert!(check_offer(&OfferPolicy::default(), &offer).is_ok());
+        let policy = OfferPolicy {
+            networks: Some(vec![Network::Testnet]),
+            min_btc_amount: Some(1_000_000),
+            max_btc_amount: Some(1_000_000),
+            min_xmr_amount: Some(1_000_000_000_000),
+            max_xmr_amount: Some(2_000_000_000_000),
+            min_timelock_gap: Some(6),
+            max_fee_rate: Some(10),
+            min_price: Some(199.0),
+            max_price: Some(201.0),
+        };
+        assert!(check_offer(&policy, &offer).is_ok());
+    }
+
+    #[test]
+    fn check_offer_rejects_out_of_bounds() {
+        use farcaster_core::blockchain::Network;
+
+        let offer = test_offer();
+        let rejected = |policy: OfferPolicy| check_offer(&policy, &offer).is_err();
+        assert!(rejected(OfferPolicy {
+            networks: Some(vec![Network::Mainnet, Network::Local]),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            min_btc_amount: Some(1_000_001),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            max_btc_amount: Some(999_999),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            min_xmr_amount: Some(2_000_000_000_001),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            max_xmr_amount: Some(1_999_999_999_999),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            min_timelock_gap: Some(7),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            max_fee_rate: Some(9),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            min_price: Some(201.0),
+            ..Default::default()
+        }));
+        assert!(rejected(OfferPolicy {
+            max_price: Some(199.0),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn offer_policy_rejects_unknown_network() {
+        use farcaster_core::blockchain::Network;
+
+        let policy: Result<OfferPolicy, _> = toml::from_str(r#"networks = ["testnet", "mainnet"]"#);
+        assert_eq!(
+            policy.unwrap().networks,
+            Some(vec![Network::Testnet, Network::Mainnet])
+        );
+        let policy: Result<OfferPolicy, _> = toml::from_str(r#"networks = ["tesnet"]"#);
+        assert!(policy.is_err());
+    }
+}
@@ -19,7 +19,7 @@ use crate::{
         BitcoinAddress, Keys, LaunchSwap, MoneroAddress, Outcome, PubOffer, RequestId, Reveal,
         Token,
     },
-    swapd::get_swap_id,
+    swapd::{checkpointed_swaps, get_swap_id},
     syncerd::opts::Coin,
     walletd::NodeSecrets,
     Senders,
@@ -51,7 +51,11 @@ use bitcoin::{
     },
     Address,
 };
-use internet2::{addr::InetSocketAddr, NodeAddr, RemoteSocketAddr, ToNodeAddr, TypedEnum};
+use internet2::zmqsocket::{Connection, ZmqType};
+use internet2::{
+    addr::InetSocketAddr, Duplex, Encrypt, NodeAddr, PlainTranscoder, RemoteSocketAddr, ToNodeAddr,
+    TypedEnum, ZMQ_CONTEXT,
+};
 use lnp::{message, Messages, TempChannelId as TempSwapId, LIGHTNING_P2P_DEFAULT_PORT};
 use lnpbp::chain::Chain;
 use microservices::esb::{self, Handler};
@@ -59,9 +63,10 @@ use microservices::rpc::Failure;
 
 use farcaster_core::{blockchain::Network, negotiation::PublicOfferId, swap::SwapId};
 
-use crate::farcasterd::Opts;
+use crate::farcasterd::{policy, Opts};
 use crate::rpc::request::{GetKeys, IntoProgressOrFalure, Msg, NodeInfo, OptionDetails};
 use crate::rpc::{request, Request, ServiceBus};
+use crate::swapd::TemporalSafety;
 use crate::{Config, Error, LogStyle, Service, ServiceConfig, ServiceId};
 
 use farcaster_core::{
@@ -70,10 +75,10 @@ use farcaster_core::{
         AliceParameters, BobParameters, CoreArbitratingTransactions, FundingTransaction,
         SignedArbitratingLock,
     },
-    negotiation::PublicOffer,
+    negotiation::{Offer, PublicOffer},
     protocol_message::{
-        BuyProcedureSignature, CommitAliceParameters, CommitBobParameters, CoreArbitratingSetup,
-        RefundProcedureSignatures,
+        Abort, BuyProcedureSignature, CommitAliceParameters, CommitBobParameters,
+        CoreArbitratingSetup, RefundProcedureSignatures,
     },
     role::{Alice, Bob, SwapRole, TradeRole},
     swap::btcxmr::{BtcXmr, KeyManager},
@@ -84,22 +89,32 @@ use std::str::FromStr;
 pub fn run(
     service_config: ServiceConfig,
     config: Config,
-    _opts: Opts,
+    opts: Opts,
     wallet_token: Token,
 ) -> Result<(), Error> {
-    let _walletd = launch("walletd", &["--token", &wallet_token.to_string()])?;
-    let runtime = Runtime {
+    let mut walletd_args = vec![s!("--token"), wallet_token.to_string()];
+    walletd_args.append(&mut opts.passphrase_opts.to_args());
+    let _walletd = launch("walletd", walletd_args)?;
+    let mut runtime = Runtime {
         identity: ServiceId::Farcasterd,
         listens: none!(),
+        listeners: none!(),
         started: SystemTime::now(),
         connections: none!(),
         running_swaps: none!(),
         spawning_services: none!(),
         making_swaps: none!(),
         taking_swaps: none!(),
+        restoring_swaps: none!(),
+        recovery_syncers: none!(),
+        pending_recoveries: none!(),
+        orphan_wallet_requests: none!(),
         arb_addrs: none!(),
         acc_addrs: none!(),
         public_offers: none!(),
+        offer_listens: none!(),
+        offer_expiries: none!(),
+        offer_capacities: none!(),
         node_ids: none!(),
         wallet_token,
         pending_requests: none!(),
@@ -107,24 +122,78 @@ pub fn run(
         syncer_clients: none!(),
         consumed_offers: none!(),
         progress: none!(),
+        progress_subscribers: none!(),
+        swap_outcomes: none!(),
+        swapd_processes: none!(),
         stats: none!(),
         config,
     };
 
-    let broker = true;
-    Service::run(service_config, runtime, broker)
+    for (swap_id, public_offer, local_trade_role) in checkpointed_swaps(&opts.shared.data_dir)? {
+        restore_swapd(&mut runtime, swap_id, public_offer, local_trade_role)?;
+    }
+
+    let tx = ZMQ_CONTEXT.socket(zmq::PAIR)?;
+    let rx = ZMQ_CONTEXT.socket(zmq::PAIR)?;
+    tx.connect("inproc://farcasterdbridge")?;
+    rx.bind("inproc://farcasterdbridge")?;
+    let identity: Vec<u8> = runtime.identity().into();
+    std::thread::spawn(move || run_heartbeat(tx, identity));
+
+    let mut service = Service::broker(service_config, runtime)?;
+    service.add_loopback(rx)?;
+    service.run_loop()?;
+    unreachable!()
+}
+
+/// Interval at which farcasterd wakes up to expire offers and stop unused listeners, even when
+/// no request comes in
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Send a heartbeat to farcasterd over its bridge every `HEARTBEAT_INTERVAL`
+fn run_heartbeat(tx: zmq::Socket, identity: Vec<u8>) {
+    let mut connection = Connection::from_zmq_socket(ZmqType::Push, tx);
+    let mut transcoder = PlainTranscoder {};
+    loop {
+        sleep(HEARTBEAT_INTERVAL);
+        let writer = connection.as_sender();
+        if let Err(err) = writer.send_routed(
+            &identity,
+            &identity,
+            &identity,
+            &transcoder.encrypt(Request::Heartbeat.serialize()),
+        ) {
+            error!(
+                "Failed to send the heartbeat over farcasterd bridge: {}",
+                err
+            );
+        }
+    }
 }
 
 pub struct Runtime {
     identity: ServiceId,
     listens: HashSet<RemoteSocketAddr>,
+    /// Listening peerd processes, stopped once no live offer remains on them
+    listeners: HashMap<RemoteSocketAddr, process::Child>,
     started: SystemTime,
     connections: HashSet<NodeAddr>,
     running_swaps: HashSet<SwapId>,
     spawning_services: HashMap<ServiceId, ServiceId>,
     making_swaps: HashMap<ServiceId, (request::InitSwap, Network)>,
     taking_swaps: HashMap<ServiceId, (request::InitSwap, Network)>,
+    restoring_swaps: HashMap<ServiceId, Network>,
+    /// Bitcoin syncer clients registered on behalf of walletd recovery scans, one per network
+    recovery_syncers: HashMap<Network, SwapId>,
+    /// Recovery scans waiting for their bitcoin syncer, with the height they start from
+    pending_recoveries: HashMap<Network, u64>,
+    /// Clients waiting for the orphaned wallets listed by the Monero syncer of a network
+    orphan_wallet_requests: HashMap<Network, Vec<ServiceId>>,
     public_offers: HashSet<PublicOffer<BtcXmr>>,
+    /// Listener bound for each offer made
+    offer_listens: HashMap<PublicOfferId, RemoteSocketAddr>,
+    offer_expiries: HashMap<PublicOfferId, SystemTime>,
+    offer_capacities: HashMap<PublicOfferId, OfferCapacity>,
     arb_addrs: HashMap<PublicOfferId, bitcoin::Address>,
     acc_addrs: HashMap<PublicOfferId, monero::Address>,
     consumed_offers: HashSet<(PublicOfferId, SwapId)>,
@@ -134,10 +203,42 @@ pub struct Runtime {
     syncer_services: HashMap<(Coin, Network), ServiceId>,
     syncer_clients: HashMap<(Coin, Network), HashSet<SwapId>>,
     progress: HashMap<ServiceId, VecDeque<Request>>,
+    /// Clients following the progress of a swap until its outcome
+    progress_subscribers: HashMap<SwapId, HashSet<ServiceId>>,
+    /// Outcomes of the last finished swaps, oldest first, replayed to late followers
+    swap_outcomes: VecDeque<(SwapId, Outcome)>,
+    /// Running swapd processes, to fail their followers if they exit without an outcome
+    swapd_processes: HashMap<SwapId, process::Child>,
     stats: Stats,
     config: Config,
 }
 
+/// Remaining capacity of a maker offer, withdrawn once it cannot be taken anymore
+#[derive(Clone, Copy, Debug)]
+struct OfferCapacity {
+    /// Number of takes left, unlimited if none
+    takes_left: Option<u32>,
+    /// Bitcoin amount left to swap in satoshis, unlimited if none
+    liquidity_left: Option<u64>,
+}
+
+impl OfferCapacity {
+    /// Account for one take of the offer, returns whether the offer is exhausted
+    fn take(&mut self, amount: u64) -> bool {
+        if let Some(takes_left) = self.takes_left.as_mut() {
+            *takes_left = takes_left.saturating_sub(1);
+        }
+        if let Some(liquidity_left) = self.liquidity_left.as_mut() {
+            *liquidity_left = liquidity_left.saturating_sub(amount);
+        }
+        self.takes_left == Some(0)
+            || self
+                .liquidity_left
+                .map(|liquidity_left| liquidity_left < amount)
+                .unwrap_or(false)
+    }
+}
+
 struct Stats {
     success: u64,
     failure: u64,
@@ -193,7 +294,7 @@ impl esb::Handler<ServiceBus> for Runtime {
         match bus {
             ServiceBus::Msg => self.handle_rpc_msg(senders, source, request),
             ServiceBus::Ctl => self.handle_rpc_ctl(senders, source, request),
-            _ => Err(Error::NotSupported(ServiceBus::Bridge, request.get_type())),
+            ServiceBus::Bridge => self.handle_bridge(senders, source, request),
         }
     }
 
@@ -205,6 +306,9 @@ impl esb::Handler<ServiceBus> for Runtime {
     }
 }
 
+/// Number of swap outcomes kept for the clients following a swap after it finished
+const SWAP_OUTCOMES_KEPT: usize = 100;
+
 impl Runtime {
     fn clean_up_after_swap(
         &mut self,
@@ -212,6 +316,7 @@ impl Runtime {
         senders: &mut esb::SenderList<ServiceBus, ServiceId>,
     ) -> Result<(), Error> {
         self.running_swaps.remove(swapid);
+        self.fail_progress_subscribers(senders, *swapid, s!("Swap cleaned up without an outcome"));
         let offers2rm: Vec<_> = self
             .consumed_offers
             .iter()
@@ -222,6 +327,7 @@ impl Runtime {
         for offer in &offers2rm {
             self.consumed_offers.remove(offer);
         }
+        self.stop_unused_listeners();
         let identity = self.identity();
         self.syncer_clients = self
             .syncer_clients
@@ -259,6 +365,266 @@ impl Runtime {
         Ok(())
     }
 
+    fn resume_restored_swaps(
+        &mut self,
+        senders: &mut esb::SenderList<ServiceBus, ServiceId>,
+    ) -> Result<(), Error> {
+        let ready: Vec<ServiceId> = self
+            .restoring_swaps
+            .iter()
+            .filter(|(swapd, network)| {
+                get_swap_id(swapd)
+                    .map(|swap_id| self.running_swaps.contains(&swap_id))
+                    .unwrap_or(false)
+                    && self
+                        .syncer_services
+                        .contains_key(&(Coin::Bitcoin, **network))
+                    && self
+                        .syncer_services
+                        .contains_key(&(Coin::Monero, **network))
+            })
+            .map(|(swapd, _)| swapd.clone())
+            .collect();
+        for swapd in ready {
+            info!("Resuming restored swap {}", swapd.bright_blue_italic());
+            senders.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                swapd.clone(),
+                Request::RestoreSwap,
+            )?;
+            self.restoring_swaps.remove(&swapd);
+        }
+        Ok(())
+    }
+
+    fn resume_recoveries(
+        &mut self,
+        senders: &mut esb::SenderList<ServiceBus, ServiceId>,
+    ) -> Result<(), Error> {
+        let ready: Vec<(Network, u64)> = self
+            .pending_recoveries
+            .iter()
+            .filter(|(network, _)| {
+                self.syncer_services
+                    .contains_key(&(Coin::Bitcoin, **network))
+            })
+            .map(|(network, from_height)| (*network, *from_height))
+            .collect();
+        for (network, from_height) in ready {
+            senders.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                ServiceId::Wallet,
+                Request::RecoverFunds(request::RecoverFunds {
+                    network,
+                    from_height,
+                }),
+            )?;
+            self.pending_recoveries.remove(&network);
+        }
+        Ok(())
+    }
+
+    /// Check the offer against the local offer policy and that its timelocks leave room for a
+    /// safe swap execution under the local swap parameters of its network
+    fn check_offer(&self, offer: &Offer<BtcXmr>) -> Result<(), Error> {
+        if let Some(offer_policy) = &self.config.offer_policy {
+            policy::check_offer(offer_policy, offer)?;
+        }
+        TemporalSafety::new(offer, self.config.get_swap_params(offer.network)).valid_params()
+    }
+
+    /// Remove a public offer that is no longer takeable
+    fn withdraw_offer(&mut self, public_offer: &PublicOffer<BtcXmr>) {
+        let id = public_offer.id();
+        self.arb_addrs.remove(&id);
+        self.acc_addrs.remove(&id);
+        self.offer_expiries.remove(&id);
+        self.offer_capacities.remove(&id);
+        self.public_offers.remove(public_offer);
+    }
+
+    /// Account for a taker committing to one of our offers and return the offer addresses,
+    /// withdrawing the offer once it cannot be taken anymore
+    fn take_offer(
+        &mut self,
+        public_offer: &PublicOffer<BtcXmr>,
+    ) -> Option<(bitcoin::Address, monero::Address)> {
+        let id = public_offer.id();
+        let addrs = (
+            self.arb_addrs.get(&id).cloned()?,
+            self.acc_addrs.get(&id).cloned()?,
+        );
+        let exhausted = match self.offer_capacities.get_mut(&id) {
+            Some(capacity) => capacity.take(public_offer.offer.arbitrating_amount.as_sat()),
+            None => true,
+        };
+        if exhausted {
+            info!(
+                "{} {}",
+                "Public offer exhausted:".bright_blue_bold(),
+                id.bright_yellow_bold()
+            );
+            self.withdraw_offer(public_offer);
+        }
+        Some(addrs)
+    }
+
+    /// Withdraw the public offers past their expiry time and stop their listener if unused
+    fn remove_expired_offers(&mut self) {
+        let now = SystemTime::now();
+        let expired: Vec<PublicOffer<BtcXmr>> = self
+            .public_offers
+            .iter()
+            .filter(|public_offer| {
+                self.offer_expiries
+                    .get(&public_offer.id())
+                    .map(|expiry| *expiry <= now)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        for public_offer in &expired {
+            info!(
+                "{} {}",
+                "Public offer expired:".bright_blue_bold(),
+                public_offer.id().bright_yellow_bold()
+            );
+            self.withdraw_offer(public_offer);
+        }
+        self.stop_unused_listeners();
+    }
+
+    /// Stop the listeners on which no offer is public or consumed by a running swap
+    fn stop_unused_listeners(&mut self) {
+        let live_offers: HashSet<PublicOfferId> = self
+            .public_offers
+            .iter()
+            .map(|public_offer| public_offer.id())
+            .chain(self.consumed_offers.iter().map(|(offer_id, _)| *offer_id))
+            .collect();
+        self.offer_listens
+            .retain(|offer_id, _| live_offers.contains(offer_id));
+        let used: HashSet<RemoteSocketAddr> = self.offer_listens.values().cloned().collect();
+        let unused: Vec<RemoteSocketAddr> = self
+            .listeners
+            .keys()
+            .filter(|addr| !used.contains(addr))
+            .cloned()
+            .collect();
+        for addr in unused {
+            if let Some(mut child) = self.listeners.remove(&addr) {
+                info!(
+                    "{} on {}, no live offer remains",
+                    "Stopping listener".bright_blue_bold(),
+                    addr.bright_blue_bold()
+                );
+                if let Err(err) = child.kill().and_then(|_| child.wait()) {
+                    warn!("Unable to stop listener on {}: {}", addr, err.err());
+                }
+            }
+            self.listens.remove(&addr);
+        }
+    }
+
+    /// Release the clients following the swap with a failure, the swap ending without outcome
+    fn fail_progress_subscribers(
+        &mut self,
+        senders: &mut esb::SenderList<ServiceBus, ServiceId>,
+        swapid: SwapId,
+        info: String,
+    ) {
+        let identity = self.identity();
+        for subscriber in self
+            .progress_subscribers
+            .remove(&swapid)
+            .unwrap_or_default()
+        {
+            let failure = Request::Failure(Failure {
+                code: 1,
+                info: info.clone(),
+            });
+            if let Err(err) =
+                senders.send_to(ServiceBus::Ctl, identity.clone(), subscriber, failure)
+            {
+                debug!("Cannot notify a follower of swap {}: {}", swapid, err);
+            }
+        }
+    }
+
+    /// Clean up the swaps whose swapd exited without reporting an outcome, failing their
+    /// followers
+    fn reap_exited_swaps(
+        &mut self,
+        senders: &mut esb::SenderList<ServiceBus, ServiceId>,
+    ) -> Result<(), Error> {
+        let exited: Vec<SwapId> = self
+            .swapd_processes
+            .iter_mut()
+            .filter_map(|(swapid, child)| match child.try_wait() {
+                Ok(None) => None,
+                Ok(Some(status)) => {
+                    debug!("swapd of swap {} exited with {}", swapid, status);
+                    Some(*swapid)
+                }
+                Err(err) => {
+                    error!("Cannot check the swapd of swap {}: {}", swapid, err);
+                    Some(*swapid)
+                }
+            })
+            .collect();
+        for swapid in exited {
+            self.swapd_processes.remove(&swapid);
+            if self.swap_outcome(&swapid).is_none() {
+                warn!(
+                    "swapd of swap {} exited without an outcome",
+                    swapid.bright_blue_italic()
+                );
+                self.fail_progress_subscribers(
+                    senders,
+                    swapid,
+                    s!("Swap daemon exited without an outcome"),
+                );
+                self.clean_up_after_swap(&swapid, senders)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn swap_outcome(&self, swapid: &SwapId) -> Option<&Outcome> {
+        self.swap_outcomes
+            .iter()
+            .find(|(id, _)| id == swapid)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// Forward a progress message of the swap to the clients following it, forgetting the
+    /// clients that are gone
+    fn notify_progress_subscribers(
+        &mut self,
+        senders: &mut esb::SenderList<ServiceBus, ServiceId>,
+        swapid: SwapId,
+        info: String,
+    ) {
+        let identity = self.identity();
+        if let Some(subscribers) = self.progress_subscribers.get_mut(&swapid) {
+            subscribers.retain(|subscriber| {
+                senders
+                    .send_to(
+                        ServiceBus::Ctl,
+                        identity.clone(),
+                        subscriber.clone(),
+                        Request::Progress(info.clone()),
+                    )
+                    .is_ok()
+            });
+        }
+    }
+
     fn consumed_offers_contains(&self, offerid: &PublicOfferId) -> bool {
         self.consumed_offers
             .iter()
@@ -283,6 +649,26 @@ impl Runtime {
             Err(Error::Farcaster("Unknown swapd".to_string()))
         }
     }
+    fn handle_bridge(
+        &mut self,
+        senders: &mut esb::SenderList<ServiceBus, ServiceId>,
+        _source: ServiceId,
+        request: Request,
+    ) -> Result<(), Error> {
+        match request {
+            Request::Heartbeat => {
+                // expire offers and stop their listeners on time, not on the next request
+                self.remove_expired_offers();
+                self.stop_unused_listeners();
+                self.reap_exited_swaps(senders)?;
+            }
+            _ => {
+                debug!("bridge request {:?} not handled here", request);
+            }
+        }
+        Ok(())
+    }
+
     fn handle_rpc_msg(
         &mut self,
         senders: &mut esb::SenderList<ServiceBus, ServiceId>,
@@ -303,43 +689,59 @@ impl Runtime {
                 swap_id,
             })) => {
                 let public_offer: PublicOffer<BtcXmr> = FromStr::from_str(public_offer)?;
-                // public offer gets removed on LaunchSwap
-                if !self.public_offers.contains(&public_offer) {
+                self.remove_expired_offers();
+                // public offer gets removed once exhausted by takers, revoked or expired
+                if self
+                    .consumed_offers
+                    .contains(&(public_offer.id(), *swap_id))
+                {
+                    // the taker sends its commit again after a reconnection
+                    debug!(
+                        "Ignoring taker commit to offer {} for already running swap {}",
+                        &public_offer, swap_id
+                    );
+                } else if !self.public_offers.contains(&public_offer) {
                     warn!(
-                        "Unknow offer {}, you are not the maker of that offer, ignoring it",
+                        "Unknow offer {}, you are not the maker of that offer or it was revoked or expired, ignoring it",
                         &public_offer
                     );
-                } else {
+                } else if let Err(err) = self.check_offer(&public_offer.offer) {
+                    let msg = format!("Refusing taker commit to offer {}: {}", &public_offer, err);
+                    warn!("{}", msg.err());
+                    // the taker swapd waits for the maker commit, it is told why it never comes
+                    senders.send_to(
+                        ServiceBus::Msg,
+                        self.identity(),
+                        source,
+                        Request::Protocol(Msg::Abort(Abort {
+                            swap_id: *swap_id,
+                            error_body: Some(msg),
+                        })),
+                    )?;
+                } else if let Some((arb_addr, acc_addr)) = self.take_offer(&public_offer) {
                     trace!(
                         "Offer {} is known, you created it previously, engaging walletd to initiate swap with taker",
                         &public_offer
                     );
-                    if let Some(arb_addr) = self.arb_addrs.remove(&public_offer.id()) {
-                        let btc_addr_req =
-                            Request::BitcoinAddress(BitcoinAddress(*swap_id, arb_addr));
-                        senders.send_to(
-                            ServiceBus::Msg,
-                            self.identity(),
-                            ServiceId::Wallet,
-                            btc_addr_req,
-                        )?;
-                    } else {
-                        error!("missing arb_addr")
-                    }
-                    if let Some(acc_addr) = self.acc_addrs.remove(&public_offer.id()) {
-                        let xmr_addr_req =
-                            Request::MoneroAddress(MoneroAddress(*swap_id, acc_addr));
-                        senders.send_to(
-                            ServiceBus::Msg,
-                            self.identity(),
-                            ServiceId::Wallet,
-                            xmr_addr_req,
-                        )?;
-                    } else {
-                        error!("missing acc_addr")
-                    }
+                    self.consumed_offers.insert((public_offer.id(), *swap_id));
+                    let btc_addr_req = Request::BitcoinAddress(BitcoinAddress(*swap_id, arb_addr));
+                    senders.send_to(
+                        ServiceBus::Msg,
+                        self.identity(),
+                        ServiceId::Wallet,
+                        btc_addr_req,
+                    )?;
+                    let xmr_addr_req = Request::MoneroAddress(MoneroAddress(*swap_id, acc_addr));
+                    senders.send_to(
+                        ServiceBus::Msg,
+                        self.identity(),
+                        ServiceId::Wallet,
+                        xmr_addr_req,
+                    )?;
 
                     senders.send_to(ServiceBus::Msg, source, ServiceId::Wallet, request)?;
+                } else {
+                    error!("missing arb_addr or acc_addr of offer {}", &public_offer);
                 }
                 return Ok(());
             }
@@ -358,6 +760,8 @@ impl Runtime {
         request: Request,
     ) -> Result<(), Error> {
         let mut report_to: Vec<(Option<ServiceId>, Request)> = none!();
+        self.remove_expired_offers();
+        self.reap_exited_swaps(senders)?;
         match request.clone() {
             Request::Hello => {
                 // Ignoring; this is used to set remote identity at ZMQ level
@@ -509,12 +913,49 @@ impl Runtime {
                         ))),
                     ));
                     self.spawning_services.remove(&source);
+                } else if let Some(network) = self.restoring_swaps.get(&source) {
+                    debug!(
+                        "Swapd {} is known: we relaunched it to resume a swap from its checkpoint",
+                        source
+                    );
+                    let swapid = get_swap_id(&source)?;
+                    syncers_up(
+                        &self.syncer_services,
+                        &mut self.syncer_clients,
+                        Coin::Bitcoin,
+                        *network,
+                        swapid,
+                        &self.config,
+                    )?;
+                    syncers_up(
+                        &self.syncer_services,
+                        &mut self.syncer_clients,
+                        Coin::Monero,
+                        *network,
+                        swapid,
+                        &self.config,
+                    )?;
                 }
+
+                // restored swaps resume once both of their syncers are up
+                self.resume_restored_swaps(senders)?;
+                self.resume_recoveries(senders)?;
             }
 
             Request::SwapOutcome(success) => {
                 let swapid = get_swap_id(&source)?;
+                for subscriber in self
+                    .progress_subscribers
+                    .remove(&swapid)
+                    .unwrap_or_default()
+                {
+                    report_to.push((Some(subscriber), Request::SwapOutcome(success.clone())));
+                }
                 self.clean_up_after_swap(&swapid, senders)?;
+                if self.swap_outcomes.len() >= SWAP_OUTCOMES_KEPT {
+                    self.swap_outcomes.pop_front();
+                }
+                self.swap_outcomes.push_back((swapid, success.clone()));
                 match success {
                     Outcome::Buy => {
                         debug!("Success on swap {}", &swapid);
@@ -558,7 +999,14 @@ impl Runtime {
                         return Ok(());
                     }
                 };
-                if self.public_offers.remove(&public_offer) {
+                let known_offer = match local_trade_role {
+                    // the take is accounted for when the taker commits to our offer
+                    TradeRole::Maker => {
+                        self.consumed_offers.contains(&(public_offer.id(), swap_id))
+                    }
+                    TradeRole::Taker => self.public_offers.remove(&public_offer),
+                };
+                if known_offer {
                     trace!(
                         "{}, {}",
                         "launching swapd with swap_id:",
@@ -676,6 +1124,20 @@ impl Runtime {
             //         Request::OfferIdList(self.public_offers.iter().map(|public_offer| public_offer.id()).collect()),
             //     )?;
             // }
+            Request::MakeOffer(request::ProtoPublicOffer { offer, .. })
+            | Request::TakeOffer(request::PubOffer {
+                public_offer: PublicOffer { offer, .. },
+                ..
+            }) if self.check_offer(&offer).is_err() => {
+                let err = self.check_offer(&offer).unwrap_err();
+                let msg = format!("Refusing offer: {}", err);
+                warn!("{}", msg.err());
+                report_to.push((
+                    Some(source.clone()),
+                    Request::Failure(Failure { code: 1, info: msg }),
+                ));
+            }
+
             Request::MakeOffer(request::ProtoPublicOffer {
                 offer,
                 public_addr,
@@ -683,6 +1145,9 @@ impl Runtime {
                 peer_secret_key,
                 arbitrating_addr,
                 accordant_addr,
+                expiry,
+                max_takes,
+                liquidity_cap,
             }) => {
                 let resp = match (self.listens.contains(&bind_addr), peer_secret_key) {
                     (false, None) => {
@@ -716,13 +1181,12 @@ impl Runtime {
                     }
                 }
                 report_to.push((
-                        Some(source.clone()),
-                        resp.into_progress_or_failure()
-                        // Request::Progress(format!(
-                        //     "Node {} listens for connections on {}",
-                        //     self.node_id, remote_addr
-                        // )),
-                    ));
+                    Some(source.clone()),
+                    resp.into_progress_or_failure(), // Request::Progress(format!(
+                                                     //     "Node {} listens for connections on {}",
+                                                     //     self.node_id, remote_addr
+                                                     // )),
+                ));
 
                 let node_ids = self.node_ids();
                 if node_ids.len() != 1 {
@@ -751,6 +1215,20 @@ impl Runtime {
                     self.arb_addrs.insert(pub_offer_id, arbitrating_addr);
                     self.acc_addrs
                         .insert(pub_offer_id, monero::Address::from_str(&accordant_addr)?);
+                    self.offer_listens.insert(pub_offer_id, bind_addr);
+                    self.offer_capacities.insert(
+                        pub_offer_id,
+                        OfferCapacity {
+                            takes_left: max_takes,
+                            liquidity_left: liquidity_cap,
+                        },
+                    );
+                    if let Some(expiry) = expiry {
+                        self.offer_expiries.insert(
+                            pub_offer_id,
+                            SystemTime::now() + Duration::from_secs(expiry),
+                        );
+                    }
                 } else {
                     let msg = "This Public offer was previously registered";
                     warn!("{}", msg.err());
@@ -764,6 +1242,38 @@ impl Runtime {
                 }
             }
 
+            Request::RevokeOffer(offer_id) => {
+                let public_offer = self
+                    .public_offers
+                    .iter()
+                    .find(|public_offer| public_offer.id() == offer_id)
+                    .cloned();
+                let resp = match public_offer {
+                    Some(public_offer) => {
+                        self.withdraw_offer(&public_offer);
+                        self.stop_unused_listeners();
+                        info!(
+                            "{} {}",
+                            "Public offer revoked:".bright_blue_bold(),
+                            offer_id.bright_yellow_bold()
+                        );
+                        Request::Success(OptionDetails(Some(format!(
+                            "Public offer {} revoked",
+                            offer_id
+                        ))))
+                    }
+                    None => {
+                        let msg = format!(
+                            "Unknown offer {}, it was never made, already taken, revoked or expired",
+                            offer_id
+                        );
+                        warn!("{}", msg.err());
+                        Request::Failure(Failure { code: 1, info: msg })
+                    }
+                };
+                report_to.push((Some(source.clone()), resp));
+            }
+
             Request::TakeOffer(request::PubOffer {
                 public_offer,
                 external_address,
@@ -863,12 +1373,131 @@ impl Runtime {
                 }
             }
 
+            Request::ListOrphanWallets(request::ListOrphanWallets { network })
+                if matches!(source, ServiceId::Client(_)) =>
+            {
+                if self.syncer_services.contains_key(&(Coin::Monero, network)) {
+                    let clients = self.orphan_wallet_requests.entry(network).or_default();
+                    clients.push(source);
+                    // a single request is pending on the syncer for all the waiting clients
+                    if clients.len() == 1 {
+                        senders.send_to(
+                            ServiceBus::Ctl,
+                            self.identity(),
+                            ServiceId::Syncer(Coin::Monero, network),
+                            request,
+                        )?;
+                    }
+                } else {
+                    let msg = format!(
+                        "No Monero syncer running on {}, its wallets are only tracked while swaps run",
+                        network
+                    );
+                    report_to.push((
+                        Some(source.clone()),
+                        Request::Failure(Failure { code: 1, info: msg }),
+                    ));
+                }
+            }
+
+            Request::WalletList(list) => {
+                if let ServiceId::Syncer(Coin::Monero, network) = source {
+                    for client in self
+                        .orphan_wallet_requests
+                        .remove(&network)
+                        .unwrap_or_default()
+                    {
+                        senders.send_to(
+                            ServiceBus::Ctl,
+                            self.identity(),
+                            client,
+                            Request::WalletList(list.clone()),
+                        )?;
+                    }
+                }
+            }
+
+            Request::RecoverFunds(request::RecoverFunds {
+                network,
+                from_height,
+            }) if matches!(source, ServiceId::Client(_)) => {
+                // the recovery scan holds the bitcoin syncer as long as farcasterd runs
+                let recovery_id = *self
+                    .recovery_syncers
+                    .entry(network)
+                    .or_insert_with(SwapId::random);
+                syncers_up(
+                    &self.syncer_services,
+                    &mut self.syncer_clients,
+                    Coin::Bitcoin,
+                    network,
+                    recovery_id,
+                    &self.config,
+                )?;
+                self.pending_recoveries.insert(network, from_height);
+                self.resume_recoveries(senders)?;
+                let msg = format!(
+                    "Scanning the funding addresses of the swap wallets for bitcoins to recover \
+                     on {}, check progress with recovery-info. This recovery is partial: \
+                     bitcoins locked in a swap and moneros are only recovered by restoring the \
+                     swap from its checkpoint",
+                    network
+                );
+                report_to.push((
+                    Some(source.clone()),
+                    Request::Success(OptionDetails(Some(msg))),
+                ));
+            }
+
             Request::Progress(..) | Request::Success(..) | Request::Failure(..) => {
                 if !self.progress.contains_key(&source) {
                     self.progress.insert(source.clone(), none!());
                 };
                 let queue = self.progress.get_mut(&source).expect("checked/added above");
-                queue.push_back(request);
+                queue.push_back(request.clone());
+                if let (Ok(swapid), Some(info)) = (get_swap_id(&source), progress_info(&request)) {
+                    self.notify_progress_subscribers(senders, swapid, info);
+                }
+            }
+
+            Request::FollowProgress(swapid) => {
+                let swap_service = ServiceId::Swap(swapid);
+                let known = self.running_swaps.contains(&swapid)
+                    || self.making_swaps.contains_key(&swap_service)
+                    || self.taking_swaps.contains_key(&swap_service)
+                    || self.restoring_swaps.contains_key(&swap_service)
+                    || self.progress.contains_key(&swap_service)
+                    || self.swap_outcome(&swapid).is_some();
+                if known {
+                    // replay the progress made so far before streaming the next updates
+                    let past_progress = self
+                        .progress
+                        .get(&swap_service)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(progress_info);
+                    for info in past_progress {
+                        report_to.push((Some(source.clone()), Request::Progress(info)));
+                    }
+                    match self.swap_outcome(&swapid) {
+                        Some(outcome) => report_to
+                            .push((Some(source.clone()), Request::SwapOutcome(outcome.clone()))),
+                        None => {
+                            self.progress_subscribers
+                                .entry(swapid)
+                                .or_default()
+                                .insert(source.clone());
+                        }
+                    }
+                } else {
+                    report_to.push((
+                        Some(source.clone()),
+                        Request::Failure(Failure {
+                            code: 1,
+                            info: s!("Unknown swapd"),
+                        }),
+                    ));
+                }
             }
 
             Request::ReadProgress(swapid) => {
@@ -949,6 +1578,7 @@ impl Runtime {
             )?;
             let msg = format!("New instance of peerd launched with PID {}", child.id());
             debug!("{}", msg);
+            self.listeners.insert(*addr, child);
             Ok(msg)
         } else {
             Err(Error::Other(s!(
@@ -1054,6 +1684,16 @@ fn syncers_up(
     Ok(())
 }
 
+/// Message of a progress report sent by a swap
+fn progress_info(request: &Request) -> Option<String> {
+    match request {
+        Request::Progress(info)
+        | Request::Success(OptionDetails(Some(info)))
+        | Request::Failure(Failure { info, .. }) => Some(info.clone()),
+        _ => None,
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn launch_swapd(
     runtime: &mut Runtime,
@@ -1067,16 +1707,7 @@ fn launch_swapd(
     funding_address: Option<bitcoin::Address>,
 ) -> Result<String, Error> {
     debug!("Instantiating swapd...");
-    let child = launch(
-        "swapd",
-        &[
-            swap_id.to_hex(),
-            public_offer.to_string(),
-            local_trade_role.to_string(),
-        ],
-    )?;
-    let msg = format!("New instance of swapd launched with PID {}", child.id());
-    debug!("{}", msg);
+    let msg = spawn_swapd(runtime, swap_id, &public_offer, local_trade_role, false)?;
 
     let list = match local_trade_role {
         TradeRole::Taker => &mut runtime.taking_swaps,
@@ -1102,21 +1733,108 @@ fn launch_swapd(
     Ok(msg)
 }
 
+/// Relaunch swapd for a swap found in the checkpoint directory; once connected it reloads its
+/// state from the checkpoint and waits for its syncers before resuming.
+fn restore_swapd(
+    runtime: &mut Runtime,
+    swap_id: SwapId,
+    public_offer: PublicOffer<BtcXmr>,
+    local_trade_role: TradeRole,
+) -> Result<String, Error> {
+    info!(
+        "{} swap {} from checkpoint",
+        "Restoring".bright_green_bold(),
+        swap_id.bright_blue_italic()
+    );
+    let msg = spawn_swapd(runtime, swap_id, &public_offer, local_trade_role, true)?;
+    runtime.consumed_offers.insert((public_offer.id(), swap_id));
+    runtime
+        .restoring_swaps
+        .insert(ServiceId::Swap(swap_id), public_offer.offer.network);
+    Ok(msg)
+}
+
+fn spawn_swapd(
+    runtime: &mut Runtime,
+    swap_id: SwapId,
+    public_offer: &PublicOffer<BtcXmr>,
+    local_trade_role: TradeRole,
+    restore: bool,
+) -> Result<String, Error> {
+    let swap_params = runtime.config.get_swap_params(public_offer.offer.network);
+    let mut args = vec![
+        swap_id.to_hex(),
+        public_offer.to_string(),
+        local_trade_role.to_string(),
+        s!("--btc-finality-thr"),
+        swap_params.btc_finality_thr.to_string(),
+        s!("--race-thr"),
+        swap_params.race_thr.to_string(),
+        s!("--xmr-finality-thr"),
+        swap_params.xmr_finality_thr.to_string(),
+        s!("--sweep-monero-thr"),
+        swap_params.sweep_monero_thr.to_string(),
+    ];
+    if restore {
+        args.push(s!("--restore"));
+    }
+    let child = launch("swapd", args)?;
+    let msg = format!("New instance of swapd launched with PID {}", child.id());
+    debug!("{}", msg);
+    runtime.swapd_processes.insert(swap_id, child);
+    Ok(msg)
+}
+
 /// Return the list of needed arguments for a syncer given a config and a network.
 /// This function only register the minimal set of URLs needed for the blockchain to work.
 fn syncer_servers_args(config: &Config, coin: Coin, net: Network) -> Result<Vec<String>, Error> {
     match config.get_syncer_servers(net) {
         Some(servers) => match coin {
             Coin::Bitcoin => Ok(vec![
-                "--electrum-server".to_string(),
-                servers.electrum_server,
-            ]),
-            Coin::Monero => Ok(vec![
-                "--monero-daemon".to_string(),
-                servers.monero_daemon,
-                "--monero-rpc-wallet".to_string(),
-                servers.monero_rpc_wallet,
-            ]),
+                (
+                    "--bitcoin-backend",
+                    servers.bitcoin_backend.map(|backend| backend.to_string()),
+                ),
+                ("--electrum-server", servers.electrum_server),
+                ("--esplora-server", servers.esplora_server),
+                ("--bitcoind-rpc", servers.bitcoind_rpc),
+                ("--bitcoind-rpc-cookie", servers.bitcoind_rpc_cookie),
+                ("--bitcoind-rpc-user", servers.bitcoind_rpc_user),
+                (
+                    "--bitcoind-rpc-password-file",
+                    servers.bitcoind_rpc_password_file,
+                ),
+            ]
+            .into_iter()
+            // fallback electrum servers follow the main one by decreasing priority
+            .chain(
+                servers
+                    .electrum_fallback_servers
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|server| ("--electrum-server", Some(server))),
+            )
+            .filter_map(|(arg, value)| value.map(|value| vec![arg.to_string(), value]))
+            .flatten()
+            .collect()),
+            Coin::Monero => {
+                let mut args = vec![
+                    "--monero-daemon".to_string(),
+                    servers.monero_daemon,
+                    "--monero-rpc-wallet".to_string(),
+                    servers.monero_rpc_wallet,
+                ];
+                for rpc_wallet in servers.monero_rpc_wallet_pool.unwrap_or_default() {
+                    args.extend(vec!["--monero-rpc-wallet".to_string(), rpc_wallet]);
+                }
+                if let Some(backend) = servers.monero_backend {
+                    args.extend(vec!["--monero-backend".to_string(), backend]);
+                }
+                if let Some(wallet_dir) = servers.monero_wallet_dir {
+                    args.extend(vec!["--monero-wallet-dir".to_string(), wallet_dir]);
+                }
+                Ok(args)
+            }
         },
         None => Err(SyncerError::InvalidConfig.into()),
     }
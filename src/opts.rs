@@ -126,6 +126,38 @@ impl FromStr for TokenString {
     }
 }
 
+/// Format of the responses printed by the command-line tool
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display)]
+pub enum OutputFormat {
+    /// Machine-readable JSON, one document per response
+    #[display("json")]
+    Json,
+
+    /// Machine-readable YAML
+    #[display("yaml")]
+    Yaml,
+
+    /// Human-readable text
+    #[display("text")]
+    Text,
+}
+
+impl FromStr for OutputFormat {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "text" => Ok(OutputFormat::Text),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown output format {}, use json, yaml or text", s),
+            )),
+        }
+    }
+}
+
 impl Opts {
     pub fn process(&mut self) {
         LogLevel::from_verbosity_flag_count(self.verbose).apply();
@@ -135,6 +167,7 @@ impl Opts {
             shellexpand::tilde(&me.data_dir.to_string_lossy().to_string()).to_string(),
         );
         fs::create_dir_all(&me.data_dir).expect("Unable to access data directory");
+        self.data_dir = me.data_dir.clone();
 
         for s in vec![&mut self.msg_socket, &mut self.ctl_socket] {
             match s {
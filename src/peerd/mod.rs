@@ -18,4 +18,4 @@ mod runtime;
 
 #[cfg(feature = "shell")]
 pub use opts::{Opts, PeerKeyOpts};
-pub use runtime::run;
+pub use runtime::{run, Reconnect};
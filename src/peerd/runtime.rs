@@ -12,25 +12,70 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::{mpsc, Arc};
 use std::time::{Duration, SystemTime};
-use std::{collections::HashMap, sync::Arc};
-use std::{rc::Rc, thread::spawn};
+use std::{rc::Rc, thread::sleep, thread::spawn};
 
 use amplify::Bipolar;
 use bitcoin::secp256k1::rand::{self, Rng};
 use bitcoin::secp256k1::PublicKey;
 use internet2::{addr::InetSocketAddr, CreateUnmarshaller, Unmarshall, Unmarshaller};
 use internet2::{presentation, transport, zmqsocket, NodeAddr, TypedEnum, ZmqType, ZMQ_CONTEXT};
+use internet2::{LocalNode, RemoteNodeAddr};
 use lnp::{message, Messages};
 use microservices::esb::{self, Handler};
 use microservices::node::TryService;
-use microservices::peer::{self, PeerConnection, PeerSender, SendMessage};
+use microservices::peer::{self, PeerConnection, PeerReceiver, PeerSender, SendMessage};
 
 use crate::rpc::{
     request::{self, Msg, PeerInfo, TakeCommit, Token},
     Request, ServiceBus,
 };
 use crate::{CtlServer, Error, LogStyle, Service, ServiceConfig, ServiceId};
+use farcaster_core::swap::SwapId;
+
+/// Delay before the first reconnection attempt, doubled after each failure
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Longest delay between two reconnection attempts
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(300);
+/// Number of reconnection attempts before giving up on the remote peer, about an hour
+const RECONNECT_MAX_ATTEMPTS: usize = 20;
+
+/// Delays before each reconnection attempt
+fn reconnect_delays() -> impl Iterator<Item = Duration> {
+    std::iter::successors(Some(RECONNECT_DELAY), |delay| {
+        Some((*delay * 2).min(RECONNECT_MAX_DELAY))
+    })
+    .take(RECONNECT_MAX_ATTEMPTS)
+}
+
+/// Remote peer to connect again to when the connection drops, only known to the connecting side
+pub struct Reconnect {
+    pub remote_node_addr: RemoteNodeAddr,
+    pub local_node: LocalNode,
+}
+
+impl Reconnect {
+    /// Connect to the remote peer, retrying with a growing delay up to
+    /// `RECONNECT_MAX_ATTEMPTS` times
+    fn connect(&self) -> Option<PeerConnection> {
+        for delay in reconnect_delays() {
+            sleep(delay);
+            info!(
+                "{} to {}",
+                "Reconnecting".bright_blue_bold(),
+                self.remote_node_addr.addr()
+            );
+            match PeerConnection::connect(self.remote_node_addr.clone(), &self.local_node) {
+                Ok(connection) => return Some(connection),
+                Err(err) => warn!("Failed to reconnect to the remote peer: {}", err),
+            }
+        }
+        None
+    }
+}
 
 #[allow(clippy::too_many_arguments)]
 pub fn run(
@@ -42,6 +87,7 @@ pub fn run(
     local_socket: Option<InetSocketAddr>,
     remote_socket: InetSocketAddr,
     connect: bool,
+    reconnect: Option<Reconnect>,
 ) -> Result<(), Error> {
     debug!("Splitting connection into receiver and sender parts");
     let (receiver, sender) = connection.split();
@@ -55,24 +101,21 @@ pub fn run(
     let identity = ServiceId::Peer(id);
 
     debug!("Starting thread listening for messages from the remote peer");
-    let bridge_handler = ListenerRuntime {
-        identity: identity.clone(),
-        bridge: esb::Controller::with(
-            map! {
-                ServiceBus::Bridge => esb::BusConfig {
-                    carrier: zmqsocket::Carrier::Socket(tx),
-                    router: None,
-                    queued: true,
-                }
-            },
-            BridgeHandler,
-            ZmqType::Rep,
-        )?,
-    };
-    let unmarshaller: Unmarshaller<Msg> = Msg::create_unmarshaller();
-    let listener =
-        peer::Listener::<ListenerRuntime, Msg>::with(receiver, bridge_handler, unmarshaller);
-    spawn(move || listener.run_or_panic("peerd-listener"));
+    let bridge = esb::Controller::with(
+        map! {
+            ServiceBus::Bridge => esb::BusConfig {
+                carrier: zmqsocket::Carrier::Socket(tx),
+                router: None,
+                queued: true,
+            }
+        },
+        BridgeHandler,
+        ZmqType::Rep,
+    )?;
+    let incoming = reconnect.is_none();
+    let (sender_tx, sender_rx) = mpsc::channel();
+    let listener_identity = identity.clone();
+    spawn(move || listen(receiver, bridge, listener_identity, reconnect, sender_tx));
     // TODO: Use the handle returned by spawn to track the child process
 
     debug!("Starting main service runtime");
@@ -84,7 +127,11 @@ pub fn run(
         remote_socket,
         routing: empty!(),
         sender,
+        reconnected_senders: sender_rx,
         connect,
+        incoming,
+        disconnected: false,
+        swaps: none!(),
         started: SystemTime::now(),
         messages_sent: 0,
         messages_received: 0,
@@ -125,9 +172,68 @@ impl esb::Handler<ServiceBus> for BridgeHandler {
     }
 }
 
+type Bridge = Rc<RefCell<esb::Controller<ServiceBus, Request, BridgeHandler>>>;
+
+/// Listen to the remote peer messages until the connection drops. The connecting side then
+/// connects again and hands the new sender over to the runtime, while the listening side stops
+/// and waits for the remote peer to connect again to a new peerd.
+fn listen(
+    mut receiver: PeerReceiver,
+    bridge: esb::Controller<ServiceBus, Request, BridgeHandler>,
+    identity: ServiceId,
+    reconnect: Option<Reconnect>,
+    sender_tx: mpsc::Sender<PeerSender>,
+) {
+    let bridge: Bridge = Rc::new(RefCell::new(bridge));
+    let notify = |request: Request| {
+        if let Err(err) = bridge
+            .borrow_mut()
+            .send_to(ServiceBus::Bridge, identity.clone(), request)
+        {
+            error!("Failed to notify the runtime over the bridge: {}", err);
+        }
+    };
+    loop {
+        let bridge_handler = ListenerRuntime {
+            identity: identity.clone(),
+            bridge: Rc::clone(&bridge),
+        };
+        let unmarshaller: Unmarshaller<Msg> = Msg::create_unmarshaller();
+        let listener =
+            peer::Listener::<ListenerRuntime, Msg>::with(receiver, bridge_handler, unmarshaller);
+        if let Err(err) = listener.try_run_loop() {
+            warn!("Connection with the remote peer lost: {}", err);
+        }
+        notify(Request::PeerDisconnected);
+
+        let reconnect = match &reconnect {
+            Some(reconnect) => reconnect,
+            None => return,
+        };
+        let (new_receiver, sender) = match reconnect.connect() {
+            Some(connection) => connection.split(),
+            None => {
+                // the swaps go on without the counterparty, towards their cancel and refund
+                error!(
+                    "{} after {} attempts, stopping",
+                    "Remote peer unreachable".err(),
+                    RECONNECT_MAX_ATTEMPTS
+                );
+                std::process::exit(1);
+            }
+        };
+        if sender_tx.send(sender).is_err() {
+            error!("Peer runtime is gone, stopping the listener");
+            return;
+        }
+        notify(Request::PeerReconnected);
+        receiver = new_receiver;
+    }
+}
+
 pub struct ListenerRuntime {
     identity: ServiceId,
-    bridge: esb::Controller<ServiceBus, Request, BridgeHandler>,
+    bridge: Bridge,
 }
 
 impl ListenerRuntime {
@@ -137,7 +243,7 @@ impl ListenerRuntime {
         req: <Unmarshaller<Msg> as Unmarshall>::Data,
     ) -> Result<(), Error> {
         debug!("Forwarding FWP message over BRIDGE interface to the runtime");
-        self.bridge.send_to(
+        self.bridge.borrow_mut().send_to(
             ServiceBus::Bridge,
             self.identity.clone(),
             Request::Protocol((&*req).clone()),
@@ -169,10 +275,10 @@ impl peer::Handler<Msg> for ListenerRuntime {
                 self.send_over_bridge(Arc::new(Msg::PingPeer))?;
                 Ok(())
             }
-            // for all other error types, indicating internal errors, we
+            // for all other error types, indicating a broken connection, we
             // propagate error to the upper level
             _ => {
-                error!("Unrecoverable peer error {}, halting", err);
+                error!("Unrecoverable peer error {}, dropping the connection", err);
                 Err(err)
             }
         }
@@ -188,7 +294,14 @@ pub struct Runtime {
 
     routing: HashMap<ServiceId, ServiceId>,
     sender: PeerSender,
+    /// Senders of the connections re-established by the listener thread
+    reconnected_senders: mpsc::Receiver<PeerSender>,
     connect: bool,
+    /// Whether the remote peer connected to us, it is then up to the remote peer to reconnect
+    incoming: bool,
+    disconnected: bool,
+    /// Swaps whose messages went through this connection
+    swaps: HashSet<SwapId>,
 
     started: SystemTime,
     messages_sent: usize,
@@ -283,6 +396,12 @@ impl Runtime {
                     "Forwarding peer message to the remote peer, request: {}",
                     &request.get_type()
                 );
+                self.swaps.insert(message.swap_id());
+                if self.disconnected {
+                    // the swap sends it again once the connection is re-established
+                    warn!("Remote peer disconnected, dropping message {}", message);
+                    return Ok(());
+                }
                 self.messages_sent += 1;
                 self.sender.send_message(message)?;
             }
@@ -326,7 +445,7 @@ impl Runtime {
                         .as_secs(),
                     messages_sent: self.messages_sent,
                     messages_received: self.messages_received,
-                    connected: !self.connect,
+                    connected: !self.connect && !self.disconnected,
                     awaits_pong: self.awaited_pong.is_some(),
                 };
                 self.send_ctl(senders, source, Request::PeerInfo(info))?;
@@ -353,6 +472,37 @@ impl Runtime {
         }
 
         match &request {
+            Request::PeerDisconnected if self.incoming => {
+                // the remote peer reconnects to the listener, spawning a new peerd under the
+                // same identity
+                warn!(
+                    "{}, stopping until the remote peer connects again",
+                    "Remote peer disconnected".err()
+                );
+                std::process::exit(0);
+            }
+
+            Request::PeerDisconnected => {
+                warn!("{}, reconnecting", "Remote peer disconnected".err());
+                self.disconnected = true;
+                self.awaited_pong = None;
+            }
+
+            Request::PeerReconnected => {
+                self.sender = self.reconnected_senders.try_recv().map_err(|_| {
+                    Error::Other(s!("Reconnected without a connection to the remote peer"))
+                })?;
+                self.disconnected = false;
+                info!(
+                    "{} with the remote peer",
+                    "Connection re-established".bright_green_bold()
+                );
+                // the messages sent while the connection was down may be lost
+                for swap_id in self.swaps.clone() {
+                    self.send_ctl(senders, ServiceId::Swap(swap_id), Request::PeerReconnected)?;
+                }
+            }
+
             Request::Protocol(Msg::PingPeer) => self.ping()?,
 
             Request::Protocol(Msg::Ping(message::Ping { pong_size, .. })) => {
@@ -371,7 +521,8 @@ impl Runtime {
             }
 
             // swap initiation message
-            Request::Protocol(Msg::TakerCommit(_)) => {
+            Request::Protocol(msg @ Msg::TakerCommit(_)) => {
+                self.swaps.insert(msg.swap_id());
                 senders.send_to(
                     ServiceBus::Msg,
                     self.identity(),
@@ -380,12 +531,23 @@ impl Runtime {
                 )?;
             }
             Request::Protocol(msg) => {
+                let swap_id = msg.swap_id();
                 senders.send_to(
                     ServiceBus::Msg,
                     self.identity(),
-                    ServiceId::Swap(msg.swap_id()),
+                    ServiceId::Swap(swap_id),
                     request,
                 )?;
+                // a running swap we know nothing about: the remote peer reconnected to this new
+                // peerd, the swap sends its last message again in case it was lost
+                if self.swaps.insert(swap_id) && self.incoming {
+                    info!(
+                        "{} for swap {}",
+                        "Remote peer reconnected".bright_green_bold(),
+                        swap_id.bright_blue_italic()
+                    );
+                    self.send_ctl(senders, ServiceId::Swap(swap_id), Request::PeerReconnected)?;
+                }
             }
             // }
             // Request::PeerMessage(Messages::OpenChannel(_)) => {
@@ -491,3 +653,18 @@ impl Runtime {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_delays_are_bounded() {
+        let delays: Vec<Duration> = reconnect_delays().collect();
+        assert_eq!(delays.len(), RECONNECT_MAX_ATTEMPTS);
+        assert_eq!(delays[0], RECONNECT_DELAY);
+        assert_eq!(delays[1], RECONNECT_DELAY * 2);
+        assert!(delays.iter().all(|delay| *delay <= RECONNECT_MAX_DELAY));
+        assert_eq!(delays.last(), Some(&RECONNECT_MAX_DELAY));
+    }
+}
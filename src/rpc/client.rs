@@ -16,10 +16,13 @@ use std::convert::TryInto;
 use std::thread::sleep;
 use std::time::Duration;
 
+use serde_json::json;
+
 use internet2::ZmqType;
 use microservices::esb;
 
-use crate::rpc::request::OptionDetails;
+use crate::opts::OutputFormat;
+use crate::rpc::request::{OptionDetails, Outcome, SeedMnemonic};
 use crate::rpc::{Request, ServiceBus};
 use crate::service::ServiceConfig;
 use crate::{Error, LogStyle, ServiceId};
@@ -28,6 +31,7 @@ use crate::{Error, LogStyle, ServiceId};
 pub struct Client {
     identity: ServiceId,
     response_queue: std::collections::VecDeque<Request>,
+    format: OutputFormat,
     esb: esb::Controller<ServiceBus, Request, Handler>,
 }
 
@@ -58,6 +62,7 @@ impl Client {
         Ok(Self {
             identity,
             response_queue: empty!(),
+            format: OutputFormat::Text,
             esb,
         })
     }
@@ -66,6 +71,46 @@ impl Client {
         self.identity.clone()
     }
 
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    /// Print a response in the output format, free text responses are wrapped in a
+    /// `progress`, `success`, `failure` or `response` field in the machine-readable formats
+    pub fn print_response(&self, resp: &Request) -> Result<(), Error> {
+        match self.format {
+            OutputFormat::Text => println!("{:#}", resp),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string(&response_value(resp)?)
+                    .map_err(|err| Error::Other(err.to_string()))?
+            ),
+            OutputFormat::Yaml => print!(
+                "{}",
+                serde_yaml::to_string(&response_value(resp)?)
+                    .map_err(|err| Error::Other(err.to_string()))?
+            ),
+        }
+        Ok(())
+    }
+
+    /// Print an error in the output format
+    pub fn print_error(&self, err: &Error) {
+        match self.format {
+            OutputFormat::Text => eprintln!("{} {}", "error:".err(), err.err()),
+            OutputFormat::Json => println!("{}", json!({ "error": err.to_string() })),
+            OutputFormat::Yaml => print!(
+                "{}",
+                serde_yaml::to_string(&json!({ "error": err.to_string() }))
+                    .unwrap_or_else(|_| format!("error: {}\n", err))
+            ),
+        }
+    }
+
     pub fn request(&mut self, daemon: ServiceId, req: Request) -> Result<(), Error> {
         debug!("Executing {}", req);
         self.esb.send_to(ServiceBus::Ctl, daemon, req)?;
@@ -102,8 +147,7 @@ impl Client {
 
     pub fn report_response(&mut self) -> Result<(), Error> {
         let resp = self.report_failure()?;
-        println!("{:#}", resp);
-        Ok(())
+        self.print_response(&resp)
     }
 
     pub fn report_progress(&mut self) -> Result<usize, Error> {
@@ -114,6 +158,12 @@ impl Client {
             counter += 1;
             match self.report_failure()? {
                 // Failure is already covered by `report_response()`
+                resp @ Request::Progress(_) | resp @ Request::Success(_)
+                    if self.format != OutputFormat::Text =>
+                {
+                    self.print_response(&resp)?;
+                    finished = !matches!(resp, Request::Progress(_));
+                }
                 Request::Progress(info) => {
                     println!("{}", info.green_bold());
                     finished = false;
@@ -132,6 +182,60 @@ impl Client {
         }
         Ok(counter)
     }
+
+    /// Print the progress of a followed swap until its outcome
+    pub fn follow_progress(&mut self) -> Result<Outcome, Error> {
+        loop {
+            match self.report_failure()? {
+                Request::Progress(info) if self.format == OutputFormat::Text => {
+                    println!("{}", info.green_bold())
+                }
+                resp @ Request::Progress(_) => self.print_response(&resp)?,
+                Request::SwapOutcome(outcome) => {
+                    match self.format {
+                        OutputFormat::Text => {
+                            println!("{} {}", "Swap finished:".bright_green_bold(), outcome)
+                        }
+                        _ => self.print_response(&Request::SwapOutcome(outcome.clone()))?,
+                    }
+                    return Ok(outcome);
+                }
+                other => {
+                    eprintln!("{}: {}", "Unexpected report".err(), other.err_details());
+                    return Err(Error::Other(s!("Unexpected server response")));
+                }
+            }
+        }
+    }
+}
+
+/// Machine-readable value of a response, serialized in the json and yaml output formats
+fn response_value(resp: &Request) -> Result<serde_json::Value, Error> {
+    let value = match resp {
+        Request::NodeInfo(info) => serde_json::to_value(info),
+        Request::PeerInfo(info) => serde_json::to_value(info),
+        Request::SwapInfo(info) => serde_json::to_value(info),
+        Request::SyncerInfo(info) => serde_json::to_value(info),
+        Request::RecoveryInfo(info) => serde_json::to_value(info),
+        Request::PeerList(list) => serde_json::to_value(list),
+        Request::SwapList(list) => serde_json::to_value(list),
+        Request::TaskList(list) => serde_json::to_value(list),
+        Request::WalletList(list) => serde_json::to_value(list),
+        Request::OfferList(list) => serde_json::to_value(list),
+        Request::SeedMnemonic(SeedMnemonic {
+            mnemonic,
+            wallet_counter,
+        }) => Ok(json!({ "mnemonic": mnemonic, "wallet_counter": wallet_counter })),
+        Request::TxHex(hex) => Ok(json!({ "tx": hex })),
+        Request::Progress(info) => Ok(json!({ "progress": info })),
+        Request::SwapOutcome(outcome) => Ok(json!({ "outcome": outcome.to_string() })),
+        Request::Success(OptionDetails(info)) => Ok(json!({ "success": info })),
+        Request::Failure(failure) => Ok(json!({
+            "failure": { "code": failure.code, "info": failure.info }
+        })),
+        other => Ok(json!({ "response": format!("{:#}", other) })),
+    };
+    value.map_err(|err| Error::Other(err.to_string()))
 }
 
 pub struct Handler {
@@ -52,7 +52,7 @@ use bitcoin::{
 };
 use farcaster_core::{
     bitcoin::BitcoinSegwitV0,
-    blockchain::FeePriority,
+    blockchain::{FeePriority, Network},
     bundle::{
         AliceParameters, BobParameters, CoreArbitratingTransactions, CosignedArbitratingCancel,
         Proof,
@@ -63,9 +63,10 @@ use farcaster_core::{
         self, CommitAliceParameters, CommitBobParameters, RevealAliceParameters,
         RevealBobParameters, RevealProof,
     },
-    role::TradeRole,
+    role::{SwapRole, TradeRole},
     swap::btcxmr::BtcXmr,
     swap::{Swap, SwapId},
+    transaction::TxLabel,
 };
 use internet2::Api;
 use internet2::{NodeAddr, RemoteSocketAddr};
@@ -227,6 +228,205 @@ pub struct Token(pub String);
 #[display("get keys(token({0}), req_id({1}))")]
 pub struct GetKeys(pub Token, pub RequestId);
 
+#[derive(Clone, StrictEncode, StrictDecode)]
+pub struct ChangePassphrase {
+    pub current: String,
+    pub new: String,
+}
+
+// never leak the passphrases in logs
+impl fmt::Debug for ChangePassphrase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ChangePassphrase(..)")
+    }
+}
+
+impl Display for ChangePassphrase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("change_passphrase(..)")
+    }
+}
+
+#[derive(Clone, StrictEncode, StrictDecode)]
+pub struct ExportSeed {
+    pub passphrase: String,
+}
+
+impl fmt::Debug for ExportSeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ExportSeed(..)")
+    }
+}
+
+impl Display for ExportSeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("export_seed(..)")
+    }
+}
+
+#[derive(Clone, StrictEncode, StrictDecode)]
+pub struct RestoreSeed {
+    pub passphrase: String,
+    pub mnemonic: String,
+    pub wallet_counter: u32,
+}
+
+impl fmt::Debug for RestoreSeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RestoreSeed(.., wallet_counter: {})",
+            self.wallet_counter
+        )
+    }
+}
+
+impl Display for RestoreSeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "restore_seed(.., {})", self.wallet_counter)
+    }
+}
+
+#[derive(Clone, StrictEncode, StrictDecode)]
+pub struct SeedMnemonic {
+    pub mnemonic: String,
+    pub wallet_counter: u32,
+}
+
+impl fmt::Debug for SeedMnemonic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SeedMnemonic(.., wallet_counter: {})",
+            self.wallet_counter
+        )
+    }
+}
+
+impl Display for SeedMnemonic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "seed_mnemonic(.., {})", self.wallet_counter)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display("recover_funds({network}, from {from_height})")]
+pub struct RecoverFunds {
+    pub network: Network,
+    /// Block height the scan of the funding addresses starts from
+    pub from_height: u64,
+}
+
+impl StrictEncode for RecoverFunds {
+    fn strict_encode<E: ::std::io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        let len = self
+            .network
+            .consensus_encode(&mut e)
+            .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))?;
+        Ok(len + self.from_height.strict_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for RecoverFunds {
+    fn strict_decode<D: ::std::io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(Self {
+            network: Network::consensus_decode(&mut d)
+                .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))?,
+            from_height: StrictDecode::strict_decode(&mut d)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display("{network}")]
+pub struct ListOrphanWallets {
+    /// Network of the Monero syncer holding the wallets
+    pub network: Network,
+}
+
+impl StrictEncode for ListOrphanWallets {
+    fn strict_encode<E: ::std::io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        self.network
+            .consensus_encode(&mut e)
+            .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))
+    }
+}
+
+impl StrictDecode for ListOrphanWallets {
+    fn strict_decode<D: ::std::io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(Self {
+            network: Network::consensus_decode(&mut d)
+                .map_err(|e| strict_encoding::Error::DataIntegrityError(e.to_string()))?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("sweep_recovered({address}, {fee_rate} sat/vB)")]
+pub struct SweepRecovered {
+    pub address: bitcoin::Address,
+    /// Fee rate of the sweep transactions in satoshi per virtual byte
+    pub fee_rate: u64,
+}
+
+/// Transaction of a swap broadcasted or exported on request of the operator, checked against
+/// the temporal safety of the swap unless forced
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{swap_id}, {tx_label}, force: {force}")]
+pub struct ManualTx {
+    pub swap_id: SwapId,
+    pub tx_label: TxLabel,
+    pub force: bool,
+}
+
+/// Fee priority within the offer fee strategy used by walletd when creating the arbitrating
+/// transactions of the swap, picked by swapd from the network fee estimation. The fee of the
+/// lock, cancel, refund, buy and punish transactions is fixed once they are signed during the
+/// swap setup, it is not re-estimated at broadcast time.
+#[derive(Clone, PartialEq, Eq, Debug, Display)]
+#[display("set_fee_priority({swap_id}, {priority:?})")]
+pub struct SetFeePriority {
+    pub swap_id: SwapId,
+    pub priority: FeePriority,
+}
+
+impl StrictEncode for SetFeePriority {
+    fn strict_encode<E: ::std::io::Write>(
+        &self,
+        mut e: E,
+    ) -> Result<usize, strict_encoding::Error> {
+        let len = self.swap_id.strict_encode(&mut e)?;
+        let priority: u8 = match self.priority {
+            FeePriority::Low => 0,
+            FeePriority::High => 1,
+        };
+        Ok(len + priority.strict_encode(&mut e)?)
+    }
+}
+
+impl StrictDecode for SetFeePriority {
+    fn strict_decode<D: ::std::io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let swap_id = SwapId::strict_decode(&mut d)?;
+        let priority = match u8::strict_decode(&mut d)? {
+            0 => FeePriority::Low,
+            1 => FeePriority::High,
+            other => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    s!("FeePriority"),
+                    other as usize,
+                ))
+            }
+        };
+        Ok(Self { swap_id, priority })
+    }
+}
+
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
 #[display("launch_swap")]
 pub struct LaunchSwap {
@@ -369,6 +569,18 @@ pub enum Request {
     #[display("swap_success()")]
     SwapOutcome(Outcome),
 
+    #[api(type = 47)]
+    #[display("peer_reconnected()")]
+    PeerReconnected,
+
+    #[api(type = 48)]
+    #[display("peer_disconnected()")]
+    PeerDisconnected,
+
+    #[api(type = 49)]
+    #[display("heartbeat()")]
+    Heartbeat,
+
     #[api(type = 5)]
     #[display("send_message({0})")]
     Protocol(Msg),
@@ -400,6 +612,9 @@ pub enum Request {
     // #[api(type = 105)]
     // #[display("list_offer_ids()")]
     // ListOfferIds,
+    #[api(type = 106)]
+    #[display("list_orphan_wallets({0})")]
+    ListOrphanWallets(ListOrphanWallets),
 
     // Can be issued from `cli` to `lnpd`
     #[api(type = 200)]
@@ -454,6 +669,62 @@ pub enum Request {
     #[display("fund_swap({0})")]
     FundSwap(OutPoint),
 
+    #[api(type = 206)]
+    #[display("restore_swap()")]
+    RestoreSwap,
+
+    #[api(type = 207)]
+    #[display("{0}")]
+    ChangePassphrase(ChangePassphrase),
+
+    #[api(type = 208)]
+    #[display("{0}")]
+    ExportSeed(ExportSeed),
+
+    #[api(type = 209)]
+    #[display("{0}")]
+    RestoreSeed(RestoreSeed),
+
+    #[api(type = 210)]
+    #[display("{0}")]
+    RecoverFunds(RecoverFunds),
+
+    #[api(type = 211)]
+    #[display("get_recovery_info()")]
+    GetRecoveryInfo,
+
+    #[api(type = 212)]
+    #[display("{0}")]
+    SweepRecovered(SweepRecovered),
+
+    #[api(type = 213)]
+    #[display("{0}")]
+    SetFeePriority(SetFeePriority),
+
+    #[api(type = 214)]
+    #[display("bump_fee({0})")]
+    BumpFee(TxLabel),
+
+    #[api(type = 215)]
+    #[display("revoke_offer({0})")]
+    RevokeOffer(PublicOfferId),
+
+    #[api(type = 216)]
+    #[display("follow_progress({0})")]
+    FollowProgress(SwapId),
+
+    #[api(type = 217)]
+    #[display("abort_swap({0})")]
+    AbortSwap(SwapId),
+
+    #[api(type = 218)]
+    #[display("broadcast_tx({0})")]
+    BroadcastTx(ManualTx),
+
+    #[api(type = 219)]
+    #[display("export_tx({0})")]
+    ExportTx(ManualTx),
+
     // Responses to CLI
     // ----------------
     #[api(type = 1002)]
@@ -518,6 +789,23 @@ pub enum Request {
     #[from]
     OfferList(List<PublicOffer<BtcXmr>>),
 
+    #[api(type = 1108)]
+    #[display("{0}")]
+    SeedMnemonic(SeedMnemonic),
+
+    #[api(type = 1109)]
+    #[display("recovery_info({0})", alt = "{0:#}")]
+    #[from]
+    RecoveryInfo(RecoveryInfo),
+
+    #[api(type = 1110)]
+    #[display("tx_hex({0})")]
+    TxHex(String),
+
+    #[api(type = 1111)]
+    #[display("wallet_list({0})", alt = "{0:#}")]
+    WalletList(List<String>),
+
     // #[api(type = 1107)]
     // #[display("offer_list({0})", alt = "{0:#}")]
     // #[from]
@@ -560,6 +848,8 @@ pub enum Outcome {
     Refund,
     #[display("Failure(Punished)")]
     Punish,
+    #[display("Failure(Aborted)")]
+    Abort,
 }
 
 impl rpc_connection::Request for Request {}
@@ -649,6 +939,12 @@ pub struct ProtoPublicOffer {
     pub arbitrating_addr: bitcoin::Address,
     pub accordant_addr: String,
     pub peer_secret_key: Option<SecretKey>,
+    /// Number of seconds after which the offer expires, never if none
+    pub expiry: Option<u64>,
+    /// Number of times the offer can be taken, unlimited if none
+    pub max_takes: Option<u32>,
+    /// Total bitcoin amount in satoshis that can be swapped through the offer, unlimited if none
+    pub liquidity_cap: Option<u64>,
 }
 
 #[cfg_attr(feature = "serde", serde_as)]
@@ -702,9 +998,22 @@ pub type RemotePeerMap<T> = BTreeMap<NodeAddr, T>;
 pub struct SwapInfo {
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub swap_id: Option<SwapId>,
-    // pub state: crate::swapd::State,
-    // #[serde_as(as = "BTreeMap<DisplayFromStr, Same>")]
-    // pub funding_outpoint: OutPoint,
+    /// Current state of the swap, e.g. `AliceState(RefundSig)`
+    pub state: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub trade_role: TradeRole,
+    #[serde_as(as = "DisplayFromStr")]
+    pub swap_role: SwapRole,
+    /// Bitcoin amount in satoshi
+    pub arbitrating_amount: u64,
+    /// Monero amount in piconero
+    pub accordant_amount: u64,
+    pub cancel_timelock: u32,
+    pub punish_timelock: u32,
+    pub bitcoin_height: u64,
+    pub monero_height: u64,
+    pub transactions: Vec<SwapTxInfo>,
+    pub next_deadline: Option<SafetyDeadline>,
     #[serde_as(as = "Vec<DisplayFromStr>")]
     pub maker_peer: Vec<NodeAddr>,
     #[serde_as(as = "DurationSeconds")]
@@ -718,6 +1027,70 @@ pub struct SwapInfo {
     pub remote_keys: BTreeMap<NodeAddr, payment::channel::Keyset>,
 }
 
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(SwapTxInfo::to_yaml_string)]
+pub struct SwapTxInfo {
+    pub label: String,
+    /// Hex id of the transaction, a bitcoin txid or the monero transaction hash of the
+    /// accordant lock
+    pub txid: Option<String>,
+    /// Latest confirmations seen, `None` if the transaction is not on the mempool
+    pub confirmations: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(SafetyDeadline::to_yaml_string)]
+pub struct SafetyDeadline {
+    /// Bitcoin height at which the safe actions of the swap change
+    pub height: u64,
+    pub description: String,
+}
+
+#[cfg_attr(feature = "serde", serde_as)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(RecoveryInfo::to_yaml_string)]
+pub struct RecoveryInfo {
+    /// Number of swap wallets scanned, i.e. the wallet counter at the time of the scan
+    pub scanned_wallets: u32,
+    pub outputs: Vec<RecoverableOutput>,
+}
+
+/// Funding output paying to one of the swap wallets found while scanning the arbitrating chain
+#[cfg_attr(feature = "serde", serde_as)]
+#[derive(Clone, PartialEq, Eq, Debug, Display, StrictEncode, StrictDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(RecoverableOutput::to_yaml_string)]
+pub struct RecoverableOutput {
+    pub wallet_index: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: bitcoin::Address,
+    #[serde_as(as = "DisplayFromStr")]
+    pub outpoint: OutPoint,
+    pub amount: u64,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub sweep_txid: Option<bitcoin::Txid>,
+    pub sweep_error: Option<String>,
+}
+
 #[cfg(feature = "serde")]
 impl ToYamlString for NodeInfo {}
 #[cfg(feature = "serde")]
@@ -725,7 +1098,15 @@ impl ToYamlString for PeerInfo {}
 #[cfg(feature = "serde")]
 impl ToYamlString for SwapInfo {}
 #[cfg(feature = "serde")]
+impl ToYamlString for SwapTxInfo {}
+#[cfg(feature = "serde")]
+impl ToYamlString for SafetyDeadline {}
+#[cfg(feature = "serde")]
 impl ToYamlString for SyncerInfo {}
+#[cfg(feature = "serde")]
+impl ToYamlString for RecoveryInfo {}
+#[cfg(feature = "serde")]
+impl ToYamlString for RecoverableOutput {}
 
 #[derive(Wrapper, Clone, PartialEq, Eq, Debug, From, StrictEncode, StrictDecode)]
 #[wrapper(IndexRange)]
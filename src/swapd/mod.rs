@@ -15,11 +15,12 @@
 #[cfg(feature = "shell")]
 mod opts;
 mod runtime;
-#[allow(dead_code)]
-pub(self) mod storage;
+pub(crate) mod storage;
 
 #[cfg(feature = "shell")]
 pub use opts::Opts;
+pub use runtime::checkpointed_swaps;
 pub use runtime::get_swap_id;
 pub use runtime::run;
 pub use runtime::State;
+pub use runtime::TemporalSafety;
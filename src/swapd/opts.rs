@@ -43,6 +43,28 @@ pub struct Opts {
     #[clap(parse(try_from_str = FromStr::from_str))]
     pub trade_role: TradeRole,
 
+    /// Resume the swap from its last checkpoint stored in the data directory
+    /// instead of starting it from scratch
+    #[clap(long)]
+    pub restore: bool,
+
+    /// Confirmations after which a Bitcoin transaction is considered final
+    #[clap(long, default_value = "0")]
+    pub btc_finality_thr: u32,
+
+    /// Blocks before a timelock expires during which transactions may be raced
+    /// by the counterparty
+    #[clap(long, default_value = "3")]
+    pub race_thr: u32,
+
+    /// Confirmations after which a Monero transaction is considered final
+    #[clap(long, default_value = "0")]
+    pub xmr_finality_thr: u32,
+
+    /// Confirmations of the Monero lock before sweeping it
+    #[clap(long, default_value = "10")]
+    pub sweep_monero_thr: u32,
+
     /// These params can be read also from the configuration file, not just
     /// Command-line args or environment variables
     #[clap(flatten)]
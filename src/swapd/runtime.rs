@@ -16,8 +16,9 @@
 use crate::{
     rpc::request::Outcome,
     syncerd::{
-        opts::Coin, Abort, GetTx, HeightChanged, SweepAddress, SweepAddressAddendum, SweepSuccess,
-        SweepXmrAddress, TaskId, TaskTarget, TransactionRetrieved, WatchHeight, XmrAddressAddendum,
+        opts::Coin, Abort, EstimateFee, FeeEstimated, GetTx, HeightChanged, Reorg, SweepAddress,
+        SweepAddressAddendum, SweepSuccess, SweepXmrAddress, TaskId, TaskTarget,
+        TransactionRetrieved, WatchHeight, XmrAddressAddendum,
     },
 };
 use std::{
@@ -27,11 +28,13 @@ use std::{
 };
 use std::{convert::TryFrom, str::FromStr};
 use std::{
-    io::Cursor,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
     time::{Duration, SystemTime},
 };
 
 use super::storage::{self, Driver};
+use crate::config::SwapParams;
 use crate::rpc::{
     request::{self, Msg},
     Request, ServiceBus,
@@ -60,15 +63,15 @@ use farcaster_core::{
         fee::SatPerVByte, segwitv0::LockTx, segwitv0::SegwitV0, timelock::CSVTimelock, Bitcoin,
         BitcoinSegwitV0,
     },
-    blockchain::{self, FeeStrategy},
+    blockchain::{self, FeePriority, FeeStrategy},
     bundle::{AliceParameters, BobParameters, Proof},
     consensus::{self, Encodable as FarEncodable},
     crypto::{CommitmentEngine, SharedKeyId, TaggedElement},
     monero::{Monero, SHARED_VIEW_KEY_ID},
     negotiation::{Offer, PublicOffer},
     protocol_message::{
-        BuyProcedureSignature, CommitAliceParameters, CommitBobParameters, CoreArbitratingSetup,
-        RefundProcedureSignatures,
+        self, BuyProcedureSignature, CommitAliceParameters, CommitBobParameters,
+        CoreArbitratingSetup, RefundProcedureSignatures,
     },
     role::{Arbitrating, SwapRole, TradeRole},
     swap::btcxmr::BtcXmr,
@@ -83,16 +86,18 @@ use lnpbp::chain::Chain;
 use microservices::esb::{self, Handler};
 use monero::{cryptonote::hash::keccak_256, PrivateKey, ViewPair};
 use request::{Commit, InitSwap, Params, Reveal, TakeCommit, Tx};
+use strict_encoding::{StrictDecode, StrictEncode};
 
 pub fn run(
     config: ServiceConfig,
+    data_dir: PathBuf,
     swap_id: SwapId,
     public_offer: PublicOffer<BtcXmr>,
     local_trade_role: TradeRole,
+    swap_params: SwapParams,
+    restore: bool,
 ) -> Result<(), Error> {
     let Offer {
-        cancel_timelock,
-        punish_timelock,
         maker_role, // SwapRole of maker (Alice or Bob)
         network,
         accordant_amount: monero_amount,
@@ -105,10 +110,9 @@ pub fn run(
     };
 
     let init_state = match local_swap_role {
-        SwapRole::Alice => State::Alice(AliceState::StartA(local_trade_role, public_offer)),
-        SwapRole::Bob => State::Bob(BobState::StartB(local_trade_role, public_offer)),
+        SwapRole::Alice => State::Alice(AliceState::StartA(local_trade_role, public_offer.clone())),
+        SwapRole::Bob => State::Bob(BobState::StartB(local_trade_role, public_offer.clone())),
     };
-    let sweep_monero_thr = 10;
     info!(
         "{}: {}",
         "Starting swap".to_string().bright_green_bold(),
@@ -120,15 +124,7 @@ pub fn run(
         init_state.bright_white_bold()
     );
 
-    let temporal_safety = TemporalSafety {
-        cancel_timelock: cancel_timelock.as_u32(),
-        punish_timelock: punish_timelock.as_u32(),
-        btc_finality_thr: 0,
-        race_thr: 3,
-        xmr_finality_thr: 0,
-        sweep_monero_thr,
-    };
-
+    let temporal_safety = TemporalSafety::new(&public_offer.offer, swap_params);
     temporal_safety.valid_params()?;
     let tasks = SyncerTasks {
         counter: 0,
@@ -137,6 +133,7 @@ pub fn run(
         retrieving_txs: none!(),
         sweeping_addr: none!(),
         txids: none!(),
+        watch_tasks: none!(),
     };
     let syncer_state = SyncerState {
         swap_id,
@@ -150,12 +147,18 @@ pub fn run(
         bitcoin_syncer: ServiceId::Syncer(Coin::Bitcoin, network),
         monero_syncer: ServiceId::Syncer(Coin::Monero, network),
         monero_amount,
+        btc_fee_estimate: None,
+        fee_priority: None,
+        broadcasts: none!(),
+        tx_confs: none!(),
     };
 
-    let runtime = Runtime {
+    let mut runtime = Runtime {
         swap_id,
         identity: ServiceId::Swap(swap_id),
         peer_service: ServiceId::Loopback,
+        public_offer,
+        local_trade_role,
         state: init_state,
         maker_peer: None,
         started: SystemTime::now(),
@@ -165,14 +168,21 @@ pub fn run(
         storage: Box::new(storage::DiskDriver::init(
             swap_id,
             Box::new(storage::DiskConfig {
-                path: Default::default(),
+                path: data_dir.join(storage::CHECKPOINT_DIR),
             }),
         )?),
         pending_requests: none!(),
         txs: none!(),
         local_params: None,
         remote_params: None,
+        aborting: false,
+        lock_broadcast: false,
+        last_peer_msg: None,
+        last_received_peer_msg: None,
     };
+    if restore {
+        runtime.restore_checkpoint()?;
+    }
     let broker = false;
     Service::run(config, runtime, broker)
 }
@@ -183,6 +193,8 @@ pub struct Runtime {
     swap_id: SwapId,
     identity: ServiceId,
     peer_service: ServiceId,
+    public_offer: PublicOffer<BtcXmr>,
+    local_trade_role: TradeRole,
     state: State,
     maker_peer: Option<NodeAddr>,
     started: SystemTime,
@@ -191,13 +203,24 @@ pub struct Runtime {
     temporal_safety: TemporalSafety,
     pending_requests: HashMap<ServiceId, Vec<PendingRequest>>, // FIXME Something more meaningful than ServiceId to index
     txs: HashMap<TxLabel, bitcoin::Transaction>,
-    #[allow(dead_code)]
     storage: Box<dyn storage::Driver>,
     local_params: Option<Params>,  // FIXME this should be removed
     remote_params: Option<Params>, // FIXME this should be removed
+    /// Whether the swap was aborted after the bitcoin lock and only moves towards the cancel and
+    /// refund
+    aborting: bool,
+    /// Whether Bob sent the bitcoin lock transaction to the syncer for broadcast
+    lock_broadcast: bool,
+    /// Last protocol message sent to the counterparty, sent again when the connection with the
+    /// peer is re-established; not checkpointed
+    last_peer_msg: Option<Msg>,
+    /// Encoding of the last protocol message received from the counterparty, to drop it when
+    /// sent again after a reconnection; not checkpointed
+    last_received_peer_msg: Option<Vec<u8>>,
 }
 
-struct TemporalSafety {
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+pub struct TemporalSafety {
     cancel_timelock: BlockHeight,
     punish_timelock: BlockHeight,
     race_thr: BlockHeight,
@@ -209,8 +232,18 @@ struct TemporalSafety {
 type BlockHeight = u32;
 
 impl TemporalSafety {
+    pub fn new(offer: &Offer<BtcXmr>, swap_params: SwapParams) -> Self {
+        TemporalSafety {
+            cancel_timelock: offer.cancel_timelock.as_u32(),
+            punish_timelock: offer.punish_timelock.as_u32(),
+            race_thr: swap_params.race_thr,
+            btc_finality_thr: swap_params.btc_finality_thr,
+            xmr_finality_thr: swap_params.xmr_finality_thr,
+            sweep_monero_thr: swap_params.sweep_monero_thr,
+        }
+    }
     /// check if temporal params are in correct order
-    fn valid_params(&self) -> Result<(), Error> {
+    pub fn valid_params(&self) -> Result<(), Error> {
         let btc_finality = self.btc_finality_thr;
         // let xmr_finality = self.xmr_finality_thr;
         let cancel = self.cancel_timelock;
@@ -225,8 +258,10 @@ impl TemporalSafety {
         {
             Ok(())
         } else {
-            Err(Error::Farcaster(s!(
-                "unsafe and invalid temporal parameters, timelocks, race and tx finality params"
+            Err(Error::Farcaster(format!(
+                "unsafe and invalid temporal parameters, timelocks (cancel {}, punish {}), race \
+                 ({}) and tx finality ({}) params",
+                cancel, punish, race, btc_finality
             )))
         }
     }
@@ -256,8 +291,65 @@ impl TemporalSafety {
         self.final_tx(cancel_confirmations, Coin::Bitcoin)
             && cancel_confirmations >= self.punish_timelock
     }
+    /// a broadcasted transaction still unconfirmed after the race threshold may not confirm
+    /// before the counterparty can race it
+    fn stuck_tx(&self, blocks_unconfirmed: u64) -> bool {
+        blocks_unconfirmed >= self.race_thr as u64
+    }
+    /// a refund still unconfirmed this close to the punish timelock is about to be raced by the
+    /// punish transaction, the margin left to bump it is twice the race threshold
+    fn refund_deadline_close(&self, cancel_confirmations: u32) -> bool {
+        cancel_confirmations.saturating_add(2 * self.race_thr) >= self.punish_timelock
+    }
+    /// next bitcoin height at which the safe actions of the swap change, given the current height
+    /// and the confirmations of the lock and cancel transactions
+    fn next_deadline(
+        &self,
+        height: u64,
+        lock_confirmations: Option<u32>,
+        cancel_confirmations: Option<u32>,
+    ) -> Option<request::SafetyDeadline> {
+        let deadline = |confs: u32, thr: BlockHeight, description: &str| {
+            // confs blocks including the one mining the transaction
+            let mined_height = (height + 1).saturating_sub(confs as u64);
+            request::SafetyDeadline {
+                height: mined_height + thr as u64 - 1,
+                description: description.to_string(),
+            }
+        };
+        match (lock_confirmations, cancel_confirmations) {
+            (_, Some(confs)) if confs > 0 => {
+                if confs <= self.punish_timelock - self.race_thr {
+                    Some(deadline(
+                        confs,
+                        self.punish_timelock - self.race_thr + 1,
+                        "refund no longer safe",
+                    ))
+                } else if confs < self.punish_timelock {
+                    Some(deadline(confs, self.punish_timelock, "punish valid"))
+                } else {
+                    None
+                }
+            }
+            (Some(confs), _) if confs > 0 => {
+                if confs <= self.cancel_timelock - self.race_thr {
+                    Some(deadline(
+                        confs,
+                        self.cancel_timelock - self.race_thr + 1,
+                        "buy no longer safe",
+                    ))
+                } else if confs < self.cancel_timelock {
+                    Some(deadline(confs, self.cancel_timelock, "cancel valid"))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
 }
 
+#[derive(Clone, Debug)]
 struct SyncerTasks {
     counter: u32,
     watched_txs: HashMap<TaskId, TxLabel>,
@@ -266,6 +358,8 @@ struct SyncerTasks {
     sweeping_addr: Option<TaskId>,
     // external address: needed to subscribe for buy (bob) or refund (alice) address_txs
     txids: HashMap<TxLabel, Txid>,
+    // watch tasks sent to the syncers, re-registered when the swap is restored
+    watch_tasks: HashMap<TaskId, (Coin, Task)>,
 }
 
 impl SyncerTasks {
@@ -273,6 +367,160 @@ impl SyncerTasks {
         self.counter += 1;
         TaskId(self.counter)
     }
+    /// Hex hash of the monero transaction watched under the label, if any
+    fn monero_tx_hash(&self, tx_label: TxLabel) -> Option<String> {
+        self.watched_txs
+            .iter()
+            .filter(|(_, label)| **label == tx_label)
+            .find_map(|(id, _)| match self.watch_tasks.get(id) {
+                Some((Coin::Monero, Task::WatchTransaction(WatchTransaction { hash, .. }))) => {
+                    Some(hex::encode(hash))
+                }
+                _ => None,
+            })
+    }
+}
+
+impl StrictEncode for SyncerTasks {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let mut len = self.counter.strict_encode(&mut e)?;
+        len += strict_encode_map(&self.watched_txs, &mut e)?;
+        len += strict_encode_map(&self.watched_addrs, &mut e)?;
+        len += self.retrieving_txs.len().strict_encode(&mut e)?;
+        for (id, (label, task)) in self.retrieving_txs.iter() {
+            len += id.strict_encode(&mut e)?;
+            len += label.strict_encode(&mut e)?;
+            len += task.strict_encode(&mut e)?;
+        }
+        len += self.sweeping_addr.strict_encode(&mut e)?;
+        len += strict_encode_map(&self.txids, &mut e)?;
+        len += self.watch_tasks.len().strict_encode(&mut e)?;
+        for (id, (coin, task)) in self.watch_tasks.iter() {
+            len += id.strict_encode(&mut e)?;
+            len += coin.strict_encode(&mut e)?;
+            len += task.strict_encode(&mut e)?;
+        }
+        Ok(len)
+    }
+}
+
+impl StrictDecode for SyncerTasks {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let counter = u32::strict_decode(&mut d)?;
+        let watched_txs = strict_decode_map(&mut d)?;
+        let watched_addrs = strict_decode_map(&mut d)?;
+        let mut retrieving_txs = HashMap::new();
+        for _ in 0..usize::strict_decode(&mut d)? {
+            let id = TaskId::strict_decode(&mut d)?;
+            let label = TxLabel::strict_decode(&mut d)?;
+            let task = Task::strict_decode(&mut d)?;
+            retrieving_txs.insert(id, (label, task));
+        }
+        let sweeping_addr = Option::<TaskId>::strict_decode(&mut d)?;
+        let txids = strict_decode_map(&mut d)?;
+        let mut watch_tasks = HashMap::new();
+        for _ in 0..usize::strict_decode(&mut d)? {
+            let id = TaskId::strict_decode(&mut d)?;
+            let coin = Coin::strict_decode(&mut d)?;
+            let task = Task::strict_decode(&mut d)?;
+            watch_tasks.insert(id, (coin, task));
+        }
+        Ok(SyncerTasks {
+            counter,
+            watched_txs,
+            watched_addrs,
+            retrieving_txs,
+            sweeping_addr,
+            txids,
+            watch_tasks,
+        })
+    }
+}
+
+/// Everything swapd needs to resume a swap after a restart, written to the
+/// storage driver on each state transition.
+#[derive(Clone, Debug)]
+pub struct CheckpointSwapd {
+    public_offer: PublicOffer<BtcXmr>,
+    local_trade_role: TradeRole,
+    state: State,
+    maker_peer: Option<NodeAddr>,
+    temporal_safety: TemporalSafety,
+    tasks: SyncerTasks,
+    txs: HashMap<TxLabel, bitcoin::Transaction>,
+    local_params: Option<Params>,
+    remote_params: Option<Params>,
+    aborting: bool,
+    lock_broadcast: bool,
+}
+
+impl StrictEncode for CheckpointSwapd {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let mut len = self.public_offer.strict_encode(&mut e)?;
+        len += self.local_trade_role.strict_encode(&mut e)?;
+        len += self.state.strict_encode(&mut e)?;
+        len += self.maker_peer.strict_encode(&mut e)?;
+        len += self.temporal_safety.strict_encode(&mut e)?;
+        len += self.tasks.strict_encode(&mut e)?;
+        len += strict_encode_map(&self.txs, &mut e)?;
+        len += self.local_params.strict_encode(&mut e)?;
+        len += self.remote_params.strict_encode(&mut e)?;
+        len += self.aborting.strict_encode(&mut e)?;
+        len += self.lock_broadcast.strict_encode(&mut e)?;
+        Ok(len)
+    }
+}
+
+impl StrictDecode for CheckpointSwapd {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(CheckpointSwapd {
+            public_offer: StrictDecode::strict_decode(&mut d)?,
+            local_trade_role: StrictDecode::strict_decode(&mut d)?,
+            state: StrictDecode::strict_decode(&mut d)?,
+            maker_peer: StrictDecode::strict_decode(&mut d)?,
+            temporal_safety: StrictDecode::strict_decode(&mut d)?,
+            tasks: StrictDecode::strict_decode(&mut d)?,
+            txs: strict_decode_map(&mut d)?,
+            local_params: StrictDecode::strict_decode(&mut d)?,
+            remote_params: StrictDecode::strict_decode(&mut d)?,
+            aborting: StrictDecode::strict_decode(&mut d)?,
+            lock_broadcast: StrictDecode::strict_decode(&mut d)?,
+        })
+    }
+}
+
+// HashMap keyed by TxLabel cannot use the strict encoding collection impls, which require
+// ordered keys, so maps are written as a length followed by the key-value pairs
+fn strict_encode_map<K, V, E>(
+    map: &HashMap<K, V>,
+    mut e: E,
+) -> Result<usize, strict_encoding::Error>
+where
+    K: StrictEncode,
+    V: StrictEncode,
+    E: io::Write,
+{
+    let mut len = map.len().strict_encode(&mut e)?;
+    for (key, val) in map.iter() {
+        len += key.strict_encode(&mut e)?;
+        len += val.strict_encode(&mut e)?;
+    }
+    Ok(len)
+}
+
+fn strict_decode_map<K, V, D>(mut d: D) -> Result<HashMap<K, V>, strict_encoding::Error>
+where
+    K: StrictDecode + Eq + std::hash::Hash,
+    V: StrictDecode,
+    D: io::Read,
+{
+    let mut map = HashMap::new();
+    for _ in 0..usize::strict_decode(&mut d)? {
+        let key = K::strict_decode(&mut d)?;
+        let val = V::strict_decode(&mut d)?;
+        map.insert(key, val);
+    }
+    Ok(map)
 }
 
 struct SyncerState {
@@ -287,9 +535,26 @@ struct SyncerState {
     bitcoin_syncer: ServiceId,
     monero_syncer: ServiceId,
     monero_amount: monero::Amount,
+    /// Latest network fee rate estimation in satoshi per virtual byte
+    btc_fee_estimate: Option<u64>,
+    /// Fee priority last sent to walletd
+    fee_priority: Option<FeePriority>,
+    /// Cancel, refund and punish transactions broadcasted and not yet mined
+    broadcasts: HashMap<TxLabel, PendingBroadcast>,
+    /// Latest confirmations reported for the watched transactions, `None` if not on the mempool
+    tx_confs: HashMap<TxLabel, Option<u32>>,
 }
 
-#[derive(Display, Clone)]
+#[derive(Clone, Debug)]
+struct PendingBroadcast {
+    tx: bitcoin::Transaction,
+    /// Bitcoin height at the first broadcast of the transaction
+    height: u64,
+    /// Whether walletd was asked to re-sign the transaction with a higher fee
+    bumped: bool,
+}
+
+#[derive(Display, Clone, Debug, StrictEncode, StrictDecode)]
 pub enum AliceState {
     // #[display("Start: {0:#?} {1:#?}")]
     #[display("Start")]
@@ -308,7 +573,7 @@ pub enum AliceState {
 }
 
 /// Content of Commit state Common to Bob and Alice
-#[derive(Clone, Display, Getters)]
+#[derive(Clone, Debug, Display, Getters, StrictEncode, StrictDecode)]
 #[display("{trade_role:#?} {local_params:#?} {local_commit:#?} {remote_commit:#?}")]
 pub struct CommitC {
     trade_role: TradeRole,
@@ -317,7 +582,7 @@ pub struct CommitC {
     remote_commit: Option<Commit>,
 }
 
-#[derive(Clone, Display)]
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
 #[display("{xmr_locked} and {buy_published}")]
 pub struct RefundSigA {
     #[display("xmr_locked({0})")]
@@ -330,7 +595,7 @@ pub struct RefundSigA {
      * local_params: Params */
 }
 
-#[derive(Display, Clone)]
+#[derive(Display, Clone, Debug, StrictEncode, StrictDecode)]
 pub enum BobState {
     // #[display("Start {0:#?} {1:#?}")]
     #[display("Start")]
@@ -350,13 +615,13 @@ pub enum BobState {
     FinishB(Outcome),
 }
 
-#[derive(Display, Clone)]
+#[derive(Display, Clone, Debug, StrictEncode, StrictDecode)]
 #[display("BuySigB(buy_tx_seen({buy_tx_seen}))")]
 pub struct BuySigB {
     buy_tx_seen: bool,
 }
 
-#[derive(Display, Clone)]
+#[derive(Display, Clone, Debug, StrictEncode, StrictDecode)]
 #[display(inner)]
 pub enum State {
     #[display("AliceState({0})")]
@@ -718,6 +983,36 @@ impl SyncerState {
     fn monero_syncer(&self) -> ServiceId {
         self.monero_syncer.clone()
     }
+    fn syncer(&self, coin: Coin) -> ServiceId {
+        match coin {
+            Coin::Bitcoin => self.bitcoin_syncer(),
+            Coin::Monero => self.monero_syncer(),
+        }
+    }
+    /// Re-register the height watchers and all the watch tasks of a restored swap, with a
+    /// lifetime refreshed against the current heights
+    fn restored_tasks(&mut self) -> Vec<(ServiceId, Task)> {
+        let mut tasks = vec![];
+        for coin in [Coin::Bitcoin, Coin::Monero] {
+            let task = Task::WatchHeight(WatchHeight {
+                id: self.tasks.new_taskid(),
+                lifetime: self.task_lifetime(coin),
+            });
+            tasks.push((self.syncer(coin), task));
+        }
+        for (coin, task) in self.tasks.watch_tasks.values() {
+            let mut task = task.clone();
+            match &mut task {
+                Task::WatchTransaction(WatchTransaction { lifetime, .. })
+                | Task::WatchAddress(WatchAddress { lifetime, .. }) => {
+                    *lifetime = self.task_lifetime(*coin)
+                }
+                _ => {}
+            }
+            tasks.push((self.syncer(*coin), task));
+        }
+        tasks
+    }
     fn height(&self, coin: Coin) -> u64 {
         match coin {
             Coin::Bitcoin => self.bitcoin_height,
@@ -736,6 +1031,26 @@ impl SyncerState {
             warn!("block height did not increment, maybe syncer sends multiple events");
         }
     }
+    /// Roll the height back to the fork and forget the last final confirmations seen, they may
+    /// have been disconnected; the syncer re-emits the confirmations against the new best chain
+    /// and the temporal safety decisions are taken again on those.
+    fn handle_reorg(&mut self, fork_height: u64, depth: u64, coin: Coin) {
+        warn!(
+            "{} | {} reorganization of {} block(s) above height {}",
+            self.swap_id.bright_blue_italic(),
+            coin,
+            depth.bright_white_bold(),
+            fork_height
+        );
+        match coin {
+            Coin::Bitcoin => {
+                self.bitcoin_height = fork_height;
+                self.lock_tx_confs = None;
+                self.cancel_tx_confs = None;
+            }
+            Coin::Monero => self.monero_height = fork_height,
+        }
+    }
     fn watch_tx_btc(&mut self, txid: Txid, tx_label: TxLabel) -> Task {
         let id = self.tasks.new_taskid();
         self.tasks.watched_txs.insert(id, tx_label);
@@ -745,12 +1060,16 @@ impl SyncerState {
             tx_label.bright_white_bold(),
             txid.bright_yellow_italic()
         );
-        Task::WatchTransaction(WatchTransaction {
+        let task = Task::WatchTransaction(WatchTransaction {
             id,
             lifetime: self.task_lifetime(Coin::Bitcoin),
             hash: txid.to_vec(),
             confirmation_bound: self.confirmation_bound,
-        })
+        });
+        self.tasks
+            .watch_tasks
+            .insert(id, (Coin::Bitcoin, task.clone()));
+        task
     }
 
     fn watch_tx_xmr(&mut self, hash: Vec<u8>, tx_label: TxLabel) -> Task {
@@ -763,11 +1082,21 @@ impl SyncerState {
             hex::encode(&hash).bright_yellow_italic(),
         );
         debug!("Watching transaction {} with {}", hex::encode(&hash), id);
-        Task::WatchTransaction(WatchTransaction {
+        let task = Task::WatchTransaction(WatchTransaction {
             id,
             lifetime: self.task_lifetime(Coin::Monero),
             hash,
             confirmation_bound: self.confirmation_bound,
+        });
+        self.tasks
+            .watch_tasks
+            .insert(id, (Coin::Monero, task.clone()));
+        task
+    }
+    fn estimate_fee_btc(&mut self, blocks_until_confirmation: u16) -> Task {
+        Task::EstimateFee(EstimateFee {
+            id: self.tasks.new_taskid(),
+            blocks_until_confirmation,
         })
     }
     fn retrieve_tx_btc(&mut self, txid: Txid, tx_label: TxLabel) -> Task {
@@ -796,12 +1125,16 @@ impl SyncerState {
             from_height,
             script_pubkey,
         };
-        Task::WatchAddress(WatchAddress {
+        let task = Task::WatchAddress(WatchAddress {
             id,
             lifetime: self.task_lifetime(Coin::Bitcoin),
             addendum: AddressAddendum::Bitcoin(addendum),
             include_tx: Boolean::True,
-        })
+        });
+        self.tasks
+            .watch_tasks
+            .insert(id, (Coin::Bitcoin, task.clone()));
+        task
     }
 
     fn watch_addr_xmr(
@@ -849,7 +1182,11 @@ impl SyncerState {
             addendum,
             include_tx: Boolean::False,
         };
-        Task::WatchAddress(watch_addr)
+        let task = Task::WatchAddress(watch_addr);
+        self.tasks
+            .watch_tasks
+            .insert(id, (Coin::Monero, task.clone()));
+        task
     }
     fn sweep_xmr(
         &mut self,
@@ -881,8 +1218,12 @@ impl SyncerState {
             .values()
             .any(|&x| x == TxLabel::AccLock)
     }
-    fn handle_tx_confs(&self, id: &TaskId, confirmations: &Option<u32>) {
+    fn handle_tx_confs(&mut self, id: &TaskId, confirmations: &Option<u32>) {
         if let Some(txlabel) = self.tasks.watched_txs.get(id) {
+            if matches!(confirmations, Some(confs) if *confs > 0) {
+                self.broadcasts.remove(txlabel);
+            }
+            self.tx_confs.insert(*txlabel, *confirmations);
             match confirmations {
                 Some(0) => {
                     debug!(
@@ -911,8 +1252,9 @@ impl SyncerState {
     }
 }
 impl Runtime {
-    fn send_peer(&self, senders: &mut Senders, msg: request::Msg) -> Result<(), Error> {
+    fn send_peer(&mut self, senders: &mut Senders, msg: request::Msg) -> Result<(), Error> {
         trace!("sending peer message {}", msg.bright_yellow_bold());
+        self.last_peer_msg = Some(msg.clone());
         senders.send_to(
             ServiceBus::Msg,
             self.identity(),
@@ -939,10 +1281,222 @@ impl Runtime {
         );
         info!("{} | {}", self.swap_id.bright_blue_italic(), &msg);
         self.state = next_state;
+        if let Err(err) = self.checkpoint() {
+            error!(
+                "{} | Failed to checkpoint swap state: {}",
+                self.swap_id.bright_blue_italic(),
+                err.err()
+            );
+        }
         self.report_success_to(senders, self.enquirer.clone(), Some(msg))?;
         Ok(())
     }
 
+    /// Whether the bitcoin lock transaction may be published, after which the swap can only be
+    /// stopped with the cancel and refund transactions
+    fn lock_published(&self) -> bool {
+        match self.state.swap_role() {
+            // Bob locks as soon as he receives the refund procedure signatures
+            SwapRole::Alice => self.state.a_refundsig(),
+            // the lock is broadcast as soon as walletd hands it over, before the state moves on;
+            // the buy procedure signature is deferred and the cancel received right after the lock
+            SwapRole::Bob => {
+                self.lock_broadcast
+                    || self.state.b_buy_sig()
+                    || self.txs.contains_key(&TxLabel::Cancel)
+                    || self.syncer_state.tasks.txids.contains_key(&TxLabel::Buy)
+            }
+        }
+    }
+
+    /// Reason the swap can no longer be aborted, if any
+    fn abort_refusal(&self) -> Option<String> {
+        if self.state.finish() {
+            Some(format!("swap already finished in state {}", self.state))
+        } else if self.state.a_xmr_locked() || self.state.a_buy_published() {
+            Some(s!(
+                "monero already locked, the swap ends with the buy or the cancel"
+            ))
+        } else if self.state.b_buy_sig() {
+            Some(s!(
+                "buy procedure signature already sent, the swap ends with the buy or the cancel"
+            ))
+        } else if self.state.cancel_seen() {
+            Some(s!(
+                "cancel transaction already seen, the swap ends with the refund or the punish"
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Finish the swap before the bitcoin lock, nothing is left to do on-chain
+    fn finish_aborted(&mut self, senders: &mut Senders) -> Result<(), Error> {
+        let abort_all = Task::Abort(Abort {
+            task_target: TaskTarget::AllTasks,
+            respond: Boolean::False,
+        });
+        senders.send_to(
+            ServiceBus::Ctl,
+            self.identity(),
+            self.syncer_state.monero_syncer(),
+            Request::SyncerTask(abort_all.clone()),
+        )?;
+        senders.send_to(
+            ServiceBus::Ctl,
+            self.identity(),
+            self.syncer_state.bitcoin_syncer(),
+            Request::SyncerTask(abort_all),
+        )?;
+        let next_state = match self.state.swap_role() {
+            SwapRole::Alice => State::Alice(AliceState::FinishA(Outcome::Abort)),
+            SwapRole::Bob => State::Bob(BobState::FinishB(Outcome::Abort)),
+        };
+        self.state_update(senders, next_state)?;
+        let swap_outcome_req = Request::SwapOutcome(Outcome::Abort);
+        self.send_wallet(ServiceBus::Ctl, senders, swap_outcome_req.clone())?;
+        self.send_ctl(senders, ServiceId::Farcasterd, swap_outcome_req)?;
+        Ok(())
+    }
+
+    /// Stop moving towards the buy after the bitcoin lock, the cancel and refund transactions
+    /// are broadcast once the cancel timelock expires
+    fn start_aborting(&mut self, senders: &mut Senders) -> Result<String, Error> {
+        self.aborting = true;
+        self.checkpoint()?;
+        // Bob's buy procedure signature waits for the monero lock to be final
+        self.pending_requests
+            .remove(&self.syncer_state.monero_syncer());
+        let msg = format!(
+            "Aborting swap {}, the cancel and refund transactions are broadcast once the cancel \
+             timelock expires",
+            self.swap_id
+        );
+        info!("{} | {}", self.swap_id.bright_blue_italic(), msg);
+        let enquirer = self.enquirer.clone();
+        let _ = self.report_progress_to(senders, &enquirer, msg.clone());
+        Ok(msg)
+    }
+
+    /// Transaction requested by the operator, refused if unknown or, unless forced, if not safe
+    /// to broadcast at the current height
+    fn manual_tx(
+        &self,
+        tx_label: TxLabel,
+        force: bool,
+    ) -> Result<bitcoin::Transaction, microservices::rpc::Failure> {
+        let failure = |info: String| {
+            warn!("{} | {}", self.swap_id.bright_blue_italic(), info);
+            microservices::rpc::Failure { code: 1, info }
+        };
+        // broadcasted transactions are removed from the ones waiting for their broadcast
+        let tx = self
+            .txs
+            .get(&tx_label)
+            .or_else(|| {
+                self.syncer_state
+                    .broadcasts
+                    .get(&tx_label)
+                    .map(|pending| &pending.tx)
+            })
+            .cloned()
+            .ok_or_else(|| failure(format!("No {} transaction known for this swap", tx_label)))?;
+        if force {
+            return Ok(tx);
+        }
+        let mined_confs = |label: TxLabel| {
+            self.syncer_state
+                .tx_confs
+                .get(&label)
+                .copied()
+                .flatten()
+                .filter(|confs| *confs > 0)
+        };
+        let lock_confs = mined_confs(TxLabel::Lock);
+        let cancel_confs = mined_confs(TxLabel::Cancel);
+        let safe = match tx_label {
+            TxLabel::Buy => {
+                !self.state.cancel_seen()
+                    && cancel_confs.is_none()
+                    && lock_confs.map_or(false, |confs| self.temporal_safety.safe_buy(confs))
+            }
+            TxLabel::Cancel => {
+                lock_confs.map_or(false, |confs| self.temporal_safety.valid_cancel(confs))
+            }
+            TxLabel::Refund => {
+                cancel_confs.map_or(false, |confs| self.temporal_safety.safe_refund(confs))
+            }
+            TxLabel::Punish => {
+                cancel_confs.map_or(false, |confs| self.temporal_safety.valid_punish(confs))
+            }
+            _ => false,
+        };
+        if safe {
+            Ok(tx)
+        } else {
+            Err(failure(format!(
+                "{} transaction is not safe to broadcast at height {} (lock confirmations {:?}, \
+                 cancel confirmations {:?}), use --force to override",
+                tx_label, self.syncer_state.bitcoin_height, lock_confs, cancel_confs
+            )))
+        }
+    }
+
+    fn checkpoint(&mut self) -> Result<(), Error> {
+        let checkpoint = CheckpointSwapd {
+            public_offer: self.public_offer.clone(),
+            local_trade_role: self.local_trade_role,
+            state: self.state.clone(),
+            maker_peer: self.maker_peer.clone(),
+            temporal_safety: self.temporal_safety.clone(),
+            tasks: self.syncer_state.tasks.clone(),
+            txs: self.txs.clone(),
+            local_params: self.local_params.clone(),
+            remote_params: self.remote_params.clone(),
+            aborting: self.aborting,
+            lock_broadcast: self.lock_broadcast,
+        };
+        let bytes = strict_encoding::strict_serialize(&checkpoint)?;
+        self.storage.store(&bytes)
+    }
+
+    fn restore_checkpoint(&mut self) -> Result<(), Error> {
+        let bytes = self.storage.load()?.ok_or_else(|| {
+            Error::Farcaster(format!("no checkpoint found for swap {}", self.swap_id))
+        })?;
+        let CheckpointSwapd {
+            state,
+            maker_peer,
+            temporal_safety,
+            tasks,
+            txs,
+            local_params,
+            remote_params,
+            aborting,
+            lock_broadcast,
+            ..
+        } = strict_encoding::strict_deserialize(&bytes)?;
+        info!(
+            "{} | Restored state from checkpoint: {}",
+            self.swap_id.bright_blue_italic(),
+            state.bright_white_bold()
+        );
+        self.state = state;
+        // the counterparty is reached again through the peer connection of its address
+        if let Some(addr) = &maker_peer {
+            self.peer_service = ServiceId::Peer(addr.clone());
+        }
+        self.maker_peer = maker_peer;
+        self.temporal_safety = temporal_safety;
+        self.syncer_state.tasks = tasks;
+        self.txs = txs;
+        self.local_params = local_params;
+        self.remote_params = remote_params;
+        self.aborting = aborting;
+        self.lock_broadcast = lock_broadcast;
+        Ok(())
+    }
+
     fn broadcast(
         &mut self,
         tx: bitcoin::Transaction,
@@ -960,6 +1514,21 @@ impl Runtime {
             tx_label.bright_white_bold(),
             tx.txid().bright_yellow_italic()
         );
+        if matches!(
+            tx_label,
+            TxLabel::Cancel | TxLabel::Refund | TxLabel::Punish
+        ) {
+            // a re-signed transaction replaces the stuck one, it is not bumped again
+            let bumped = self
+                .syncer_state
+                .broadcasts
+                .get(&tx_label)
+                .map_or(false, |pending| pending.bumped);
+            let height = self.syncer_state.height(Coin::Bitcoin);
+            self.syncer_state
+                .broadcasts
+                .insert(tx_label, PendingBroadcast { tx, height, bumped });
+        }
         Ok(senders.send_to(
             ServiceBus::Ctl,
             self.identity(),
@@ -968,6 +1537,101 @@ impl Runtime {
         )?)
     }
 
+    /// Rebroadcast the cancel, refund and punish transactions still unconfirmed after the race
+    /// threshold, they may have been evicted from the mempools, and ask walletd to re-sign them
+    /// with a higher fee where the protocol allows it.
+    ///
+    /// Only the punish transaction can be bumped by the node. The cancel and refund transactions
+    /// keep the fee they were co-signed with during the setup, and walletd holds the key of none
+    /// of their outputs: the cancel output is the shared refund/punish script and the refund pays
+    /// to Bob's external refund address. A stuck refund, and the cancel it spends, can only be
+    /// bumped by spending the refund output with a higher feerate (CPFP) from the wallet of the
+    /// refund address; the user is told so, and the swap reports a failure once the punish
+    /// timelock gets close.
+    fn check_pending_broadcasts(&mut self, senders: &mut Senders) -> Result<(), Error> {
+        let height = self.syncer_state.height(Coin::Bitcoin);
+        let temporal_safety = &self.temporal_safety;
+        let stuck: Vec<(TxLabel, bitcoin::Transaction, u64, bool)> = self
+            .syncer_state
+            .broadcasts
+            .iter_mut()
+            .filter(|(_, pending)| temporal_safety.stuck_tx(height.saturating_sub(pending.height)))
+            .map(|(tx_label, pending)| {
+                // only the punish transaction is signed by a single party, the cancel and refund
+                // transactions are co-signed and can only be bumped through CPFP of the refund
+                let bump = *tx_label == TxLabel::Punish && !pending.bumped;
+                pending.bumped |= bump;
+                (*tx_label, pending.tx.clone(), height - pending.height, bump)
+            })
+            .collect();
+        let cancel_confirmations = self
+            .syncer_state
+            .tx_confs
+            .get(&TxLabel::Cancel)
+            .copied()
+            .flatten();
+        for (tx_label, tx, blocks, bump) in stuck {
+            let msg = format!(
+                "{} transaction {} still unconfirmed after {} blocks, {}",
+                tx_label,
+                tx.txid(),
+                blocks,
+                match tx_label {
+                    _ if bump => s!("re-signing it with a higher fee"),
+                    TxLabel::Cancel => s!("rebroadcasting it, its co-signed fee cannot be raised"),
+                    TxLabel::Refund => format!(
+                        "rebroadcasting it, its co-signed fee cannot be raised: bump it by \
+                         spending its output {}:0 with a higher feerate from the wallet of the \
+                         refund address",
+                        tx.txid()
+                    ),
+                    _ => s!("rebroadcasting it"),
+                }
+            );
+            warn!("{} | {}", self.swap_id.bright_blue_italic(), msg);
+            let enquirer = self.enquirer.clone();
+            let _ = self.report_progress_to(senders, &enquirer, msg);
+            match cancel_confirmations {
+                Some(confs)
+                    if tx_label == TxLabel::Refund
+                        && self.temporal_safety.refund_deadline_close(confs) =>
+                {
+                    let info = format!(
+                        "Refund transaction {} stuck with {} blocks left before the \
+                         counterparty can punish, bump it through CPFP from the refund address \
+                         now or the locked bitcoin may be lost",
+                        tx.txid(),
+                        self.temporal_safety.punish_timelock.saturating_sub(confs),
+                    );
+                    error!("{} | {}", self.swap_id.bright_blue_italic(), info);
+                    if let Some(enquirer) = enquirer {
+                        senders.send_to(
+                            ServiceBus::Ctl,
+                            self.identity(),
+                            enquirer,
+                            Request::Failure(microservices::rpc::Failure { code: 1, info }),
+                        )?;
+                    }
+                }
+                _ => {}
+            }
+            if bump {
+                self.send_wallet(ServiceBus::Ctl, senders, Request::BumpFee(tx_label))?;
+            }
+            let req = Request::SyncerTask(Task::BroadcastTransaction(BroadcastTransaction {
+                id: self.syncer_state.tasks.new_taskid(),
+                tx: bitcoin::consensus::serialize(&tx),
+            }));
+            senders.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                self.syncer_state.bitcoin_syncer(),
+                req,
+            )?;
+        }
+        Ok(())
+    }
+
     fn handle_rpc_msg(
         &mut self,
         senders: &mut Senders,
@@ -991,6 +1655,14 @@ impl Runtime {
                         msg.swap_id(),
                     )));
                 }
+                if repeated_peer_msg(&mut self.last_received_peer_msg, msg) {
+                    debug!(
+                        "{} | Ignoring {} received again after a reconnection",
+                        self.swap_id.bright_blue_italic(),
+                        msg
+                    );
+                    return Ok(());
+                }
                 match &msg {
                     // we are taker and the maker committed, now we reveal after checking
                     // whether we're Bob or Alice and that we're on a compatible state
@@ -1244,8 +1916,33 @@ impl Runtime {
                     }
 
                     // bob and alice
-                    Msg::Abort(_) => {
-                        return Err(Error::Farcaster("Abort not yet supported".to_string()))
+                    Msg::Abort(protocol_message::Abort { error_body, .. }) => {
+                        let reason = error_body.clone().unwrap_or_default();
+                        if self.state.finish() {
+                            debug!("ignoring abort of finished swap: {}", reason);
+                        } else if !self.lock_published() {
+                            warn!(
+                                "{} | Counterparty aborted the swap: {}",
+                                self.swap_id.bright_blue_italic(),
+                                reason
+                            );
+                            self.finish_aborted(senders)?;
+                        } else if self.abort_refusal().is_none() {
+                            warn!(
+                                "{} | Counterparty aborted the swap after the bitcoin lock, \
+                                 waiting for the cancel timelock: {}",
+                                self.swap_id.bright_blue_italic(),
+                                reason
+                            );
+                            self.start_aborting(senders)?;
+                        } else {
+                            warn!(
+                                "{} | Ignoring the abort of the counterparty in state {}: {}",
+                                self.swap_id.bright_blue_italic(),
+                                self.state,
+                                reason
+                            );
+                        }
                     }
                     Msg::Ping(_) | Msg::Pong(_) | Msg::PingPeer => {
                         unreachable!("ping/pong must remain in peerd, and unreachable in swapd")
@@ -1285,6 +1982,7 @@ impl Runtime {
                 | ServiceId::Wallet
             ) => {}
             (Request::GetInfo, ServiceId::Client(_)) => {}
+            (Request::PeerReconnected, ServiceId::Peer(_)) => {}
             _ => return Err(Error::Farcaster(
                 "Permission Error: only Farcasterd, Wallet, Client and Syncer can can control swapd"
                     .to_string(),
@@ -1298,11 +1996,41 @@ impl Runtime {
                     self.swap_id.bright_blue_italic(),
                     format!("Terminating {}", self.identity()).bright_white_bold()
                 );
+                // the swap is over, there is nothing left to resume
+                if let Err(err) = self.storage.remove() {
+                    warn!(
+                        "{} | Failed to remove swap checkpoint: {}",
+                        self.swap_id.bright_blue_italic(),
+                        err
+                    );
+                }
                 std::process::exit(match success {
                     request::Outcome::Buy => 0,
                     _ => 1,
                 });
             }
+            Request::PeerReconnected if source == self.peer_service => {
+                if let Some(msg) = self.last_peer_msg.clone() {
+                    info!(
+                        "{} | Connection with the counterparty re-established, sending {} again",
+                        self.swap_id.bright_blue_italic(),
+                        msg
+                    );
+                    self.send_peer(senders, msg)?;
+                }
+            }
+            Request::RestoreSwap if source == ServiceId::Farcasterd => {
+                info!(
+                    "{} | Syncers are up, re-registering tasks of restored swap",
+                    self.swap_id.bright_blue_italic(),
+                );
+                // walletd reloads its keys and signatures for the swap before any transaction
+                // seen by the syncers reaches it
+                self.send_ctl(senders, ServiceId::Wallet, Request::RestoreSwap)?;
+                for (syncer, task) in self.syncer_state.restored_tasks() {
+                    self.send_ctl(senders, syncer, Request::SyncerTask(task))?;
+                }
+            }
             Request::SweepXmrAddress(SweepXmrAddress {
                 view_key,
                 spend_key,
@@ -1516,17 +2244,19 @@ impl Runtime {
                         self.syncer_state
                             .handle_height_change(*height, Coin::Monero);
                     }
+                    Event::Reorg(Reorg {
+                        fork_height, depth, ..
+                    }) => {
+                        self.syncer_state
+                            .handle_reorg(*fork_height, *depth, Coin::Monero);
+                    }
                     Event::AddressTransaction(AddressTransaction {
                         id,
                         hash,
                         amount,
-                        block,
                         tx,
                     }) if self.state.swap_role() == SwapRole::Alice => {
-                        debug!(
-                            "Event details: {} {:?} {} {:?} {:?}",
-                            id, hash, amount, block, tx
-                        );
+                        debug!("Event details: {} {:?} {} {:?}", id, hash, amount, tx);
                         self.state.a_sup_refundsig_xmrlocked();
                         let task = self
                             .syncer_state
@@ -1542,7 +2272,6 @@ impl Runtime {
                         id,
                         hash,
                         amount,
-                        block: _,
                         tx: _,
                     }) if self.state.swap_role() == SwapRole::Bob => {
                         if amount < &self.syncer_state.monero_amount.as_pico() {
@@ -1619,7 +2348,7 @@ impl Runtime {
                         // error!("not checking tx rcvd is accordant lock");
                         let PendingRequest {
                             request,
-                            dest,
+                            dest: _,
                             bus_id,
                         } = self
                             .pending_requests
@@ -1627,10 +2356,12 @@ impl Runtime {
                             .expect("Checked above")
                             .pop()
                             .unwrap();
-                        if let (Request::Protocol(Msg::BuyProcedureSignature(_)), ServiceBus::Msg) =
-                            (&request, &bus_id)
+                        if let (
+                            Request::Protocol(msg @ Msg::BuyProcedureSignature(_)),
+                            ServiceBus::Msg,
+                        ) = (&request, &bus_id)
                         {
-                            senders.send_to(bus_id, self.identity(), dest, request)?;
+                            self.send_peer(senders, msg.clone())?;
                             debug!("sent buyproceduresignature at state {}", &self.state);
                             let next_state =
                                 State::Bob(BobState::BuySigB(BuySigB { buy_tx_seen: false }));
@@ -1703,12 +2434,63 @@ impl Runtime {
                     Event::HeightChanged(HeightChanged { height, .. }) => {
                         self.syncer_state
                             .handle_height_change(*height, Coin::Bitcoin);
+                        self.check_pending_broadcasts(senders)?;
+                        // the arbitrating transactions must confirm before the counterparty
+                        // can race them, fees are estimated for that target on each new block
+                        // until they are signed: the cancel, refund and buy transactions are
+                        // co-signed with the counterparty during the setup and keep their fee,
+                        // only a stuck punish is re-signed later through `BumpFee`
+                        if !self.txs.contains_key(&TxLabel::Cancel) {
+                            let task = self
+                                .syncer_state
+                                .estimate_fee_btc(self.temporal_safety.race_thr as u16);
+                            senders.send_to(
+                                ServiceBus::Ctl,
+                                self.identity(),
+                                self.syncer_state.bitcoin_syncer(),
+                                Request::SyncerTask(task),
+                            )?;
+                        }
+                    }
+                    Event::FeeEstimated(FeeEstimated {
+                        sat_per_vbyte: Some(sat_per_vbyte),
+                        ..
+                    }) => {
+                        self.syncer_state.btc_fee_estimate = Some(*sat_per_vbyte);
+                        let priority =
+                            fee_priority(&self.public_offer.offer.fee_strategy, *sat_per_vbyte);
+                        if self.syncer_state.fee_priority != Some(priority) {
+                            debug!(
+                                "{} | Estimated fee rate {} sat/vB, using {:?} fee priority",
+                                self.swap_id.bright_blue_italic(),
+                                sat_per_vbyte,
+                                priority
+                            );
+                            self.syncer_state.fee_priority = Some(priority);
+                            let swap_id = self.swap_id();
+                            self.send_wallet(
+                                ServiceBus::Ctl,
+                                senders,
+                                Request::SetFeePriority(request::SetFeePriority {
+                                    swap_id,
+                                    priority,
+                                }),
+                            )?;
+                        }
+                    }
+                    Event::FeeEstimated(event) => {
+                        debug!("{}", event)
+                    }
+                    Event::Reorg(Reorg {
+                        fork_height, depth, ..
+                    }) => {
+                        self.syncer_state
+                            .handle_reorg(*fork_height, *depth, Coin::Bitcoin);
                     }
                     Event::AddressTransaction(AddressTransaction {
                         id,
                         hash: _,
                         amount: _,
-                        block: _,
                         tx,
                     }) if self.syncer_state.tasks.watched_addrs.get(id).is_some() => {
                         let tx = bitcoin::Transaction::deserialize(tx)?;
@@ -1827,6 +2609,7 @@ impl Runtime {
                                 if self.state.a_refundsig()
                                     && !self.state.a_xmr_locked()
                                     && !self.state.a_buy_published()
+                                    && !self.aborting
                                     && self.remote_params.is_some()
                                     && !self.syncer_state.acc_lock_watched() =>
                             {
@@ -2164,8 +2947,18 @@ impl Runtime {
                 self.state_update(senders, next_state)?;
             }
 
+            Request::Tx(Tx::Lock(_)) if self.state.finish() => {
+                warn!(
+                    "{} | Not broadcasting the lock transaction of a finished swap",
+                    self.swap_id.bright_blue_italic(),
+                );
+            }
+
             Request::Tx(Tx::Lock(btc_lock)) if self.state.b_core_arb() => {
                 log_tx_received(self.swap_id, TxLabel::Lock);
+                // recorded before the broadcast, an abort must not wipe a swap with funds locked
+                self.lock_broadcast = true;
+                self.checkpoint()?;
                 self.broadcast(btc_lock, TxLabel::Lock, senders)?;
                 if let (Some(Params::Bob(bob_params)), Some(Params::Alice(alice_params))) =
                     (&self.local_params, &self.remote_params)
@@ -2238,6 +3031,13 @@ impl Runtime {
                 self.state_update(senders, next_state)?;
             }
 
+            Request::Protocol(Msg::BuyProcedureSignature(_)) if self.aborting => {
+                warn!(
+                    "{} | Not sending the buy procedure signature of an aborted swap",
+                    self.swap_id.bright_blue_italic(),
+                );
+            }
+
             Request::Protocol(Msg::BuyProcedureSignature(ref buy_proc_sig))
                 if self.state.b_core_arb()
                     && !self.syncer_state.tasks.txids.contains_key(&TxLabel::Buy) =>
@@ -2273,6 +3073,83 @@ impl Runtime {
                 };
             }
 
+            Request::AbortSwap(swap_id) if swap_id == self.swap_id() => {
+                if let Some(reason) = self.abort_refusal() {
+                    let msg = format!("Cannot abort swap {}, {}", swap_id, reason);
+                    warn!("{} | {}", self.swap_id.bright_blue_italic(), msg);
+                    self.send_ctl(
+                        senders,
+                        source,
+                        Request::Failure(microservices::rpc::Failure { code: 1, info: msg }),
+                    )?;
+                } else if !self.lock_published() {
+                    if self.peer_service != ServiceId::Loopback {
+                        self.send_peer(
+                            senders,
+                            Msg::Abort(protocol_message::Abort {
+                                swap_id,
+                                error_body: Some(s!("swap aborted by the counterparty")),
+                            }),
+                        )?;
+                    }
+                    self.finish_aborted(senders)?;
+                    let mut msg = format!("Swap {} aborted", swap_id);
+                    if self.state.swap_role() == SwapRole::Bob {
+                        msg.push_str(
+                            ", bitcoins sent to the funding address can be swept with swap-cli \
+                             recover",
+                        );
+                    }
+                    self.report_success_to(senders, source, Some(msg))?;
+                } else {
+                    let msg = self.start_aborting(senders)?;
+                    self.report_success_to(senders, source, Some(msg))?;
+                }
+            }
+
+            Request::BroadcastTx(request::ManualTx {
+                swap_id,
+                tx_label,
+                force,
+            }) if swap_id == self.swap_id() => match self.manual_tx(tx_label, force) {
+                Ok(tx) => {
+                    if tx_label == TxLabel::Buy {
+                        if let State::Alice(AliceState::RefundSigA(RefundSigA {
+                            buy_published,
+                            ..
+                        })) = &mut self.state
+                        {
+                            *buy_published = true;
+                        }
+                    }
+                    let msg = format!(
+                        "Broadcasting {} transaction {} of swap {}",
+                        tx_label,
+                        tx.txid(),
+                        swap_id
+                    );
+                    self.broadcast(tx, tx_label, senders)?;
+                    self.report_success_to(senders, source, Some(msg))?;
+                }
+                Err(err) => {
+                    self.send_ctl(senders, source, Request::Failure(err))?;
+                }
+            },
+
+            Request::ExportTx(request::ManualTx {
+                swap_id,
+                tx_label,
+                force,
+            }) if swap_id == self.swap_id() => match self.manual_tx(tx_label, force) {
+                Ok(tx) => {
+                    let hex = bitcoin::consensus::encode::serialize_hex(&tx);
+                    self.send_ctl(senders, source, Request::TxHex(hex))?;
+                }
+                Err(err) => {
+                    self.send_ctl(senders, source, Request::Failure(err))?;
+                }
+            },
+
             Request::GetInfo => {
                 fn bmap<T>(remote_peer: &Option<NodeAddr>, v: &T) -> BTreeMap<NodeAddr, T>
                 where
@@ -2289,9 +3166,60 @@ impl Runtime {
                 } else {
                     Some(self.swap_id())
                 };
+                let offer = &self.public_offer.offer;
+                let syncer_state = &self.syncer_state;
+                let transactions = [
+                    TxLabel::Funding,
+                    TxLabel::Lock,
+                    TxLabel::AccLock,
+                    TxLabel::Buy,
+                    TxLabel::Cancel,
+                    TxLabel::Refund,
+                    TxLabel::Punish,
+                ]
+                .iter()
+                .filter_map(|label| {
+                    let txid = syncer_state
+                        .tasks
+                        .txids
+                        .get(label)
+                        .copied()
+                        .or_else(|| self.txs.get(label).map(|tx| tx.txid()))
+                        .map(|txid| txid.to_string())
+                        .or_else(|| syncer_state.tasks.monero_tx_hash(*label));
+                    let confirmations = syncer_state.tx_confs.get(label).copied();
+                    if txid.is_none() && confirmations.is_none() {
+                        return None;
+                    }
+                    Some(request::SwapTxInfo {
+                        label: label.to_string(),
+                        txid,
+                        confirmations: confirmations.flatten(),
+                    })
+                })
+                .collect();
+                let next_deadline = self.temporal_safety.next_deadline(
+                    syncer_state.bitcoin_height,
+                    syncer_state.tx_confs.get(&TxLabel::Lock).copied().flatten(),
+                    syncer_state
+                        .tx_confs
+                        .get(&TxLabel::Cancel)
+                        .copied()
+                        .flatten(),
+                );
                 let info = request::SwapInfo {
                     swap_id,
-                    // state: self.state, // FIXME serde missing
+                    state: self.state.to_string(),
+                    trade_role: self.local_trade_role,
+                    swap_role: self.state.swap_role(),
+                    arbitrating_amount: offer.arbitrating_amount.as_sat(),
+                    accordant_amount: offer.accordant_amount.as_pico(),
+                    cancel_timelock: offer.cancel_timelock.as_u32(),
+                    punish_timelock: offer.punish_timelock.as_u32(),
+                    bitcoin_height: syncer_state.bitcoin_height,
+                    monero_height: syncer_state.monero_height,
+                    transactions,
+                    next_deadline,
                     maker_peer: self.maker_peer.clone().map(|p| vec![p]).unwrap_or_default(),
                     uptime: SystemTime::now()
                         .duration_since(self.started)
@@ -2388,6 +3316,61 @@ impl Runtime {
     }
 }
 
+/// List the swaps with a checkpoint in the data directory, along with the public offer and
+/// local trade role needed to relaunch their swapd in restore mode
+pub fn checkpointed_swaps(
+    data_dir: &Path,
+) -> Result<Vec<(SwapId, PublicOffer<BtcXmr>, TradeRole)>, Error> {
+    let path = data_dir.join(storage::CHECKPOINT_DIR);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let mut swaps = vec![];
+    for entry in std::fs::read_dir(&path)? {
+        let file = entry?.path();
+        if file.extension().and_then(|ext| ext.to_str()) != Some(storage::CHECKPOINT_EXT) {
+            continue;
+        }
+        let swap_id = match file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| SwapId::from_str(stem).ok())
+        {
+            Some(swap_id) => swap_id,
+            None => {
+                warn!("Ignoring unrecognized checkpoint file {}", file.display());
+                continue;
+            }
+        };
+        let driver = storage::DiskDriver::init(
+            swap_id,
+            Box::new(storage::DiskConfig { path: path.clone() }),
+        )?;
+        let checkpoint = driver.load().and_then(|bytes| {
+            bytes
+                .map(|bytes| strict_encoding::strict_deserialize::<CheckpointSwapd>(&bytes))
+                .transpose()
+                .map_err(Error::from)
+        });
+        match checkpoint {
+            Ok(Some(checkpoint)) => swaps.push((
+                swap_id,
+                checkpoint.public_offer,
+                checkpoint.local_trade_role,
+            )),
+            Ok(None) => {}
+            Err(err) => {
+                warn!(
+                    "Ignoring unreadable checkpoint file {}: {}",
+                    file.display(),
+                    err
+                );
+            }
+        }
+    }
+    Ok(swaps)
+}
+
 pub fn get_swap_id(source: &ServiceId) -> Result<SwapId, Error> {
     if let ServiceId::Swap(swap_id) = source {
         Ok(*swap_id)
@@ -2435,6 +3418,19 @@ fn aggregate_xmr_spend_view(
     (alice_params.spend + bob_params.spend, alice_view + bob_view)
 }
 
+/// Pick the fee priority of the offer fee strategy: the high end of the range when the lowest fee
+/// rate would not meet the network fee estimation
+fn fee_priority(fee_strategy: &FeeStrategy<SatPerVByte>, sat_per_vbyte: u64) -> FeePriority {
+    match fee_strategy {
+        FeeStrategy::Range { min_inclusive, .. }
+            if min_inclusive.as_native_unit().as_sat() < sat_per_vbyte =>
+        {
+            FeePriority::High
+        }
+        _ => FeePriority::Low,
+    }
+}
+
 #[derive(Debug)]
 struct PendingRequest {
     request: Request,
@@ -2472,3 +3468,235 @@ fn remote_params_candidate(reveal: &Reveal, remote_commit: Commit) -> Result<Par
         ))),
     }
 }
+
+/// Whether the message is the last one received from the counterparty, recording it otherwise
+fn repeated_peer_msg(last_received: &mut Option<Vec<u8>>, msg: &Msg) -> bool {
+    let encoded = match strict_encoding::strict_serialize(msg) {
+        Ok(encoded) => encoded,
+        Err(_) => return false,
+    };
+    if last_received.as_ref() == Some(&encoded) {
+        return true;
+    }
+    *last_received = Some(encoded);
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_peer_msg_is_dropped() {
+        let mut last_received = None;
+        assert!(!repeated_peer_msg(
+            &mut last_received,
+            &Msg::Pong(vec![1, 2])
+        ));
+        // sent again after a reconnection
+        assert!(repeated_peer_msg(
+            &mut last_received,
+            &Msg::Pong(vec![1, 2])
+        ));
+        assert!(!repeated_peer_msg(&mut last_received, &Msg::Pong(vec![3])));
+        // only the last message is sent again
+        assert!(!repeated_peer_msg(
+            &mut last_received,
+            &Msg::Pong(vec![1, 2])
+        ));
+    }
+
+    #[test]
+    fn temporal_safety_next_deadline() {
+        let temporal_safety = TemporalSafety {
+            cancel_timelock: 10,
+            punish_timelock: 20,
+            race_thr: 3,
+            btc_finality_thr: 1,
+            xmr_finality_thr: 1,
+            sweep_monero_thr: 1,
+        };
+        let deadline = |height, lock_confs, cancel_confs| {
+            temporal_safety
+                .next_deadline(height, lock_confs, cancel_confs)
+                .map(|deadline| (deadline.height, deadline.description))
+        };
+
+        // nothing is locked yet
+        assert_eq!(deadline(100, None, None), None);
+        assert_eq!(deadline(100, Some(0), None), None);
+
+        // lock mined at height 100
+        assert_eq!(
+            deadline(100, Some(1), None),
+            Some((107, s!("buy no longer safe")))
+        );
+        assert_eq!(
+            deadline(106, Some(7), None),
+            Some((107, s!("buy no longer safe")))
+        );
+        assert_eq!(
+            deadline(107, Some(8), None),
+            Some((109, s!("cancel valid")))
+        );
+        assert_eq!(deadline(109, Some(10), None), None);
+
+        // cancel mined at height 200, its confirmations take over the lock ones
+        assert_eq!(
+            deadline(200, Some(101), Some(1)),
+            Some((217, s!("refund no longer safe")))
+        );
+        assert_eq!(
+            deadline(217, Some(118), Some(18)),
+            Some((219, s!("punish valid")))
+        );
+        assert_eq!(deadline(219, Some(120), Some(20)), None);
+    }
+
+    #[test]
+    fn checkpoint_swapd_round_trip() {
+        let node_id = secp256k1::PublicKey::from_secret_key(
+            &secp256k1::Secp256k1::new(),
+            &secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+        );
+        let peer_address: std::net::SocketAddr = "127.0.0.1:9735".parse().unwrap();
+        let public_offer =
+            crate::farcasterd::policy::test_offer().to_public_v1(node_id, peer_address.into());
+        let maker_peer = NodeAddr::Remote(internet2::RemoteNodeAddr {
+            node_id,
+            remote_addr: internet2::RemoteSocketAddr::Ftcp(peer_address.into()),
+        });
+
+        let mut tasks = SyncerTasks {
+            counter: 0,
+            watched_addrs: none!(),
+            watched_txs: none!(),
+            retrieving_txs: none!(),
+            sweeping_addr: none!(),
+            txids: none!(),
+            watch_tasks: none!(),
+        };
+        let height_id = tasks.new_taskid();
+        let lock_id = tasks.new_taskid();
+        let get_tx_id = tasks.new_taskid();
+        tasks.watched_txs.insert(lock_id, TxLabel::Lock);
+        tasks.watched_addrs.insert(get_tx_id, TxLabel::AccLock);
+        tasks.retrieving_txs.insert(
+            get_tx_id,
+            (
+                TxLabel::Buy,
+                Task::GetTx(GetTx {
+                    id: get_tx_id,
+                    hash: vec![2; 32],
+                }),
+            ),
+        );
+        tasks.sweeping_addr = Some(get_tx_id);
+        tasks.txids.insert(TxLabel::Lock, Txid::default());
+        tasks.watch_tasks.insert(
+            height_id,
+            (
+                Coin::Bitcoin,
+                Task::WatchHeight(WatchHeight {
+                    id: height_id,
+                    lifetime: 100,
+                }),
+            ),
+        );
+        tasks.watch_tasks.insert(
+            lock_id,
+            (
+                Coin::Monero,
+                Task::WatchTransaction(WatchTransaction {
+                    id: lock_id,
+                    lifetime: 100,
+                    hash: vec![1; 32],
+                    confirmation_bound: 20,
+                }),
+            ),
+        );
+
+        let tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: 1000,
+                script_pubkey: bitcoin::Script::new(),
+            }],
+        };
+        let mut txs = HashMap::new();
+        txs.insert(TxLabel::Cancel, tx);
+
+        let checkpoint = CheckpointSwapd {
+            public_offer: public_offer.clone(),
+            local_trade_role: TradeRole::Maker,
+            state: State::Bob(BobState::StartB(TradeRole::Maker, public_offer)),
+            maker_peer: Some(maker_peer),
+            temporal_safety: TemporalSafety {
+                cancel_timelock: 10,
+                punish_timelock: 20,
+                race_thr: 3,
+                btc_finality_thr: 1,
+                xmr_finality_thr: 1,
+                sweep_monero_thr: 1,
+            },
+            tasks,
+            txs,
+            local_params: None,
+            remote_params: None,
+            aborting: true,
+            lock_broadcast: false,
+        };
+
+        let bytes = strict_encoding::strict_serialize(&checkpoint).unwrap();
+        let decoded: CheckpointSwapd = strict_encoding::strict_deserialize(&bytes).unwrap();
+
+        assert_eq!(decoded.public_offer, checkpoint.public_offer);
+        assert_eq!(decoded.local_trade_role, checkpoint.local_trade_role);
+        assert_eq!(decoded.state.to_string(), checkpoint.state.to_string());
+        assert_eq!(decoded.maker_peer, checkpoint.maker_peer);
+        assert_eq!(
+            strict_encoding::strict_serialize(&decoded.temporal_safety).unwrap(),
+            strict_encoding::strict_serialize(&checkpoint.temporal_safety).unwrap()
+        );
+        assert_eq!(decoded.tasks.counter, 3);
+        assert_eq!(decoded.tasks.watched_txs, checkpoint.tasks.watched_txs);
+        assert_eq!(decoded.tasks.watched_addrs, checkpoint.tasks.watched_addrs);
+        assert_eq!(
+            decoded.tasks.retrieving_txs,
+            checkpoint.tasks.retrieving_txs
+        );
+        assert_eq!(decoded.tasks.sweeping_addr, checkpoint.tasks.sweeping_addr);
+        assert_eq!(decoded.tasks.txids, checkpoint.tasks.txids);
+        assert_eq!(decoded.tasks.watch_tasks, checkpoint.tasks.watch_tasks);
+        assert_eq!(decoded.txs, checkpoint.txs);
+        assert!(decoded.local_params.is_none());
+        assert!(decoded.remote_params.is_none());
+        assert!(decoded.aborting);
+        assert!(!decoded.lock_broadcast);
+
+        // a truncated checkpoint is refused rather than half restored
+        assert!(
+            strict_encoding::strict_deserialize::<CheckpointSwapd>(&bytes[..bytes.len() - 1])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn temporal_safety_refund_deadline_close() {
+        let temporal_safety = TemporalSafety {
+            cancel_timelock: 10,
+            punish_timelock: 20,
+            race_thr: 3,
+            btc_finality_thr: 1,
+            xmr_finality_thr: 1,
+            sweep_monero_thr: 1,
+        };
+        assert!(!temporal_safety.refund_deadline_close(0));
+        assert!(!temporal_safety.refund_deadline_close(13));
+        assert!(temporal_safety.refund_deadline_close(14));
+        assert!(temporal_safety.refund_deadline_close(20));
+        assert!(temporal_safety.refund_deadline_close(u32::MAX));
+    }
+}
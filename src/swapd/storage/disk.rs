@@ -13,14 +13,28 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 use std::any::Any;
+use std::fs;
+use std::io;
 use std::path::PathBuf;
 
+use bitcoin::hashes::hex::ToHex;
 use farcaster_core::swap::SwapId;
 
 use super::Driver;
 use crate::Error;
 
+/// Name of the directory, relative to the data directory, holding swap checkpoints
+pub const CHECKPOINT_DIR: &str = "checkpoints";
+
+/// Extension of the swap checkpoint files
+pub const CHECKPOINT_EXT: &str = "dat";
+
+/// Name of the directory, relative to the checkpoint directory, holding the walletd state of
+/// each checkpointed swap
+pub const WALLET_CHECKPOINT_DIR: &str = "wallets";
+
 pub struct DiskConfig {
+    /// Directory where the checkpoint files are written
     pub path: PathBuf,
 }
 
@@ -31,11 +45,84 @@ pub struct DiskDriver {
 
 impl Driver for DiskDriver {
     fn init(channel_id: SwapId, config: Box<dyn Any>) -> Result<Self, Error> {
-        let config = *config.downcast().map_err(|_| Error::Other(s!("")))?;
+        let config: DiskConfig = *config
+            .downcast()
+            .map_err(|_| Error::Other(s!("invalid disk driver configuration")))?;
+        fs::create_dir_all(&config.path)?;
         Ok(Self { channel_id, config })
     }
 
-    fn store(&mut self) -> Result<(), Error> {
-        unimplemented!()
+    fn store(&mut self, checkpoint: &[u8]) -> Result<(), Error> {
+        // write to a temporary file first so a crash never leaves a truncated checkpoint
+        let tmp = self.checkpoint_file().with_extension("tmp");
+        fs::write(&tmp, checkpoint)?;
+        fs::rename(&tmp, self.checkpoint_file())?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>, Error> {
+        match fs::read(self.checkpoint_file()) {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn remove(&mut self) -> Result<(), Error> {
+        match fs::remove_file(self.checkpoint_file()) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl DiskDriver {
+    fn checkpoint_file(&self) -> PathBuf {
+        self.config
+            .path
+            .join(self.channel_id.to_hex())
+            .with_extension(CHECKPOINT_EXT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_driver_store_load_remove() {
+        let path =
+            std::env::temp_dir().join(format!("farcaster-disk-driver-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let swap_id = SwapId::random();
+        let mut driver =
+            DiskDriver::init(swap_id, Box::new(DiskConfig { path: path.clone() })).unwrap();
+        // the checkpoint directory is created on init
+        assert!(path.is_dir());
+        assert_eq!(driver.load().unwrap(), None);
+
+        driver.store(&[1, 2, 3]).unwrap();
+        let file = path.join(swap_id.to_hex()).with_extension(CHECKPOINT_EXT);
+        assert_eq!(fs::read(&file).unwrap(), vec![1, 2, 3]);
+        // the temporary file is renamed over the checkpoint
+        assert!(!file.with_extension("tmp").exists());
+        assert_eq!(driver.load().unwrap(), Some(vec![1, 2, 3]));
+
+        // a leftover temporary file from a crash is overwritten by the next store
+        fs::write(file.with_extension("tmp"), [9]).unwrap();
+        driver.store(&[4, 5]).unwrap();
+        assert!(!file.with_extension("tmp").exists());
+        assert_eq!(driver.load().unwrap(), Some(vec![4, 5]));
+
+        driver.remove().unwrap();
+        assert!(!file.exists());
+        assert_eq!(driver.load().unwrap(), None);
+        // removing a missing checkpoint is not an error
+        driver.remove().unwrap();
+
+        // an invalid configuration is refused
+        assert!(DiskDriver::init(swap_id, Box::new(path.clone())).is_err());
+
+        fs::remove_dir_all(&path).unwrap();
     }
 }
@@ -23,5 +23,12 @@ pub trait Driver {
     where
         Self: Sized;
 
-    fn store(&mut self) -> Result<(), Error>;
+    /// Persist the serialized swap checkpoint, replacing any previous one.
+    fn store(&mut self, checkpoint: &[u8]) -> Result<(), Error>;
+
+    /// Retrieve the last serialized swap checkpoint, if any was stored.
+    fn load(&self) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Drop the stored checkpoint, once the swap no longer needs to be resumed.
+    fn remove(&mut self) -> Result<(), Error>;
 }
@@ -15,5 +15,5 @@
 mod disk;
 mod driver;
 
-pub use disk::{DiskConfig, DiskDriver};
+pub use disk::{DiskConfig, DiskDriver, CHECKPOINT_DIR, CHECKPOINT_EXT, WALLET_CHECKPOINT_DIR};
 pub use driver::Driver;
@@ -2,6 +2,7 @@ use crate::internet2::Encrypt;
 use crate::internet2::TypedEnum;
 use crate::rpc::request::SyncerdBridgeEvent;
 use crate::rpc::Request;
+use crate::syncerd::electrum_servers::ElectrumServers;
 use crate::syncerd::opts::{Coin, Opts};
 use crate::syncerd::runtime::SyncerdTask;
 use crate::syncerd::runtime::Synclet;
@@ -11,11 +12,15 @@ use crate::syncerd::syncer_state::WatchedTransaction;
 use crate::syncerd::types::{AddressAddendum, Boolean, Task};
 use crate::syncerd::BtcAddressAddendum;
 use crate::syncerd::Event;
+use crate::syncerd::FeeEstimated;
 use crate::syncerd::TaskTarget;
 use crate::syncerd::TransactionBroadcasted;
 use crate::syncerd::TransactionRetrieved;
 use crate::ServiceId;
-use crate::{error::Error, syncerd::syncer_state::create_set};
+use crate::{
+    error::Error,
+    syncerd::syncer_state::{create_set, find_fork},
+};
 use crate::{error::SyncerError, internet2::Duplex};
 use crate::{farcaster_core::consensus::Decodable, LogStyle};
 use bitcoin::hashes::{
@@ -36,6 +41,7 @@ use internet2::PlainTranscoder;
 use internet2::ZMQ_CONTEXT;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::future;
 use std::io;
 use std::iter::FromIterator;
 use std::marker::{Send, Sized};
@@ -54,6 +60,9 @@ use hex;
 
 const RETRY_TIMEOUT: u64 = 5;
 const PING_WAIT: u8 = 2;
+/// Number of height polling iterations between two cross-checks of the tip with the other
+/// electrum servers
+const TIP_CHECK_WAIT: u8 = 30;
 
 pub struct ElectrumRpc {
     client: Client,
@@ -366,6 +375,47 @@ impl ElectrumRpc {
     }
 }
 
+/// Connect to the first healthy electrum server by priority, reporting the health of the servers
+/// tried along the way
+fn connect(
+    servers: &ElectrumServers,
+    polling: bool,
+    purpose: &str,
+) -> Option<(String, ElectrumRpc)> {
+    for electrum_server in servers.candidates() {
+        match ElectrumRpc::new(&electrum_server, polling) {
+            Ok(rpc) => {
+                servers.report_success(&electrum_server, rpc.height);
+                return Some((electrum_server, rpc));
+            }
+            Err(err) => {
+                error!(
+                    "failed to spawn electrum rpc client ({}) in {}: {:?}",
+                    &electrum_server, purpose, err
+                );
+                servers.report_failure(&electrum_server);
+            }
+        }
+    }
+    None
+}
+
+/// Fetch the tip of the other electrum servers to detect whether the current one lags behind
+fn cross_check_tip(servers: &ElectrumServers, electrum_server: &str) {
+    for other_server in servers.others(electrum_server) {
+        match Client::new(&other_server).and_then(|client| client.block_headers_subscribe()) {
+            Ok(header) => servers.record_tip(&other_server, header.height as u64),
+            Err(err) => {
+                debug!(
+                    "failed to fetch tip of electrum server {}: {:?}",
+                    other_server, err
+                );
+                servers.report_failure(&other_server);
+            }
+        }
+    }
+}
+
 fn query_addr_history(
     client: &mut Client,
     script: Script,
@@ -407,7 +457,7 @@ fn query_addr_history(
     Ok(addr_txs)
 }
 
-async fn run_syncerd_bridge_event_sender(
+pub(crate) async fn run_syncerd_bridge_event_sender(
     tx: zmq::Socket,
     mut event_rx: TokioReceiver<SyncerdBridgeEvent>,
     syncer_address: Vec<u8>,
@@ -432,8 +482,38 @@ async fn run_syncerd_bridge_event_sender(
     });
 }
 
+/// Convert a fee rate in bitcoin per kilo virtual byte, as returned by electrum and bitcoind, to
+/// satoshi per virtual byte, rounding up to not undershoot the estimation
+pub(crate) fn btc_per_kvb_to_sat_per_vbyte(btc_per_kvb: f64) -> u64 {
+    (btc_per_kvb * 100_000.0).ceil() as u64
+}
+
+/// Run a one-off request against the electrum servers by priority until one of them answers,
+/// returning the last error otherwise
+fn with_electrum_client<T>(
+    servers: &ElectrumServers,
+    request: impl Fn(Client) -> Result<T, electrum_client::Error>,
+) -> Result<T, electrum_client::Error> {
+    let mut res = Err(electrum_client::Error::Message(s!(
+        "no electrum server configured"
+    )));
+    for electrum_server in servers.candidates() {
+        res = Client::new(&electrum_server).and_then(&request);
+        match &res {
+            Ok(_) => return res,
+            // the server answered, the request itself failed
+            Err(electrum_client::Error::Protocol(_)) => return res,
+            Err(err) => {
+                error!("electrum server {} failed: {:?}", electrum_server, err);
+                servers.report_failure(&electrum_server);
+            }
+        }
+    }
+    res
+}
+
 async fn run_syncerd_task_receiver(
-    electrum_server: String,
+    servers: ElectrumServers,
     receive_task_channel: Receiver<SyncerdTask>,
     state: Arc<Mutex<SyncerState>>,
     tx_event: TokioSender<SyncerdBridgeEvent>,
@@ -449,7 +529,7 @@ async fn run_syncerd_task_receiver(
                 Ok(syncerd_task) => {
                     match syncerd_task.task {
                         Task::GetTx(task) => {
-                            match Client::new(&electrum_server).and_then(|transaction_client| {
+                            match with_electrum_client(&servers, |transaction_client| {
                                 transaction_client.transaction_get(
                                     &bitcoin::Txid::from_slice(&task.hash).unwrap(),
                                 )
@@ -489,6 +569,31 @@ async fn run_syncerd_task_receiver(
                                 }
                             }
                         }
+                        Task::EstimateFee(task) => {
+                            let sat_per_vbyte = match with_electrum_client(&servers, |client| {
+                                client.estimate_fee(task.blocks_until_confirmation as usize)
+                            }) {
+                                // electrum servers answer -1 when they lack the data to estimate
+                                Ok(btc_per_kvb) if btc_per_kvb > 0.0 => {
+                                    Some(btc_per_kvb_to_sat_per_vbyte(btc_per_kvb))
+                                }
+                                Ok(_) => None,
+                                Err(e) => {
+                                    error!("failed to estimate fee: {:?}", e);
+                                    None
+                                }
+                            };
+                            tx_event
+                                .send(SyncerdBridgeEvent {
+                                    event: Event::FeeEstimated(FeeEstimated {
+                                        id: task.id,
+                                        sat_per_vbyte,
+                                    }),
+                                    source: syncerd_task.source,
+                                })
+                                .await
+                                .expect("error sending fee estimated event");
+                        }
                         Task::SweepAddress(_) => {
                             error!("sweep address not implemented for bitcoin syncer");
                         }
@@ -506,7 +611,7 @@ async fn run_syncerd_task_receiver(
                         Task::BroadcastTransaction(task) => {
                             // TODO: match error and emit event with fail code
                             debug!("trying to broadcast tx: {:?}", task.tx.to_hex());
-                            match Client::new(&electrum_server).and_then(|broadcast_client| {
+                            match with_electrum_client(&servers, |broadcast_client| {
                                 broadcast_client.transaction_broadcast_raw(&task.tx.clone())
                             }) {
                                 Ok(txid) => {
@@ -590,18 +695,14 @@ async fn run_syncerd_task_receiver(
 
 fn address_polling(
     state: Arc<Mutex<SyncerState>>,
-    electrum_server: String,
+    servers: ElectrumServers,
     polling: bool,
 ) -> tokio::task::JoinHandle<()> {
     tokio::task::spawn(async move {
         loop {
-            let mut rpc = match ElectrumRpc::new(&electrum_server, polling) {
-                Ok(client) => client,
-                Err(err) => {
-                    error!(
-                        "failed to spawn electrum rpc client ({}) t in address polling: {:?}",
-                        &electrum_server, err
-                    );
+            let (electrum_server, mut rpc) = match connect(&servers, polling, "address polling") {
+                Some(connection) => connection,
+                None => {
                     // wait a bit before retrying the connection
                     tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
                     continue;
@@ -611,10 +712,19 @@ fn address_polling(
             loop {
                 if let Err(err) = rpc.ping() {
                     error!("error ping electrum client in address polling: {:?}", err);
+                    servers.report_failure(&electrum_server);
                     // break this loop and retry, since the electrum rpc client is probably
                     // broken
                     break;
                 }
+                if !servers.preferred(&electrum_server) {
+                    info!(
+                        "address polling switching away from electrum server {}",
+                        electrum_server
+                    );
+                    // subscriptions are made again on the new server
+                    break;
+                }
                 let state_guard = state.lock().await;
                 let addresses = state_guard.addresses.clone();
                 drop(state_guard);
@@ -663,24 +773,21 @@ fn address_polling(
 
 fn height_polling(
     state: Arc<Mutex<SyncerState>>,
-    electrum_server: String,
+    servers: ElectrumServers,
     polling: bool,
 ) -> tokio::task::JoinHandle<()> {
     tokio::task::spawn(async move {
         // outer loop ensures the polling restarts if there is an error
         loop {
-            let mut rpc = match ElectrumRpc::new(&electrum_server, polling) {
-                Ok(client) => client,
-                Err(err) => {
-                    error!(
-                        "failed to spawn electrum rpc client ({}) in height polling: {:?}",
-                        &electrum_server, err
-                    );
+            let (electrum_server, mut rpc) = match connect(&servers, polling, "height polling") {
+                Some(connection) => connection,
+                None => {
                     // wait a bit before retrying the connection
                     tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
                     continue;
                 }
             };
+            let mut tip_check_count: u8 = 0;
 
             let mut state_guard = state.lock().await;
             state_guard
@@ -691,6 +798,7 @@ fn height_polling(
             loop {
                 if let Err(err) = rpc.ping() {
                     error!("error ping electrum client in height polling: {:?}", err);
+                    servers.report_failure(&electrum_server);
                     // break this loop and retry, since the electrum rpc client is probably
                     // broken
                     break;
@@ -699,16 +807,58 @@ fn height_polling(
                     Ok(blks) => blks,
                     Err(err) => {
                         error!("error polling bitcoin block height: {:?}", err);
+                        servers.report_failure(&electrum_server);
                         // break this loop and retry, since the electrum rpc client is probably
                         // broken
                         break;
                     }
                 };
+                servers.record_tip(&electrum_server, rpc.height);
+                if tip_check_count % TIP_CHECK_WAIT == 0 {
+                    cross_check_tip(&servers, &electrum_server);
+                    tip_check_count = 0;
+                }
+                tip_check_count += 1;
+                if servers.lagging(&electrum_server) {
+                    warn!(
+                        "electrum server {} lags behind the other servers at height {}, failing over",
+                        electrum_server, rpc.height
+                    );
+                    servers.report_failure(&electrum_server);
+                    break;
+                }
+                if !servers.preferred(&electrum_server) {
+                    info!(
+                        "height polling switching away from electrum server {}",
+                        electrum_server
+                    );
+                    break;
+                }
                 let mut state_guard = state.lock().await;
                 let mut block_change = false;
                 for block_notif in blocks.drain(..) {
+                    let block_hash = block_notif.block_hash.to_vec();
+                    match find_fork(
+                        &state_guard.recent_blocks(),
+                        block_notif.height,
+                        &block_hash,
+                        |height| {
+                            future::ready(
+                                rpc.client
+                                    .block_header(height as usize)
+                                    .map(|header| header.block_hash().to_vec())
+                                    .map_err(Error::from),
+                            )
+                        },
+                    )
+                    .await
+                    {
+                        Ok(Some(fork_height)) => state_guard.reorg(fork_height).await,
+                        Ok(None) => {}
+                        Err(err) => error!("error checking for a bitcoin reorganization: {}", err),
+                    }
                     block_change = state_guard
-                        .change_height(block_notif.height, block_notif.block_hash.to_vec())
+                        .change_height(block_notif.height, block_hash)
                         .await;
                 }
                 drop(state_guard);
@@ -728,25 +878,21 @@ fn height_polling(
 
 fn unseen_transaction_polling(
     state: Arc<Mutex<SyncerState>>,
-    electrum_server: String,
+    servers: ElectrumServers,
     polling: bool,
 ) -> tokio::task::JoinHandle<()> {
     tokio::task::spawn(async move {
         // outer loop ensures the polling restarts if there is an error
         loop {
-            let rpc = match ElectrumRpc::new(&electrum_server, polling) {
-                Ok(client) => client,
-                Err(err) => {
-                    error!(
-                        "failed to spawn electrum rpc client ({}) in transaction polling: {:?}",
-                        &electrum_server, err
-                    );
+            let (electrum_server, rpc) = match connect(&servers, polling, "transaction polling") {
+                Some(connection) => connection,
+                None => {
                     // wait a bit before retrying the connection
                     tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
                     continue;
                 }
             };
-            loop {
+            while servers.preferred(&electrum_server) {
                 rpc.query_transactions(Arc::clone(&state), true).await;
                 tokio::time::sleep(std::time::Duration::from_secs(10)).await;
             }
@@ -773,8 +919,11 @@ impl Synclet for BitcoinSyncer {
         _network: Network,
         polling: bool,
     ) -> Result<(), Error> {
-        if let Some(electrum_server) = &opts.electrum_server {
-            let electrum_server = electrum_server.clone();
+        if !opts.electrum_server.is_empty() {
+            let servers = ElectrumServers::new(
+                opts.electrum_server.clone(),
+                Duration::from_secs(RETRY_TIMEOUT),
+            );
             std::thread::spawn(move || {
                 use tokio::runtime::Builder;
                 let rt = Builder::new_multi_thread()
@@ -790,7 +939,7 @@ impl Synclet for BitcoinSyncer {
                     let state = Arc::new(Mutex::new(SyncerState::new(event_tx.clone())));
 
                     run_syncerd_task_receiver(
-                        electrum_server.clone(),
+                        servers.clone(),
                         receive_task_channel,
                         Arc::clone(&state),
                         event_tx.clone(),
@@ -799,13 +948,13 @@ impl Synclet for BitcoinSyncer {
                     run_syncerd_bridge_event_sender(tx, event_rx, syncer_address).await;
 
                     let address_handle =
-                        address_polling(Arc::clone(&state), electrum_server.clone(), polling);
+                        address_polling(Arc::clone(&state), servers.clone(), polling);
 
                     let height_handle =
-                        height_polling(Arc::clone(&state), electrum_server.clone(), polling);
+                        height_polling(Arc::clone(&state), servers.clone(), polling);
 
                     let unseen_transaction_handle =
-                        unseen_transaction_polling(Arc::clone(&state), electrum_server, polling);
+                        unseen_transaction_polling(Arc::clone(&state), servers, polling);
 
                     let res =
                         tokio::try_join!(address_handle, height_handle, unseen_transaction_handle);
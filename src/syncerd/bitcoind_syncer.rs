@@ -0,0 +1,606 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Bitcoin synclet backed by the JSON-RPC interface of a Bitcoin Core node.
+//!
+//! Watched addresses are found by scanning the blocks above their minimum height and the mempool,
+//! so no wallet has to be loaded on the node. When the node runs with `-blockfilterindex`, only
+//! the blocks whose compact filter (BIP 158) matches a watched script are downloaded. Mempool
+//! transactions are fetched once and only the ones paying to a watched script are kept. Watched
+//! transactions are looked up with `getrawtransaction`, which requires the node to run with
+//! `-txindex` once they are confirmed.
+
+use crate::error::{Error, SyncerError};
+use crate::rpc::request::SyncerdBridgeEvent;
+use crate::syncerd::bitcoin_syncer::run_syncerd_bridge_event_sender;
+use crate::syncerd::opts::Opts;
+use crate::syncerd::runtime::SyncerdTask;
+use crate::syncerd::runtime::Synclet;
+use crate::syncerd::syncer_state::AddressTx;
+use crate::syncerd::syncer_state::SyncerState;
+use crate::syncerd::syncer_state::{create_set, find_fork};
+use crate::syncerd::types::{AddressAddendum, Boolean, Task};
+use crate::syncerd::BtcAddressAddendum;
+use crate::syncerd::Event;
+use crate::syncerd::FeeEstimated;
+use crate::syncerd::TaskTarget;
+use crate::syncerd::TransactionBroadcasted;
+use crate::syncerd::TransactionRetrieved;
+use crate::LogStyle;
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, Script, Transaction, Txid};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use farcaster_core::blockchain::Network;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::future;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver as TokioReceiver;
+use tokio::sync::mpsc::Sender as TokioSender;
+use tokio::sync::Mutex;
+
+const RETRY_TIMEOUT: u64 = 5;
+
+/// Connection parameters of the bitcoind JSON-RPC interface
+#[derive(Clone, Debug)]
+pub struct BitcoindServer {
+    pub url: String,
+    pub auth: Auth,
+}
+
+impl BitcoindServer {
+    fn from_opts(opts: &Opts) -> Result<Self, Error> {
+        let url = opts.bitcoind_rpc.clone().ok_or_else(|| {
+            error!("Missing --bitcoind-rpc argument");
+            SyncerError::InvalidConfig
+        })?;
+        let auth = match (
+            &opts.bitcoind_rpc_cookie,
+            &opts.bitcoind_rpc_user,
+            &opts.bitcoind_rpc_password_file,
+        ) {
+            (Some(cookie), _, _) => {
+                Auth::CookieFile(PathBuf::from(shellexpand::tilde(cookie).to_string()))
+            }
+            (None, Some(user), Some(password_file)) => {
+                let password = fs::read_to_string(shellexpand::tilde(password_file).as_ref())
+                    .map_err(|err| {
+                        error!("Cannot read the bitcoind rpc password file: {}", err);
+                        SyncerError::InvalidConfig
+                    })?;
+                Auth::UserPass(
+                    user.clone(),
+                    password.trim_end_matches(&['\r', '\n'][..]).to_string(),
+                )
+            }
+            _ => Auth::None,
+        };
+        Ok(Self { url, auth })
+    }
+}
+
+pub struct BitcoindRpc {
+    client: Client,
+    height: u64,
+    block_hash: BlockHash,
+    /// last scanned height and transactions found in blocks for each watched script
+    scanned: HashMap<Script, (u64, Vec<AddressTx>)>,
+    /// whether the node serves compact block filters, to skip the blocks not paying to a script
+    block_filters: bool,
+    /// mempool transactions paying to a watched script
+    mempool: HashMap<Txid, Transaction>,
+    /// mempool transactions already checked against `mempool_scripts`, not fetched again
+    mempool_checked: HashSet<Txid>,
+    mempool_scripts: HashSet<Script>,
+}
+
+impl BitcoindRpc {
+    fn new(server: &BitcoindServer) -> Result<Self, Error> {
+        let client = Client::new(&server.url, server.auth.clone())?;
+        let height = client.get_block_count()?;
+        let block_hash = client.get_block_hash(height)?;
+        debug!("New BitcoindRpc at height {:?}", height);
+        let block_filters = client.get_block_filter(&block_hash).is_ok();
+        if !block_filters {
+            warn!(
+                "bitcoind serves no block filters, run it with -blockfilterindex to not download \
+                 every block scanned for the watched addresses"
+            );
+        }
+
+        Ok(Self {
+            client,
+            height,
+            block_hash,
+            scanned: none!(),
+            block_filters,
+            mempool: none!(),
+            mempool_checked: none!(),
+            mempool_scripts: none!(),
+        })
+    }
+
+    /// Returns the new tip if it changed since the last check
+    fn new_block_check(&mut self) -> Result<Option<(u64, BlockHash)>, Error> {
+        let height = self.client.get_block_count()?;
+        let block_hash = self.client.get_block_hash(height)?;
+        if height == self.height && block_hash == self.block_hash {
+            return Ok(None);
+        }
+        self.height = height;
+        self.block_hash = block_hash;
+        trace!("new height received: {:?}", self.height);
+        Ok(Some((height, block_hash)))
+    }
+
+    /// Forget the address transactions found in blocks disconnected from the best chain
+    fn reorg(&mut self, fork_height: u64) {
+        self.scanned
+            .retain(|_, (scanned_height, _)| *scanned_height <= fork_height);
+    }
+
+    /// Fetch the transactions entering the mempool, keeping the ones paying to the watched
+    /// scripts, and forget the ones leaving it
+    fn refresh_mempool(&mut self, scripts: &HashSet<Script>) -> Result<(), Error> {
+        if scripts.is_empty() {
+            self.mempool.clear();
+            self.mempool_checked.clear();
+            self.mempool_scripts.clear();
+            return Ok(());
+        }
+        // the transactions checked before a script was watched are checked again for it
+        if !scripts.is_subset(&self.mempool_scripts) {
+            self.mempool_checked.clear();
+        }
+        self.mempool_scripts = scripts.clone();
+        let txids: HashSet<Txid> = self.client.get_raw_mempool()?.into_iter().collect();
+        self.mempool.retain(|txid, _| txids.contains(txid));
+        self.mempool_checked.retain(|txid| txids.contains(txid));
+        for txid in txids {
+            if self.mempool_checked.contains(&txid) {
+                continue;
+            }
+            // the transaction may have been evicted or mined in the meantime
+            if let Ok(tx) = self.client.get_raw_transaction(&txid, None) {
+                self.mempool_checked.insert(txid);
+                if tx
+                    .output
+                    .iter()
+                    .any(|output| scripts.contains(&output.script_pubkey))
+                {
+                    self.mempool.insert(txid, tx);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the block may pay to the script, always true without block filters
+    fn block_may_pay_to(&self, block_hash: &BlockHash, script: &Script) -> Result<bool, Error> {
+        if !self.block_filters {
+            return Ok(true);
+        }
+        let filter = self.client.get_block_filter(block_hash)?.to_filter();
+        // a filter that cannot be read does not skip the block
+        Ok(filter
+            .match_any(block_hash, &mut std::iter::once(script.as_bytes()))
+            .unwrap_or(true))
+    }
+
+    /// Transactions paying to the address, with the same semantic as the Electrum synclet: a
+    /// transaction confirmed at or below the minimum height is skipped
+    fn address_history(&mut self, address: &BtcAddressAddendum) -> Result<Vec<AddressTx>, Error> {
+        let script = &address.script_pubkey;
+        let (mut scanned_height, mut addr_txs) = self
+            .scanned
+            .get(script)
+            .cloned()
+            .unwrap_or((address.from_height, vec![]));
+        while scanned_height < self.height {
+            let block_hash = self.client.get_block_hash(scanned_height + 1)?;
+            if self.block_may_pay_to(&block_hash, script)? {
+                let block = self.client.get_block(&block_hash)?;
+                addr_txs.extend(block.txdata.iter().filter_map(|tx| address_tx(tx, script)));
+            }
+            scanned_height += 1;
+        }
+        self.scanned
+            .insert(script.clone(), (scanned_height, addr_txs.clone()));
+        addr_txs.extend(
+            self.mempool
+                .values()
+                .filter_map(|tx| address_tx(tx, script)),
+        );
+        Ok(addr_txs)
+    }
+
+    async fn query_transactions(&self, state: Arc<Mutex<SyncerState>>, unseen: bool) {
+        let state_guard = state.lock().await;
+        let txids: Vec<Vec<u8>> = if unseen {
+            state_guard
+                .unseen_transactions
+                .iter()
+                .map(|task_id| state_guard.transactions[task_id].task.hash.clone())
+                .collect()
+        } else {
+            state_guard
+                .transactions
+                .iter()
+                .map(|(_, watched_tx)| watched_tx.task.hash.clone())
+                .collect()
+        };
+        drop(state_guard);
+        for tx_id in txids.iter() {
+            let txid = match Txid::from_slice(tx_id) {
+                Ok(txid) => txid,
+                Err(err) => {
+                    error!("invalid watched transaction id: {}", err);
+                    continue;
+                }
+            };
+            let (block_hash, confirmations, tx) =
+                match self.client.get_raw_transaction_info(&txid, None) {
+                    Ok(raw_tx) => (
+                        raw_tx.blockhash,
+                        Some(raw_tx.confirmations.unwrap_or(0)),
+                        raw_tx.hex,
+                    ),
+                    Err(err) => {
+                        debug!("error getting transaction, treating as not found: {}", err);
+                        (None, None, vec![])
+                    }
+                };
+            debug!("Updated tx: {}", &txid);
+            let mut state_guard = state.lock().await;
+            state_guard
+                .change_transaction(
+                    tx_id.clone(),
+                    block_hash.map(|hash| hash.to_vec()),
+                    confirmations,
+                    tx,
+                )
+                .await;
+            drop(state_guard);
+        }
+    }
+}
+
+async fn run_syncerd_task_receiver(
+    server: BitcoindServer,
+    receive_task_channel: Receiver<SyncerdTask>,
+    state: Arc<Mutex<SyncerState>>,
+    tx_event: TokioSender<SyncerdBridgeEvent>,
+) {
+    let task_receiver = Arc::new(Mutex::new(receive_task_channel));
+    tokio::spawn(async move {
+        loop {
+            // this is a hack around the Receiver not being Sync
+            let guard = task_receiver.lock().await;
+            let syncerd_task = guard.try_recv();
+            drop(guard);
+            match syncerd_task {
+                Ok(syncerd_task) => match syncerd_task.task {
+                    Task::GetTx(task) => {
+                        let tx = match (
+                            Client::new(&server.url, server.auth.clone()),
+                            Txid::from_slice(&task.hash),
+                        ) {
+                            (Ok(client), Ok(txid)) => client
+                                .get_raw_transaction(&txid, None)
+                                .map_err(|err| info!("failed to retrieve tx: {}", err))
+                                .ok(),
+                            _ => None,
+                        };
+                        if tx.is_some() {
+                            info!("successfully retrieved tx: {:?}", hex::encode(&task.hash));
+                        }
+                        tx_event
+                            .send(SyncerdBridgeEvent {
+                                event: Event::TransactionRetrieved(TransactionRetrieved {
+                                    id: task.id,
+                                    tx,
+                                }),
+                                source: syncerd_task.source,
+                            })
+                            .await
+                            .expect("error sending transaction retrieved event");
+                    }
+                    Task::EstimateFee(task) => {
+                        let sat_per_vbyte = match Client::new(&server.url, server.auth.clone())
+                            .and_then(|client| {
+                                client.estimate_smart_fee(task.blocks_until_confirmation, None)
+                            }) {
+                            // the fee rate is given per kilo virtual byte, rounded up
+                            Ok(estimate) => estimate
+                                .fee_rate
+                                .map(|fee_rate| (fee_rate.as_sat() + 999) / 1000),
+                            Err(err) => {
+                                error!("failed to estimate fee: {}", err);
+                                None
+                            }
+                        };
+                        tx_event
+                            .send(SyncerdBridgeEvent {
+                                event: Event::FeeEstimated(FeeEstimated {
+                                    id: task.id,
+                                    sat_per_vbyte,
+                                }),
+                                source: syncerd_task.source,
+                            })
+                            .await
+                            .expect("error sending fee estimated event");
+                    }
+                    Task::SweepAddress(_) => {
+                        error!("sweep address not implemented for bitcoin syncer");
+                    }
+                    Task::Abort(task) => {
+                        let mut state_guard = state.lock().await;
+                        let respond = match task.respond {
+                            Boolean::True => true,
+                            Boolean::False => false,
+                        };
+                        state_guard
+                            .abort(task.task_target, syncerd_task.source, respond)
+                            .await;
+                        drop(state_guard);
+                    }
+                    Task::BroadcastTransaction(task) => {
+                        let error = match Client::new(&server.url, server.auth.clone())
+                            .and_then(|client| client.send_raw_transaction(&task.tx))
+                        {
+                            Ok(txid) => {
+                                info!("Successfully broadcasted: {}", txid.bright_yellow_italic());
+                                None
+                            }
+                            Err(err) => {
+                                error!("failed to broadcast tx: {}", err.err());
+                                Some(format!("failed to broadcast tx: {}", err.err()))
+                            }
+                        };
+                        tx_event
+                            .send(SyncerdBridgeEvent {
+                                event: Event::TransactionBroadcasted(TransactionBroadcasted {
+                                    id: task.id,
+                                    tx: task.tx,
+                                    error,
+                                }),
+                                source: syncerd_task.source,
+                            })
+                            .await
+                            .expect("error sending transaction broadcast event");
+                    }
+                    Task::WatchAddress(task) => match task.addendum.clone() {
+                        AddressAddendum::Bitcoin(_) => {
+                            let mut state_guard = state.lock().await;
+                            state_guard
+                                .watch_address(task.clone(), syncerd_task.source)
+                                .expect("Bitcoin Task::WatchAddress");
+                            drop(state_guard);
+                        }
+                        _ => {
+                            error!(
+                                "Aborting watch address task - unable to decode address addendum"
+                            );
+                            let mut state_guard = state.lock().await;
+                            state_guard
+                                .abort(TaskTarget::TaskId(task.id), syncerd_task.source, true)
+                                .await;
+                            drop(state_guard);
+                        }
+                    },
+                    Task::WatchHeight(task) => {
+                        let mut state_guard = state.lock().await;
+                        state_guard.watch_height(task, syncerd_task.source).await;
+                        drop(state_guard);
+                    }
+                    Task::WatchTransaction(task) => {
+                        let mut state_guard = state.lock().await;
+                        state_guard.watch_transaction(task, syncerd_task.source);
+                        drop(state_guard);
+                    }
+                },
+                Err(TryRecvError::Disconnected) => return,
+                Err(TryRecvError::Empty) => {
+                    // do nothing
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    });
+}
+
+/// Poll the chain tip, the watched addresses and the watched transactions with a single
+/// connection, bitcoind serializing the requests anyway
+fn polling(state: Arc<Mutex<SyncerState>>, server: BitcoindServer) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        // outer loop ensures the polling restarts if there is an error
+        loop {
+            let mut rpc = match BitcoindRpc::new(&server) {
+                Ok(rpc) => rpc,
+                Err(err) => {
+                    error!(
+                        "failed to spawn bitcoind rpc client ({}): {}",
+                        &server.url, err
+                    );
+                    // wait a bit before retrying the connection
+                    tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
+                    continue;
+                }
+            };
+
+            let mut state_guard = state.lock().await;
+            state_guard
+                .change_height(rpc.height, rpc.block_hash.to_vec())
+                .await;
+            drop(state_guard);
+            // inner loop actually polls
+            loop {
+                let block_change = match rpc.new_block_check() {
+                    Ok(Some((height, block_hash))) => {
+                        let mut state_guard = state.lock().await;
+                        match find_fork(
+                            &state_guard.recent_blocks(),
+                            height,
+                            &block_hash.to_vec(),
+                            |height| {
+                                future::ready(
+                                    rpc.client
+                                        .get_block_hash(height)
+                                        .map(|hash| hash.to_vec())
+                                        .map_err(Error::from),
+                                )
+                            },
+                        )
+                        .await
+                        {
+                            Ok(Some(fork_height)) => {
+                                rpc.reorg(fork_height);
+                                state_guard.reorg(fork_height).await
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                error!("error checking for a bitcoin reorganization: {}", err)
+                            }
+                        }
+                        let changed = state_guard.change_height(height, block_hash.to_vec()).await;
+                        drop(state_guard);
+                        changed
+                    }
+                    Ok(None) => false,
+                    Err(err) => {
+                        error!("error polling bitcoin block height: {}", err);
+                        // break this loop and retry, since the bitcoind rpc client is probably
+                        // broken
+                        break;
+                    }
+                };
+
+                let state_guard = state.lock().await;
+                let addresses = state_guard.addresses.clone();
+                drop(state_guard);
+                let scripts: HashSet<Script> = addresses
+                    .values()
+                    .filter_map(|address| match &address.task.addendum {
+                        AddressAddendum::Bitcoin(addendum) => Some(addendum.script_pubkey.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                if let Err(err) = rpc.refresh_mempool(&scripts) {
+                    error!("error polling bitcoin mempool: {}", err);
+                    break;
+                }
+
+                for (_, address) in addresses {
+                    if let AddressAddendum::Bitcoin(address_addendum) = address.task.addendum {
+                        match rpc.address_history(&address_addendum) {
+                            Ok(txs) => {
+                                let mut state_guard = state.lock().await;
+                                state_guard
+                                    .change_address(
+                                        AddressAddendum::Bitcoin(address_addendum),
+                                        create_set(txs),
+                                    )
+                                    .await;
+                                drop(state_guard);
+                            }
+                            Err(err) => error!("error in bitcoin address polling: {}", err),
+                        }
+                    }
+                }
+
+                // confirmations only change with new blocks, unseen transactions may appear in
+                // the mempool at any time
+                rpc.query_transactions(Arc::clone(&state), !block_change)
+                    .await;
+
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+            // wait a bit before retrying the connection
+            tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
+        }
+    })
+}
+
+fn address_tx(tx: &Transaction, script: &Script) -> Option<AddressTx> {
+    let our_amount: u64 = tx
+        .output
+        .iter()
+        .filter(|output| &output.script_pubkey == script)
+        .map(|output| output.value)
+        .sum();
+    if our_amount == 0 {
+        return None;
+    }
+    Some(AddressTx {
+        our_amount,
+        tx_id: tx.txid().to_vec(),
+        tx: bitcoin::consensus::serialize(tx),
+    })
+}
+
+#[derive(Default)]
+pub struct BitcoindSyncer {}
+
+impl BitcoindSyncer {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Synclet for BitcoindSyncer {
+    fn run(
+        &mut self,
+        receive_task_channel: Receiver<SyncerdTask>,
+        tx: zmq::Socket,
+        syncer_address: Vec<u8>,
+        opts: &Opts,
+        _network: Network,
+        polling_enabled: bool,
+    ) -> Result<(), Error> {
+        if !polling_enabled {
+            warn!("bitcoind syncer only supports polling for now - switching to polling=true");
+        }
+        let server = BitcoindServer::from_opts(opts)?;
+        std::thread::spawn(move || {
+            use tokio::runtime::Builder;
+            let rt = Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                let (event_tx, event_rx): (
+                    TokioSender<SyncerdBridgeEvent>,
+                    TokioReceiver<SyncerdBridgeEvent>,
+                ) = tokio::sync::mpsc::channel(120);
+                let state = Arc::new(Mutex::new(SyncerState::new(event_tx.clone())));
+
+                run_syncerd_task_receiver(
+                    server.clone(),
+                    receive_task_channel,
+                    Arc::clone(&state),
+                    event_tx.clone(),
+                )
+                .await;
+                run_syncerd_bridge_event_sender(tx, event_rx, syncer_address).await;
+
+                let res = polling(Arc::clone(&state), server).await;
+                debug!("exiting bitcoind synclet run routine with: {:?}", res);
+            });
+        });
+        Ok(())
+    }
+}
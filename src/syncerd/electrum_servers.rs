@@ -0,0 +1,202 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Prioritized list of Electrum servers shared by the tasks of a Bitcoin syncer, tracking the
+//! health of each server to fail over to the next one.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Cap of the exponential backoff applied to a failing server, in multiples of the retry timeout
+const MAX_BACKOFF_EXP: u32 = 6;
+/// Number of blocks a server may lag behind the tips reported by the other servers before being
+/// considered stale
+const TIP_TOLERANCE: u64 = 1;
+
+#[derive(Clone, Debug)]
+struct ServerHealth {
+    url: String,
+    failures: u32,
+    last_failure: Option<Instant>,
+    tip: Option<u64>,
+}
+
+impl ServerHealth {
+    fn in_backoff(&self, retry_timeout: Duration) -> bool {
+        match self.last_failure {
+            Some(last_failure) if self.failures > 0 => {
+                let exp = (self.failures - 1).min(MAX_BACKOFF_EXP);
+                last_failure.elapsed() < retry_timeout * 2u32.pow(exp)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ElectrumServers {
+    servers: Arc<Mutex<Vec<ServerHealth>>>,
+    retry_timeout: Duration,
+}
+
+impl ElectrumServers {
+    /// Servers are given by decreasing priority
+    pub fn new(urls: Vec<String>, retry_timeout: Duration) -> Self {
+        let servers = urls
+            .into_iter()
+            .map(|url| ServerHealth {
+                url,
+                failures: 0,
+                last_failure: None,
+                tip: None,
+            })
+            .collect();
+        Self {
+            servers: Arc::new(Mutex::new(servers)),
+            retry_timeout,
+        }
+    }
+
+    /// Servers to try by priority, skipping the ones failing recently; all of them if none is
+    /// left
+    pub fn candidates(&self) -> Vec<String> {
+        let servers = self.servers.lock().expect("electrum servers lock poisoned");
+        let healthy: Vec<String> = servers
+            .iter()
+            .filter(|server| !server.in_backoff(self.retry_timeout))
+            .map(|server| server.url.clone())
+            .collect();
+        if healthy.is_empty() {
+            servers.iter().map(|server| server.url.clone()).collect()
+        } else {
+            healthy
+        }
+    }
+
+    /// Servers other than `url`, to cross-check its tip
+    pub fn others(&self, url: &str) -> Vec<String> {
+        self.servers
+            .lock()
+            .expect("electrum servers lock poisoned")
+            .iter()
+            .filter(|server| server.url != url)
+            .map(|server| server.url.clone())
+            .collect()
+    }
+
+    pub fn report_failure(&self, url: &str) {
+        self.update(url, |server| {
+            server.failures += 1;
+            server.last_failure = Some(Instant::now());
+        });
+    }
+
+    pub fn report_success(&self, url: &str, tip: u64) {
+        self.update(url, |server| {
+            server.failures = 0;
+            server.last_failure = None;
+            server.tip = Some(tip);
+        });
+    }
+
+    pub fn record_tip(&self, url: &str, tip: u64) {
+        self.update(url, |server| server.tip = Some(tip));
+    }
+
+    /// Whether the server is the best candidate, otherwise the tasks using it should reconnect
+    /// to fail over from a failing server or back to a recovered one of higher priority
+    pub fn preferred(&self, url: &str) -> bool {
+        self.candidates().first().map(String::as_str) == Some(url)
+    }
+
+    /// Whether the server tip lags behind the tips reported by a majority of the healthy
+    /// servers, e.g. a server stuck or lying about the chain. The tips are not verified, so a
+    /// single server reporting a higher tip is not enough to fail over from another one.
+    pub fn lagging(&self, url: &str) -> bool {
+        let servers = self.servers.lock().expect("electrum servers lock poisoned");
+        let tip = match servers.iter().find(|server| server.url == url) {
+            Some(ServerHealth { tip: Some(tip), .. }) => *tip,
+            _ => return false,
+        };
+        let tips: Vec<u64> = servers
+            .iter()
+            .filter(|server| server.url == url || server.failures == 0)
+            .filter_map(|server| server.tip)
+            .collect();
+        let ahead = tips
+            .iter()
+            .filter(|other_tip| **other_tip > tip + TIP_TOLERANCE)
+            .count();
+        ahead * 2 > tips.len()
+    }
+
+    fn update(&self, url: &str, f: impl FnOnce(&mut ServerHealth)) {
+        if let Some(server) = self
+            .servers
+            .lock()
+            .expect("electrum servers lock poisoned")
+            .iter_mut()
+            .find(|server| server.url == url)
+        {
+            f(server);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn electrum_servers_failover() {
+        let servers = ElectrumServers::new(
+            vec![
+                "tcp://a".to_string(),
+                "tcp://b".to_string(),
+                "tcp://c".to_string(),
+            ],
+            Duration::from_secs(60),
+        );
+        servers.report_failure("tcp://c");
+        assert_eq!(servers.candidates(), vec!["tcp://a", "tcp://b"]);
+        assert!(servers.preferred("tcp://a"));
+
+        servers.report_failure("tcp://a");
+        assert_eq!(servers.candidates(), vec!["tcp://b"]);
+        assert!(!servers.preferred("tcp://a"));
+        assert!(servers.preferred("tcp://b"));
+
+        // all servers failing, retry them all by priority
+        servers.report_failure("tcp://b");
+        assert_eq!(servers.candidates(), vec!["tcp://a", "tcp://b", "tcp://c"]);
+        assert!(servers.preferred("tcp://a"));
+
+        servers.report_success("tcp://b", 100);
+        servers.report_success("tcp://a", 100);
+        servers.report_success("tcp://c", 100);
+        assert_eq!(servers.candidates(), vec!["tcp://a", "tcp://b", "tcp://c"]);
+        assert!(!servers.lagging("tcp://a"));
+
+        // a single server claiming a higher tip is not trusted
+        servers.record_tip("tcp://b", 102);
+        assert!(!servers.lagging("tcp://a"));
+        assert!(!servers.lagging("tcp://b"));
+
+        // a majority of the servers agree on the higher tip
+        servers.record_tip("tcp://c", 102);
+        assert!(servers.lagging("tcp://a"));
+        assert!(!servers.lagging("tcp://b"));
+        assert_eq!(servers.others("tcp://a"), vec!["tcp://b", "tcp://c"]);
+    }
+}
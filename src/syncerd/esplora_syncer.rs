@@ -0,0 +1,670 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Bitcoin synclet backed by the HTTP REST API of an Esplora block explorer, for users that can
+//! only reach block explorers over HTTP(S).
+
+use crate::error::{Error, SyncerError};
+use crate::rpc::request::SyncerdBridgeEvent;
+use crate::syncerd::bitcoin_syncer::run_syncerd_bridge_event_sender;
+use crate::syncerd::opts::Opts;
+use crate::syncerd::runtime::SyncerdTask;
+use crate::syncerd::runtime::Synclet;
+use crate::syncerd::syncer_state::AddressTx;
+use crate::syncerd::syncer_state::SyncerState;
+use crate::syncerd::syncer_state::{create_set, find_fork};
+use crate::syncerd::types::{AddressAddendum, Boolean, Task};
+use crate::syncerd::BtcAddressAddendum;
+use crate::syncerd::Event;
+use crate::syncerd::FeeEstimated;
+use crate::syncerd::TaskTarget;
+use crate::syncerd::TransactionBroadcasted;
+use crate::syncerd::TransactionRetrieved;
+use crate::LogStyle;
+use bitcoin::hashes::{
+    hex::{FromHex, ToHex},
+    sha256, Hash,
+};
+use bitcoin::{BlockHash, Script, Transaction, Txid};
+use farcaster_core::blockchain::Network;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver as TokioReceiver;
+use tokio::sync::mpsc::Sender as TokioSender;
+use tokio::sync::Mutex;
+
+const RETRY_TIMEOUT: u64 = 5;
+/// Number of confirmed transactions returned per page by the Esplora history endpoints
+const CHAIN_TXS_PER_PAGE: usize = 25;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+struct TxStatus {
+    confirmed: bool,
+    block_height: Option<u64>,
+    block_hash: Option<BlockHash>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+struct TxOut {
+    scriptpubkey: String,
+    value: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+struct EsploraBlock {
+    height: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+struct EsploraTx {
+    txid: Txid,
+    vout: Vec<TxOut>,
+    status: TxStatus,
+}
+
+/// Async client of the Esplora REST API
+#[derive(Clone, Debug)]
+pub struct EsploraClient {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl EsploraClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    async fn get_text(&self, path: &str) -> Result<Option<String>, Error> {
+        let res = self
+            .http
+            .get(&format!("{}{}", self.url, path))
+            .send()
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(res.error_for_status()?.text().await?))
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        let res = self
+            .http
+            .get(&format!("{}{}", self.url, path))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(res.json().await?)
+    }
+
+    pub async fn tip(&self) -> Result<(u64, BlockHash), Error> {
+        let hash = self
+            .get_text("/blocks/tip/hash")
+            .await?
+            .ok_or(SyncerError::InvalidConfig)?;
+        let hash = BlockHash::from_hex(hash.trim())?;
+        // the height is read from the tip block itself, a block found between two tip requests
+        // would pair the height of a tip with the hash of another
+        let block: EsploraBlock = self.get_json(&format!("/block/{}", hash)).await?;
+        Ok((block.height, hash))
+    }
+
+    pub async fn block_hash_at(&self, height: u64) -> Result<BlockHash, Error> {
+        let hash = self
+            .get_text(&format!("/block-height/{}", height))
+            .await?
+            .ok_or(SyncerError::InvalidConfig)?;
+        Ok(BlockHash::from_hex(hash.trim())?)
+    }
+
+    /// Raw transaction, `None` if unknown to the explorer
+    pub async fn tx_raw(&self, txid: &Txid) -> Result<Option<Vec<u8>>, Error> {
+        match self.get_text(&format!("/tx/{}/hex", txid)).await? {
+            Some(hex) => Ok(Some(
+                hex::decode(hex.trim()).map_err(|e| Error::Farcaster(e.to_string()))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn tx_status(&self, txid: &Txid) -> Result<TxStatus, Error> {
+        self.get_json(&format!("/tx/{}/status", txid)).await
+    }
+
+    /// Broadcast the transaction, returning the explorer error message on failure
+    pub async fn broadcast(&self, tx: &[u8]) -> Result<Txid, String> {
+        let res = self
+            .http
+            .post(&format!("{}/tx", self.url))
+            .body(hex::encode(tx))
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        let status = res.status();
+        let body = res.text().await.map_err(|err| err.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{}: {}", status, body.trim()));
+        }
+        Txid::from_hex(body.trim()).map_err(|err| err.to_string())
+    }
+
+    /// Fee rate in satoshi per virtual byte to confirm within `blocks`, rounded up. Esplora only
+    /// estimates some confirmation targets, the closest one below `blocks` is used
+    pub async fn fee_estimate(&self, blocks: u16) -> Result<Option<u64>, Error> {
+        let estimates: HashMap<String, f64> = self.get_json("/fee-estimates").await?;
+        let mut estimates: Vec<(u16, f64)> = estimates
+            .into_iter()
+            .filter_map(|(target, rate)| u16::from_str(&target).ok().map(|target| (target, rate)))
+            .collect();
+        estimates.sort_by_key(|(target, _)| *target);
+        let estimate = estimates
+            .iter()
+            .rev()
+            .find(|(target, _)| *target <= blocks)
+            .or_else(|| estimates.first());
+        Ok(estimate.map(|(_, rate)| rate.ceil() as u64))
+    }
+
+    /// History of the script, mempool transactions first, down to the transactions confirmed
+    /// above `from_height`
+    async fn script_history(
+        &self,
+        script: &Script,
+        from_height: u64,
+    ) -> Result<Vec<EsploraTx>, Error> {
+        // unlike Electrum, Esplora expects the script hash in natural byte order
+        let script_hash = sha256::Hash::hash(script.as_bytes()).to_hex();
+
+        let mut txs: Vec<EsploraTx> = self
+            .get_json(&format!("/scripthash/{}/txs", script_hash))
+            .await?;
+        let mut chain_txs = txs.iter().filter(|tx| tx.status.confirmed).count();
+        // confirmed transactions are sorted by descending height, fetch more pages only as long
+        // as they may hold transactions above the minimum height
+        while chain_txs >= CHAIN_TXS_PER_PAGE
+            && txs
+                .last()
+                .and_then(|tx| tx.status.block_height)
+                .map_or(false, |height| height > from_height)
+        {
+            let last_seen = txs.last().expect("checked above").txid;
+            let page: Vec<EsploraTx> = self
+                .get_json(&format!(
+                    "/scripthash/{}/txs/chain/{}",
+                    script_hash, last_seen
+                ))
+                .await?;
+            chain_txs = page.len();
+            txs.extend(page);
+        }
+        Ok(txs)
+    }
+}
+
+pub struct EsploraRpc {
+    client: EsploraClient,
+    height: u64,
+    block_hash: BlockHash,
+    /// raw transactions of the watched addresses histories, not fetched again on each poll
+    raw_txs: HashMap<Txid, Vec<u8>>,
+    /// transactions of `raw_txs` found in a history since the last `forget_unused_raw_txs`
+    used_raw_txs: HashSet<Txid>,
+}
+
+impl EsploraRpc {
+    async fn new(esplora_server: &str) -> Result<Self, Error> {
+        let client = EsploraClient::new(esplora_server);
+        let (height, block_hash) = client.tip().await?;
+        debug!("New EsploraRpc at height {:?}", height);
+        Ok(Self {
+            client,
+            height,
+            block_hash,
+            raw_txs: none!(),
+            used_raw_txs: none!(),
+        })
+    }
+
+    /// Returns the new tip if it changed since the last check
+    async fn new_block_check(&mut self) -> Result<Option<(u64, BlockHash)>, Error> {
+        let (height, block_hash) = self.client.tip().await?;
+        if height == self.height && block_hash == self.block_hash {
+            return Ok(None);
+        }
+        self.height = height;
+        self.block_hash = block_hash;
+        trace!("new height received: {:?}", self.height);
+        Ok(Some((height, block_hash)))
+    }
+
+    /// Transactions paying to the address, with the same semantic as the Electrum synclet: a
+    /// transaction confirmed at or below the minimum height is skipped
+    async fn address_history(
+        &mut self,
+        address: &BtcAddressAddendum,
+    ) -> Result<Vec<AddressTx>, Error> {
+        let script_hex = address.script_pubkey.to_hex();
+        let mut addr_txs = vec![];
+        for tx in self
+            .client
+            .script_history(&address.script_pubkey, address.from_height)
+            .await?
+        {
+            if let Some(height) = tx.status.block_height {
+                if height <= address.from_height {
+                    continue;
+                }
+            }
+            let our_amount: u64 = tx
+                .vout
+                .iter()
+                .filter(|output| output.scriptpubkey == script_hex)
+                .map(|output| output.value)
+                .sum();
+            if our_amount == 0 {
+                debug!("ignoring outgoing transaction in handle address notification, continuing");
+                continue;
+            }
+            let raw = match self.raw_txs.get(&tx.txid) {
+                Some(raw) => raw.clone(),
+                None => match self.client.tx_raw(&tx.txid).await? {
+                    Some(raw) => {
+                        self.raw_txs.insert(tx.txid, raw.clone());
+                        raw
+                    }
+                    // the transaction may have been dropped from the mempool in the meantime
+                    None => continue,
+                },
+            };
+            self.used_raw_txs.insert(tx.txid);
+            addr_txs.push(AddressTx {
+                our_amount,
+                tx_id: tx.txid.to_vec(),
+                tx: raw,
+            });
+        }
+        Ok(addr_txs)
+    }
+
+    /// Forget the cached raw transactions no longer in the history of a watched address
+    fn forget_unused_raw_txs(&mut self) {
+        let used = std::mem::take(&mut self.used_raw_txs);
+        self.raw_txs.retain(|txid, _| used.contains(txid));
+    }
+
+    async fn query_transactions(&self, state: Arc<Mutex<SyncerState>>, unseen: bool) {
+        let state_guard = state.lock().await;
+        let txids: Vec<Vec<u8>> = if unseen {
+            state_guard
+                .unseen_transactions
+                .iter()
+                .map(|task_id| state_guard.transactions[task_id].task.hash.clone())
+                .collect()
+        } else {
+            state_guard
+                .transactions
+                .iter()
+                .map(|(_, watched_tx)| watched_tx.task.hash.clone())
+                .collect()
+        };
+        drop(state_guard);
+        for tx_id in txids.iter() {
+            let txid = match Txid::from_slice(tx_id) {
+                Ok(txid) => txid,
+                Err(err) => {
+                    error!("invalid watched transaction id: {}", err);
+                    continue;
+                }
+            };
+            // Esplora reports unknown transactions as unconfirmed, check they exist first
+            let tx = match self.client.tx_raw(&txid).await {
+                Ok(tx) => tx,
+                Err(err) => {
+                    // do not report the transaction as not found because of a network error
+                    error!("error getting transaction: {}", err);
+                    continue;
+                }
+            };
+            let (block_hash, confirmations, tx) = match tx {
+                Some(tx) => {
+                    let status = match self.client.tx_status(&txid).await {
+                        Ok(status) => status,
+                        Err(err) => {
+                            error!("error getting transaction status: {}", err);
+                            continue;
+                        }
+                    };
+                    let confirmations = match status.block_height {
+                        Some(height) if status.confirmed => {
+                            (self.height + 1).saturating_sub(height) as u32
+                        }
+                        _ => 0,
+                    };
+                    (status.block_hash, Some(confirmations), tx)
+                }
+                None => (None, None, vec![]),
+            };
+            debug!("Updated tx: {}", &txid);
+            let mut state_guard = state.lock().await;
+            state_guard
+                .change_transaction(
+                    tx_id.clone(),
+                    block_hash.map(|hash| hash.to_vec()),
+                    confirmations,
+                    tx,
+                )
+                .await;
+            drop(state_guard);
+        }
+    }
+}
+
+async fn run_syncerd_task_receiver(
+    esplora_server: String,
+    receive_task_channel: Receiver<SyncerdTask>,
+    state: Arc<Mutex<SyncerState>>,
+    tx_event: TokioSender<SyncerdBridgeEvent>,
+) {
+    let task_receiver = Arc::new(Mutex::new(receive_task_channel));
+    let client = EsploraClient::new(&esplora_server);
+    tokio::spawn(async move {
+        loop {
+            // this is a hack around the Receiver not being Sync
+            let guard = task_receiver.lock().await;
+            let syncerd_task = guard.try_recv();
+            drop(guard);
+            match syncerd_task {
+                Ok(syncerd_task) => match syncerd_task.task {
+                    Task::GetTx(task) => {
+                        let tx = match Txid::from_slice(&task.hash) {
+                            Ok(txid) => match client.tx_raw(&txid).await {
+                                Ok(raw) => raw.and_then(|raw| {
+                                    bitcoin::consensus::deserialize::<Transaction>(&raw).ok()
+                                }),
+                                Err(err) => {
+                                    info!("failed to retrieve tx: {}", err);
+                                    None
+                                }
+                            },
+                            Err(_) => None,
+                        };
+                        if tx.is_some() {
+                            info!("successfully retrieved tx: {:?}", hex::encode(&task.hash));
+                        }
+                        tx_event
+                            .send(SyncerdBridgeEvent {
+                                event: Event::TransactionRetrieved(TransactionRetrieved {
+                                    id: task.id,
+                                    tx,
+                                }),
+                                source: syncerd_task.source,
+                            })
+                            .await
+                            .expect("error sending transaction retrieved event");
+                    }
+                    Task::EstimateFee(task) => {
+                        let sat_per_vbyte = client
+                            .fee_estimate(task.blocks_until_confirmation)
+                            .await
+                            .unwrap_or_else(|err| {
+                                error!("failed to estimate fee: {}", err);
+                                None
+                            });
+                        tx_event
+                            .send(SyncerdBridgeEvent {
+                                event: Event::FeeEstimated(FeeEstimated {
+                                    id: task.id,
+                                    sat_per_vbyte,
+                                }),
+                                source: syncerd_task.source,
+                            })
+                            .await
+                            .expect("error sending fee estimated event");
+                    }
+                    Task::SweepAddress(_) => {
+                        error!("sweep address not implemented for bitcoin syncer");
+                    }
+                    Task::Abort(task) => {
+                        let mut state_guard = state.lock().await;
+                        let respond = match task.respond {
+                            Boolean::True => true,
+                            Boolean::False => false,
+                        };
+                        state_guard
+                            .abort(task.task_target, syncerd_task.source, respond)
+                            .await;
+                        drop(state_guard);
+                    }
+                    Task::BroadcastTransaction(task) => {
+                        let error = match client.broadcast(&task.tx).await {
+                            Ok(txid) => {
+                                info!("Successfully broadcasted: {}", txid.bright_yellow_italic());
+                                None
+                            }
+                            Err(err) => {
+                                error!("failed to broadcast tx: {}", err.err());
+                                Some(format!("failed to broadcast tx: {}", err))
+                            }
+                        };
+                        tx_event
+                            .send(SyncerdBridgeEvent {
+                                event: Event::TransactionBroadcasted(TransactionBroadcasted {
+                                    id: task.id,
+                                    tx: task.tx,
+                                    error,
+                                }),
+                                source: syncerd_task.source,
+                            })
+                            .await
+                            .expect("error sending transaction broadcast event");
+                    }
+                    Task::WatchAddress(task) => match task.addendum.clone() {
+                        AddressAddendum::Bitcoin(_) => {
+                            let mut state_guard = state.lock().await;
+                            state_guard
+                                .watch_address(task.clone(), syncerd_task.source)
+                                .expect("Bitcoin Task::WatchAddress");
+                            drop(state_guard);
+                        }
+                        _ => {
+                            error!(
+                                "Aborting watch address task - unable to decode address addendum"
+                            );
+                            let mut state_guard = state.lock().await;
+                            state_guard
+                                .abort(TaskTarget::TaskId(task.id), syncerd_task.source, true)
+                                .await;
+                            drop(state_guard);
+                        }
+                    },
+                    Task::WatchHeight(task) => {
+                        let mut state_guard = state.lock().await;
+                        state_guard.watch_height(task, syncerd_task.source).await;
+                        drop(state_guard);
+                    }
+                    Task::WatchTransaction(task) => {
+                        let mut state_guard = state.lock().await;
+                        state_guard.watch_transaction(task, syncerd_task.source);
+                        drop(state_guard);
+                    }
+                },
+                Err(TryRecvError::Disconnected) => return,
+                Err(TryRecvError::Empty) => {
+                    // do nothing
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    });
+}
+
+/// Poll the chain tip, the watched addresses and the watched transactions, Esplora offering no
+/// subscriptions
+fn polling(state: Arc<Mutex<SyncerState>>, esplora_server: String) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        // outer loop ensures the polling restarts if there is an error
+        loop {
+            let mut rpc = match EsploraRpc::new(&esplora_server).await {
+                Ok(rpc) => rpc,
+                Err(err) => {
+                    error!(
+                        "failed to spawn esplora client ({}): {}",
+                        &esplora_server, err
+                    );
+                    // wait a bit before retrying the connection
+                    tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
+                    continue;
+                }
+            };
+
+            let mut state_guard = state.lock().await;
+            state_guard
+                .change_height(rpc.height, rpc.block_hash.to_vec())
+                .await;
+            drop(state_guard);
+            // inner loop actually polls
+            loop {
+                let block_change = match rpc.new_block_check().await {
+                    Ok(Some((height, block_hash))) => {
+                        let mut state_guard = state.lock().await;
+                        let client = &rpc.client;
+                        match find_fork(
+                            &state_guard.recent_blocks(),
+                            height,
+                            &block_hash.to_vec(),
+                            |height| async move {
+                                Ok::<_, Error>(client.block_hash_at(height).await?.to_vec())
+                            },
+                        )
+                        .await
+                        {
+                            Ok(Some(fork_height)) => state_guard.reorg(fork_height).await,
+                            Ok(None) => {}
+                            Err(err) => {
+                                error!("error checking for a bitcoin reorganization: {}", err)
+                            }
+                        }
+                        let changed = state_guard.change_height(height, block_hash.to_vec()).await;
+                        drop(state_guard);
+                        changed
+                    }
+                    Ok(None) => false,
+                    Err(err) => {
+                        error!("error polling bitcoin block height: {}", err);
+                        // break this loop and retry with a fresh client
+                        break;
+                    }
+                };
+
+                let state_guard = state.lock().await;
+                let addresses = state_guard.addresses.clone();
+                drop(state_guard);
+                for (_, address) in addresses {
+                    if let AddressAddendum::Bitcoin(address_addendum) = address.task.addendum {
+                        match rpc.address_history(&address_addendum).await {
+                            Ok(txs) => {
+                                let mut state_guard = state.lock().await;
+                                state_guard
+                                    .change_address(
+                                        AddressAddendum::Bitcoin(address_addendum),
+                                        create_set(txs),
+                                    )
+                                    .await;
+                                drop(state_guard);
+                            }
+                            Err(err) => error!("error in bitcoin address polling: {}", err),
+                        }
+                    }
+                }
+                rpc.forget_unused_raw_txs();
+
+                // confirmations only change with new blocks, unseen transactions may appear in
+                // the mempool at any time
+                rpc.query_transactions(Arc::clone(&state), !block_change)
+                    .await;
+
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+            // wait a bit before retrying the connection
+            tokio::time::sleep(std::time::Duration::from_secs(RETRY_TIMEOUT)).await;
+        }
+    })
+}
+
+#[derive(Default)]
+pub struct EsploraSyncer {}
+
+impl EsploraSyncer {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Synclet for EsploraSyncer {
+    fn run(
+        &mut self,
+        receive_task_channel: Receiver<SyncerdTask>,
+        tx: zmq::Socket,
+        syncer_address: Vec<u8>,
+        opts: &Opts,
+        _network: Network,
+        polling_enabled: bool,
+    ) -> Result<(), Error> {
+        if !polling_enabled {
+            warn!("esplora syncer only supports polling - switching to polling=true");
+        }
+        let esplora_server = opts.esplora_server.clone().ok_or_else(|| {
+            error!("Missing --esplora-server argument");
+            SyncerError::InvalidConfig
+        })?;
+        std::thread::spawn(move || {
+            use tokio::runtime::Builder;
+            let rt = Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                let (event_tx, event_rx): (
+                    TokioSender<SyncerdBridgeEvent>,
+                    TokioReceiver<SyncerdBridgeEvent>,
+                ) = tokio::sync::mpsc::channel(120);
+                let state = Arc::new(Mutex::new(SyncerState::new(event_tx.clone())));
+
+                run_syncerd_task_receiver(
+                    esplora_server.clone(),
+                    receive_task_channel,
+                    Arc::clone(&state),
+                    event_tx.clone(),
+                )
+                .await;
+                run_syncerd_bridge_event_sender(tx, event_rx, syncer_address).await;
+
+                let res = polling(Arc::clone(&state), esplora_server).await;
+                debug!("exiting esplora synclet run routine with: {:?}", res);
+            });
+        });
+        Ok(())
+    }
+}
@@ -13,7 +13,12 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 pub mod bitcoin_syncer;
+pub mod bitcoind_syncer;
+pub mod electrum_servers;
+pub mod esplora_syncer;
+pub mod monero_scanner;
 pub mod monero_syncer;
+pub mod monero_wallets;
 pub mod syncer_state;
 pub mod types;
 
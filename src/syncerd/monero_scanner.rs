@@ -0,0 +1,654 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Detection of the outputs received by the watched Monero addresses, scanning the blocks and
+//! the transaction pool fetched from monerod with the view key and public spend key of each
+//! address, without any wallet in monero-wallet-rpc.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use monero::consensus::deserialize;
+use monero::{Hash, ViewPair};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::Error;
+use crate::syncerd::syncer_state::AddressTx;
+use crate::syncerd::XmrAddressAddendum;
+
+/// Number of blocks scanned at most per call, to report the outputs found in a long catch up
+/// along the way
+const MAX_BLOCKS_PER_SCAN: u64 = 100;
+
+/// Number of the last scanned block hashes kept to detect the reorgs
+const MAX_REORG_DEPTH: u64 = 100;
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct GetBlockCountResult {
+    count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct BlockHeader {
+    hash: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct GetBlockHeaderResult {
+    block_header: BlockHeader,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct GetBlockResult {
+    block_header: BlockHeader,
+    /// Transactions of the block, the coinbase excluded; absent for an empty block
+    #[serde(default)]
+    tx_hashes: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct TransactionPoolHashes {
+    #[serde(default)]
+    tx_hashes: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct GetTransactionsEntry {
+    tx_hash: String,
+    as_hex: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "serde_crate")]
+struct GetTransactionsResult {
+    /// Transactions found, the unknown ones being omitted
+    #[serde(default)]
+    txs: Vec<GetTransactionsEntry>,
+}
+
+#[derive(Debug)]
+struct ScannedTx {
+    /// Height of the block including the transaction, none while in the pool
+    height: Option<u64>,
+    tx: AddressTx,
+}
+
+#[derive(Debug, Default)]
+struct ScannedAddress {
+    /// Next block height to scan for the address
+    next_height: u64,
+    /// Transactions paying to the address, found in the scanned blocks or in the pool
+    txs: HashMap<Hash, ScannedTx>,
+}
+
+/// Scanner of the Monero chain for the outputs of the watched addresses
+#[derive(Debug)]
+pub struct MoneroScanner {
+    url: String,
+    http: reqwest::Client,
+    addresses: HashMap<XmrAddressAddendum, ScannedAddress>,
+    /// Pool transactions already scanned for all the watched addresses
+    scanned_pool: HashSet<Hash>,
+    /// Hashes of the last scanned blocks by height
+    block_hashes: BTreeMap<u64, Hash>,
+}
+
+impl MoneroScanner {
+    pub fn new(daemon_url: &str) -> Self {
+        Self {
+            url: daemon_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            addresses: none!(),
+            scanned_pool: none!(),
+            block_hashes: none!(),
+        }
+    }
+
+    /// Scan the new blocks and the transaction pool for the watched addresses, returning the
+    /// transactions paying to each of them found so far
+    pub async fn scan(
+        &mut self,
+        watched: Vec<XmrAddressAddendum>,
+    ) -> Result<HashMap<XmrAddressAddendum, Vec<AddressTx>>, Error> {
+        let watched: HashSet<XmrAddressAddendum> = watched.into_iter().collect();
+        self.addresses
+            .retain(|address, _| watched.contains(address));
+        for address in watched {
+            if !self.addresses.contains_key(&address) {
+                // the pool must be scanned again for the new address
+                self.scanned_pool.clear();
+                let next_height = address.from_height + 1;
+                self.addresses.insert(
+                    address,
+                    ScannedAddress {
+                        next_height,
+                        txs: none!(),
+                    },
+                );
+            }
+        }
+
+        if let Some(start) = self
+            .addresses
+            .values()
+            .map(|scanned| scanned.next_height)
+            .min()
+        {
+            let tip = self.block_count().await?.saturating_sub(1);
+            if let Some(fork) = self.reorg_fork(tip).await? {
+                warn!("monero chain reorganized above height {}", fork);
+                self.rewind(fork);
+            }
+            let start = self
+                .addresses
+                .values()
+                .map(|scanned| scanned.next_height)
+                .min()
+                .unwrap_or(start);
+            let end = tip.min(start + MAX_BLOCKS_PER_SCAN - 1);
+            for height in start..=end {
+                let (block_hash, tx_hashes) = self.block(height).await?;
+                let txs = self.transactions(&tx_hashes).await?;
+                self.record_block(height, block_hash, &txs);
+            }
+            self.scan_pool(tip).await?;
+        }
+
+        Ok(self
+            .addresses
+            .iter()
+            .map(|(address, scanned)| {
+                (
+                    address.clone(),
+                    scanned.txs.values().map(|found| found.tx.clone()).collect(),
+                )
+            })
+            .collect())
+    }
+
+    /// Height of the last scanned block still in the chain ending at `tip`, if a reorg dropped
+    /// some of the scanned blocks
+    async fn reorg_fork(&self, tip: u64) -> Result<Option<u64>, Error> {
+        let mut reorged = false;
+        for (&height, hash) in self.block_hashes.iter().rev() {
+            if height <= tip && self.block_hash(height).await? == *hash {
+                return Ok(if reorged { Some(height) } else { None });
+            }
+            reorged = true;
+        }
+        // none of the kept blocks is left in the chain
+        Ok(self
+            .block_hashes
+            .keys()
+            .next()
+            .map(|height| height.saturating_sub(1)))
+    }
+
+    /// Forget the blocks above `fork` and the transactions they included, to scan the new
+    /// chain again from there
+    fn rewind(&mut self, fork: u64) {
+        self.block_hashes.retain(|&height, _| height <= fork);
+        for (address, scanned) in self.addresses.iter_mut() {
+            scanned
+                .txs
+                .retain(|_, found| found.height.map_or(true, |height| height <= fork));
+            scanned.next_height = scanned
+                .next_height
+                .min(fork + 1)
+                .max(address.from_height + 1);
+        }
+    }
+
+    /// Check the transactions of the block at `height` for the addresses waiting for it
+    fn record_block(
+        &mut self,
+        height: u64,
+        block_hash: Hash,
+        txs: &[(Hash, Vec<u8>, monero::Transaction)],
+    ) {
+        // selected before moving them forward, an address starting at this height has its next
+        // height past it already and must not scan it
+        let waiting: HashSet<XmrAddressAddendum> = self
+            .addresses
+            .iter_mut()
+            .filter(|(_, scanned)| scanned.next_height <= height)
+            .map(|(address, scanned)| {
+                scanned.next_height = height + 1;
+                address.clone()
+            })
+            .collect();
+        self.check_outputs(txs, Some(height), |address| waiting.contains(address));
+        self.block_hashes.insert(height, block_hash);
+        if let Some(oldest) = height.checked_sub(MAX_REORG_DEPTH) {
+            self.block_hashes = self.block_hashes.split_off(&(oldest + 1));
+        }
+    }
+
+    /// Scan the pool transactions not scanned yet, forgetting the ones that left the pool
+    async fn scan_pool(&mut self, tip: u64) -> Result<(), Error> {
+        let pool: HashSet<Hash> = self
+            .http
+            .get(&format!("{}/get_transaction_pool_hashes", self.url))
+            .send()
+            .await
+            .map_err(anyhow::Error::from)?
+            .json::<TransactionPoolHashes>()
+            .await
+            .map_err(anyhow::Error::from)?
+            .tx_hashes
+            .iter()
+            .filter_map(|hash| parse_hash(hash).ok())
+            .collect();
+        self.forget_dropped(&pool, tip);
+        let new: Vec<Hash> = pool
+            .into_iter()
+            .filter(|hash| !self.scanned_pool.contains(hash))
+            .collect();
+        if new.is_empty() {
+            return Ok(());
+        }
+        let txs = self.transactions(&new).await?;
+        self.check_outputs(&txs, None, |_| true);
+        self.scanned_pool.extend(new);
+        Ok(())
+    }
+
+    /// Forget the transactions that left the pool without being found in a block by the
+    /// addresses scanned up to `tip`
+    fn forget_dropped(&mut self, pool: &HashSet<Hash>, tip: u64) {
+        self.scanned_pool.retain(|hash| pool.contains(hash));
+        for scanned in self
+            .addresses
+            .values_mut()
+            .filter(|scanned| scanned.next_height > tip)
+        {
+            scanned
+                .txs
+                .retain(|hash, found| found.height.is_some() || pool.contains(hash));
+        }
+    }
+
+    /// Record the transactions paying to the addresses selected by `filter`, included in the
+    /// block at `height` or in the pool
+    fn check_outputs(
+        &mut self,
+        txs: &[(Hash, Vec<u8>, monero::Transaction)],
+        height: Option<u64>,
+        filter: impl Fn(&XmrAddressAddendum) -> bool,
+    ) {
+        for (address, scanned) in self
+            .addresses
+            .iter_mut()
+            .filter(|(address, _)| filter(address))
+        {
+            let viewpair = ViewPair {
+                spend: address.spend_key,
+                view: address.view_key,
+            };
+            for (hash, raw_tx, tx) in txs {
+                // only the primary address of the view pair is watched
+                let owned = match tx.check_outputs(&viewpair, 0..1, 0..1) {
+                    Ok(owned) => owned,
+                    Err(err) => {
+                        warn!(
+                            "failed to scan monero transaction {}: {}",
+                            hex::encode(hash.0),
+                            err
+                        );
+                        continue;
+                    }
+                };
+                if owned.is_empty() {
+                    continue;
+                }
+                let our_amount = owned
+                    .iter()
+                    .filter_map(|out| out.amount())
+                    .map(|amount| amount.as_pico())
+                    .sum();
+                debug!(
+                    "monero transaction {} pays {} piconero",
+                    hex::encode(hash.0),
+                    our_amount
+                );
+                scanned.txs.insert(
+                    *hash,
+                    ScannedTx {
+                        height,
+                        tx: AddressTx {
+                            our_amount,
+                            tx_id: hash.0.to_vec(),
+                            tx: raw_tx.clone(),
+                        },
+                    },
+                );
+            }
+        }
+    }
+
+    async fn json_rpc<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, Error> {
+        let res: JsonRpcResponse<T> = self
+            .http
+            .post(&format!("{}/json_rpc", self.url))
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": "0",
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .map_err(anyhow::Error::from)?
+            .json()
+            .await
+            .map_err(anyhow::Error::from)?;
+        match (res.result, res.error) {
+            (Some(result), None) => Ok(result),
+            (_, Some(JsonRpcError { code, message })) => {
+                Err(
+                    anyhow::anyhow!("monerod {} failed with code {}: {}", method, code, message)
+                        .into(),
+                )
+            }
+            (None, None) => Err(anyhow::anyhow!("monerod {} returned no result", method).into()),
+        }
+    }
+
+    async fn block_count(&self) -> Result<u64, Error> {
+        let res: GetBlockCountResult = self.json_rpc("get_block_count", json!({})).await?;
+        Ok(res.count)
+    }
+
+    async fn block_hash(&self, height: u64) -> Result<Hash, Error> {
+        let res: GetBlockHeaderResult = self
+            .json_rpc("get_block_header_by_height", json!({ "height": height }))
+            .await?;
+        parse_hash(&res.block_header.hash)
+    }
+
+    /// Hash and transactions of the block at `height`
+    async fn block(&self, height: u64) -> Result<(Hash, Vec<Hash>), Error> {
+        let res: GetBlockResult = self
+            .json_rpc("get_block", json!({ "height": height }))
+            .await?;
+        let tx_hashes = res
+            .tx_hashes
+            .iter()
+            .map(|hash| parse_hash(hash))
+            .collect::<Result<_, _>>()?;
+        Ok((parse_hash(&res.block_header.hash)?, tx_hashes))
+    }
+
+    /// Raw and decoded transactions known to monerod, skipping the ones failing to decode
+    async fn transactions(
+        &self,
+        hashes: &[Hash],
+    ) -> Result<Vec<(Hash, Vec<u8>, monero::Transaction)>, Error> {
+        if hashes.is_empty() {
+            return Ok(vec![]);
+        }
+        let res: GetTransactionsResult = self
+            .http
+            .post(&format!("{}/get_transactions", self.url))
+            .json(&json!({
+                "txs_hashes": hashes.iter().map(|hash| hex::encode(hash.0)).collect::<Vec<_>>(),
+                "decode_as_json": false,
+                "prune": false,
+            }))
+            .send()
+            .await
+            .map_err(anyhow::Error::from)?
+            .json()
+            .await
+            .map_err(anyhow::Error::from)?;
+        Ok(res
+            .txs
+            .into_iter()
+            .filter_map(|entry| match decode_transaction(&entry) {
+                Ok(decoded) => Some(decoded),
+                Err(err) => {
+                    warn!(
+                        "skipping undecodable monero transaction {}: {}",
+                        entry.tx_hash, err
+                    );
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+fn decode_transaction(
+    entry: &GetTransactionsEntry,
+) -> Result<(Hash, Vec<u8>, monero::Transaction), Error> {
+    let raw_tx = hex::decode(&entry.as_hex).map_err(anyhow::Error::from)?;
+    let tx = deserialize(&raw_tx).map_err(anyhow::Error::from)?;
+    Ok((parse_hash(&entry.tx_hash)?, raw_tx, tx))
+}
+
+fn parse_hash(hash: &str) -> Result<Hash, Error> {
+    let mut buffer = [0u8; 32];
+    hex::decode_to_slice(hash, &mut buffer).map_err(anyhow::Error::from)?;
+    Ok(Hash::from(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_address(seed: u8, from_height: u64) -> XmrAddressAddendum {
+        let spend = monero::PrivateKey::from_slice(&[seed; 32]).unwrap();
+        XmrAddressAddendum {
+            spend_key: monero::PublicKey::from_private_key(&spend),
+            view_key: monero::PrivateKey::from_slice(&[seed + 1; 32]).unwrap(),
+            from_height,
+        }
+    }
+
+    /// Transaction paying `amount` to the primary address of `address`
+    fn test_tx(address: &XmrAddressAddendum, amount: u64) -> (Hash, Vec<u8>, monero::Transaction) {
+        use monero::blockdata::transaction::{
+            ExtraField, RctSig, SubField, TransactionPrefix, TxOut, TxOutTarget,
+        };
+        use monero::consensus::encode::VarInt;
+        use monero::cryptonote::onetime_key::KeyGenerator;
+
+        let random = monero::PrivateKey::from_slice(&[amount as u8; 32]).unwrap();
+        let key = KeyGenerator::from_random(
+            monero::PublicKey::from_private_key(&address.view_key),
+            address.spend_key,
+            random,
+        )
+        .one_time_key(0);
+        let tx = monero::Transaction {
+            prefix: TransactionPrefix {
+                version: VarInt(1),
+                unlock_time: VarInt(0),
+                inputs: vec![],
+                outputs: vec![TxOut {
+                    amount: VarInt(amount),
+                    target: TxOutTarget::ToKey { key },
+                }],
+                extra: ExtraField(vec![SubField::TxPublicKey(
+                    monero::PublicKey::from_private_key(&random),
+                )]),
+            },
+            signatures: vec![],
+            rct_signatures: RctSig { sig: None, p: None },
+        };
+        (
+            Hash::from([amount as u8; 32]),
+            monero::consensus::serialize(&tx),
+            tx,
+        )
+    }
+
+    fn test_scanner(addresses: &[XmrAddressAddendum]) -> MoneroScanner {
+        let mut scanner = MoneroScanner::new("http://localhost:38081");
+        for address in addresses {
+            scanner.addresses.insert(
+                address.clone(),
+                ScannedAddress {
+                    next_height: address.from_height + 1,
+                    txs: none!(),
+                },
+            );
+        }
+        scanner
+    }
+
+    #[test]
+    fn check_outputs_records_owned_transactions() {
+        let ours = test_address(1, 10);
+        let other = test_address(3, 10);
+        let mut scanner = test_scanner(&[ours.clone(), other.clone()]);
+        let (hash, raw_tx, tx) = test_tx(&ours, 5);
+
+        scanner.check_outputs(&[(hash, raw_tx.clone(), tx)], Some(11), |_| true);
+
+        let found = &scanner.addresses[&ours].txs[&hash];
+        assert_eq!(found.height, Some(11));
+        assert_eq!(found.tx.tx_id, hash.0.to_vec());
+        assert_eq!(found.tx.tx, raw_tx);
+        assert!(scanner.addresses[&other].txs.is_empty());
+    }
+
+    #[test]
+    fn record_block_scans_the_addresses_waiting_for_it() {
+        let early = test_address(1, 10);
+        let late = test_address(3, 20);
+        let mut scanner = test_scanner(&[early.clone(), late.clone()]);
+        let txs = vec![test_tx(&early, 5), test_tx(&late, 6)];
+
+        scanner.record_block(11, Hash::from([11; 32]), &txs);
+
+        assert_eq!(scanner.addresses[&early].next_height, 12);
+        assert_eq!(scanner.addresses[&early].txs.len(), 1);
+        // the block is below the starting height of the late address
+        assert_eq!(scanner.addresses[&late].next_height, 21);
+        assert!(scanner.addresses[&late].txs.is_empty());
+        assert_eq!(scanner.block_hashes[&11], Hash::from([11; 32]));
+    }
+
+    #[test]
+    fn record_block_keeps_the_last_block_hashes() {
+        let address = test_address(1, 0);
+        let mut scanner = test_scanner(&[address.clone()]);
+
+        for height in 1..=MAX_REORG_DEPTH + 10 {
+            scanner.record_block(height, Hash::from([height as u8; 32]), &[]);
+        }
+
+        assert_eq!(scanner.block_hashes.len() as u64, MAX_REORG_DEPTH);
+        assert_eq!(scanner.block_hashes.keys().next(), Some(&11));
+        assert_eq!(
+            scanner.addresses[&address].next_height,
+            MAX_REORG_DEPTH + 11
+        );
+    }
+
+    #[test]
+    fn rewind_forgets_reorged_blocks() {
+        let address = test_address(1, 10);
+        let late = test_address(3, 12);
+        let mut scanner = test_scanner(&[address.clone(), late.clone()]);
+        let (hash_11, raw_tx, tx) = test_tx(&address, 5);
+        scanner.record_block(11, Hash::from([11; 32]), &[(hash_11, raw_tx, tx)]);
+        let (hash_12, raw_tx, tx) = test_tx(&address, 6);
+        scanner.record_block(12, Hash::from([12; 32]), &[(hash_12, raw_tx, tx)]);
+        scanner.record_block(13, Hash::from([13; 32]), &[]);
+
+        scanner.rewind(11);
+
+        let scanned = &scanner.addresses[&address];
+        assert_eq!(scanned.next_height, 12);
+        assert!(scanned.txs.contains_key(&hash_11));
+        assert!(!scanned.txs.contains_key(&hash_12));
+        // never rewound below the starting height
+        assert_eq!(scanner.addresses[&late].next_height, 13);
+        assert_eq!(scanner.block_hashes.keys().last(), Some(&11));
+    }
+
+    #[test]
+    fn forget_dropped_pool_transactions() {
+        let synced = test_address(1, 10);
+        let lagging = test_address(3, 10);
+        let mut scanner = test_scanner(&[synced.clone(), lagging.clone()]);
+        scanner.addresses.get_mut(&synced).unwrap().next_height = 21;
+        let (hash, raw_tx, tx) = test_tx(&synced, 5);
+        let (lagging_hash, lagging_raw_tx, lagging_tx) = test_tx(&lagging, 6);
+        let txs = vec![
+            (hash, raw_tx, tx),
+            (lagging_hash, lagging_raw_tx, lagging_tx),
+        ];
+        scanner.check_outputs(&txs, None, |_| true);
+        scanner.scanned_pool.extend(vec![hash, lagging_hash]);
+
+        scanner.forget_dropped(&HashSet::new(), 20);
+
+        assert!(scanner.scanned_pool.is_empty());
+        assert!(scanner.addresses[&synced].txs.is_empty());
+        // the transaction may be in a block not scanned yet for the lagging address
+        assert!(scanner.addresses[&lagging].txs.contains_key(&lagging_hash));
+    }
+
+    #[test]
+    fn record_block_skips_the_starting_height_of_an_address() {
+        let early = test_address(1, 10);
+        let starting = test_address(3, 11);
+        let mut scanner = test_scanner(&[early.clone(), starting.clone()]);
+
+        // block 11 is the starting height of the second address, it is not scanned for it
+        scanner.record_block(
+            11,
+            Hash::from([11; 32]),
+            &[test_tx(&early, 5), test_tx(&starting, 6)],
+        );
+        assert_eq!(scanner.addresses[&early].next_height, 12);
+        assert_eq!(scanner.addresses[&early].txs.len(), 1);
+        assert_eq!(scanner.addresses[&starting].next_height, 12);
+        assert!(scanner.addresses[&starting].txs.is_empty());
+
+        // both addresses scan the next block
+        let (hash, raw_tx, tx) = test_tx(&starting, 7);
+        scanner.record_block(12, Hash::from([12; 32]), &[(hash, raw_tx, tx)]);
+        assert_eq!(scanner.addresses[&early].next_height, 13);
+        assert_eq!(scanner.addresses[&starting].next_height, 13);
+        assert_eq!(scanner.addresses[&starting].txs[&hash].height, Some(12));
+    }
+}
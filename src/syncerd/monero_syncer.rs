@@ -5,11 +5,14 @@ use crate::internet2::TypedEnum;
 use crate::rpc::request::SyncerdBridgeEvent;
 use crate::rpc::Request;
 use crate::service::LogStyle;
-use crate::syncerd::opts::Opts;
+use crate::syncerd::monero_scanner::MoneroScanner;
+use crate::syncerd::monero_wallets::MoneroWallets;
+use crate::syncerd::opts::{MoneroBackend, Opts};
 use crate::syncerd::runtime::SyncerdTask;
 use crate::syncerd::runtime::Synclet;
 use crate::syncerd::syncer_state::create_set;
 use crate::syncerd::syncer_state::AddressTx;
+use crate::syncerd::syncer_state::RetiredWallet;
 use crate::syncerd::syncer_state::SyncerState;
 use crate::syncerd::types::{AddressAddendum, Boolean, SweepAddressAddendum, Task};
 use crate::syncerd::Event;
@@ -24,8 +27,10 @@ use monero_rpc::{
     GenerateFromKeysArgs, GetBlockHeaderSelector, GetTransfersCategory, GetTransfersSelector,
     PrivateKeyType, TransferType,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io;
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::mpsc::{Receiver, TryRecvError};
 use std::sync::Arc;
@@ -58,8 +63,11 @@ struct AddressNotif {
 #[derive(Debug)]
 pub struct Transaction {
     tx_id: Vec<u8>,
+    /// Zero while in the transaction pool, none if unknown to the daemon
     confirmations: Option<u32>,
     block_hash: Option<Vec<u8>>,
+    /// Raw transaction, empty if unknown to the daemon
+    tx: Vec<u8>,
 }
 
 impl MoneroRpc {
@@ -84,46 +92,53 @@ impl MoneroRpc {
     }
 
     async fn get_transactions(&mut self, tx_ids: Vec<Vec<u8>>) -> Result<Vec<Transaction>, Error> {
-        let mut buffer: [u8; 32] = [0; 32];
         let monero_txids = tx_ids
             .iter()
-            .map(|tx_id| {
-                hex::decode_to_slice(hex::encode(tx_id), &mut buffer).unwrap();
-                Hash::from(buffer)
-            })
+            .filter(|tx_id| tx_id.len() == 32)
+            .map(|tx_id| Hash::from_slice(tx_id))
             .collect();
 
+        // the full transactions are needed to report the raw transactions
         let txs = self
             .daemon_rpc
-            .get_transactions(monero_txids, Some(true), Some(true))
+            .get_transactions(monero_txids, Some(false), Some(false))
             .await?;
 
         let block_height = self.get_height().await?;
 
         let mut transactions: Vec<Transaction> = vec![];
-        if txs.txs.is_some() {
-            for tx in txs.txs.unwrap().iter() {
-                let mut block_hash: Option<Vec<u8>> = None;
-                let mut confirmations: Option<u32> = Some(0);
-                if let Some(tx_height) = tx.block_height {
-                    if tx_height > 0 {
-                        block_hash = Some(self.get_block_hash(tx_height).await?);
-                    }
-                    confirmations = Some((block_height - tx_height + 1) as u32);
+        for tx in txs.txs.unwrap_or_default().iter() {
+            let tx_id = hex::decode(tx.tx_hash.to_string()).map_err(anyhow::Error::from)?;
+            let (confirmations, block_hash) = match tx.block_height {
+                // the daemon omits the height of the pool transactions, or sets it to zero
+                Some(tx_height) if !tx.in_pool && tx_height > 0 => {
+                    let confirmations = block_height.saturating_sub(tx_height) + 1;
+                    (
+                        Some(confirmations as u32),
+                        Some(self.get_block_hash(tx_height).await?),
+                    )
                 }
-                transactions.push(Transaction {
-                    tx_id: hex::decode(tx.tx_hash.to_string()).unwrap(),
-                    confirmations,
-                    block_hash,
-                });
-            }
+                _ => (Some(0), None),
+            };
+            trace!(
+                "monero tx {} with {:?} confirmations",
+                hex::encode(&tx_id),
+                confirmations
+            );
+            transactions.push(Transaction {
+                tx_id,
+                confirmations,
+                block_hash,
+                tx: hex::decode(&tx.as_hex).unwrap_or_default(),
+            });
         }
-        if txs.missed_tx.is_some() {
-            transactions.extend(txs.missed_tx.unwrap().iter().map(|tx| Transaction {
-                tx_id: hex::decode(tx.to_string()).unwrap(),
+        for tx in txs.missed_tx.unwrap_or_default().iter() {
+            transactions.push(Transaction {
+                tx_id: hex::decode(tx.to_string()).map_err(anyhow::Error::from)?,
                 confirmations: None,
                 block_hash: None,
-            }));
+                tx: vec![],
+            });
         }
         Ok(transactions)
     }
@@ -145,18 +160,19 @@ impl MoneroRpc {
         &mut self,
         address_addendum: XmrAddressAddendum,
         network: monero::Network,
-        wallet_mutex: Arc<Mutex<monero_rpc::WalletClient>>,
+        wallets: MoneroWallets,
     ) -> Result<AddressNotif, Error> {
         let keypair = monero::ViewPair {
             spend: address_addendum.spend_key,
             view: address_addendum.view_key,
         };
         let address = monero::Address::from_viewpair(network, &keypair);
-        let wallet_filename = format!("watch:{}", address);
+        let wallet_filename = watch_wallet_filename(&address_addendum, network);
         let password = s!(" ");
 
+        let (url, wallet_mutex) = wallets.open(&wallet_filename);
         let wallet = wallet_mutex.lock().await;
-        trace!("taking check address lock");
+        trace!("taking check address lock on rpc wallet {}", url);
 
         match wallet
             .open_wallet(wallet_filename.clone(), Some(password.clone()))
@@ -183,10 +199,9 @@ impl MoneroRpc {
             }
         }
 
+        // only the incoming transfers, mined or in the pool, pay to the watched address
         let mut category_selector: HashMap<GetTransfersCategory, bool> = HashMap::new();
         category_selector.insert(GetTransfersCategory::In, true);
-        category_selector.insert(GetTransfersCategory::Out, true);
-        category_selector.insert(GetTransfersCategory::Pending, true);
         category_selector.insert(GetTransfersCategory::Pool, true);
 
         let selector = GetTransfersSelector {
@@ -203,17 +218,34 @@ impl MoneroRpc {
         trace!("releasing check address lock");
         drop(wallet);
 
-        let mut address_txs: Vec<AddressTx> = vec![];
+        // a transaction may pay several transfers to the address
+        let mut amounts: HashMap<Vec<u8>, u64> = HashMap::new();
         for (_category, mut txs) in transfers.drain() {
             for tx in txs.drain(..) {
-                debug!("FIXME: tx set to vec![0]");
-                address_txs.push(AddressTx {
-                    our_amount: tx.amount,
-                    tx_id: tx.txid.0,
-                    tx: vec![0],
-                });
+                *amounts.entry(tx.txid.0).or_insert(0) += tx.amount;
             }
         }
+        if amounts.is_empty() {
+            return Ok(AddressNotif { txs: vec![] });
+        }
+
+        // the raw transactions are fetched from the daemon, the ones it does not know yet are
+        // reported on a later poll
+        let transactions = self
+            .get_transactions(amounts.keys().cloned().collect())
+            .await?;
+        let address_txs = transactions
+            .into_iter()
+            .filter(|tx| tx.confirmations.is_some())
+            .filter_map(|tx| {
+                let our_amount = amounts.remove(&tx.tx_id)?;
+                Some(AddressTx {
+                    our_amount,
+                    tx_id: tx.tx_id,
+                    tx: tx.tx,
+                })
+            })
+            .collect();
 
         Ok(AddressNotif { txs: address_txs })
     }
@@ -224,13 +256,13 @@ async fn sweep_address(
     view: monero::PrivateKey,
     spend: monero::PrivateKey,
     network: &monero::Network,
-    wallet_mutex: Arc<Mutex<monero_rpc::WalletClient>>,
+    wallets: MoneroWallets,
     restore_height: Option<u64>,
 ) -> Result<Vec<Vec<u8>>, Error> {
     let keypair = monero::KeyPair { view, spend };
     let password = s!(" ");
     let address = monero::Address::from_keypair(*network, &keypair);
-    let wallet_filename = format!("sweep:{}", address);
+    let wallet_filename = sweep_wallet_filename(view, spend, *network);
 
     // add some extra leeway for the wallet restore height
     // let restore_height = match from_height {
@@ -238,8 +270,9 @@ async fn sweep_address(
     //     _ => None,
     // };
 
+    let (url, wallet_mutex) = wallets.open(&wallet_filename);
     let wallet = wallet_mutex.lock().await;
-    trace!("taking sweep wallet lock");
+    trace!("taking sweep wallet lock on rpc wallet {}", url);
 
     while let Err(err) = wallet
         .open_wallet(wallet_filename.clone(), Some(password.clone()))
@@ -314,12 +347,180 @@ async fn sweep_address(
     }
 }
 
+/// Name of the watch-only wallet file of an address
+fn watch_wallet_filename(address: &XmrAddressAddendum, network: monero::Network) -> String {
+    let viewpair = monero::ViewPair {
+        spend: address.spend_key,
+        view: address.view_key,
+    };
+    format!(
+        "watch:{}",
+        monero::Address::from_viewpair(network, &viewpair)
+    )
+}
+
+/// Name of the spending wallet file of a swept address
+fn sweep_wallet_filename(
+    view: monero::PrivateKey,
+    spend: monero::PrivateKey,
+    network: monero::Network,
+) -> String {
+    let keypair = monero::KeyPair { view, spend };
+    format!("sweep:{}", monero::Address::from_keypair(network, &keypair))
+}
+
+/// Wallet files used by the running watch and sweep tasks
+fn live_wallet_filenames(state: &SyncerState, network: monero::Network) -> HashSet<String> {
+    let watches = state.addresses.values().filter_map(|watched_address| {
+        match &watched_address.task.addendum {
+            AddressAddendum::Monero(address) => Some(watch_wallet_filename(address, network)),
+            _ => None,
+        }
+    });
+    let sweeps =
+        state
+            .sweep_addresses
+            .values()
+            .filter_map(|sweep_address| match &sweep_address.addendum {
+                SweepAddressAddendum::Monero(sweep) => Some(sweep_wallet_filename(
+                    sweep.view_key,
+                    sweep.spend_key,
+                    network,
+                )),
+                _ => None,
+            });
+    watches.chain(sweeps).collect()
+}
+
+/// Watch and sweep wallet files found in the wallet directory
+fn wallet_dir_filenames(wallet_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut filenames = vec![];
+    for entry in std::fs::read_dir(wallet_dir)? {
+        let filename = entry?.file_name().to_string_lossy().to_string();
+        if let Some(wallet_filename) = filename.strip_suffix(".keys") {
+            if wallet_filename.starts_with("watch:") || wallet_filename.starts_with("sweep:") {
+                filenames.push(wallet_filename.to_string());
+            }
+        }
+    }
+    Ok(filenames)
+}
+
+/// Close the wallet file in its rpc wallet if `opened` and delete it if the wallet directory is
+/// known, returns whether the files were deleted
+async fn remove_wallet(
+    wallets: &MoneroWallets,
+    wallet_filename: &str,
+    opened: bool,
+    wallet_dir: Option<&Path>,
+) -> Result<bool, Error> {
+    if opened {
+        let (url, wallet_mutex) = wallets.get(wallet_filename);
+        let wallet = wallet_mutex.lock().await;
+        trace!("taking remove wallet lock on rpc wallet {}", url);
+        // the rpc wallet keeps the last opened wallet open, which may be this one; the other
+        // tasks always open their wallet first
+        if let Err(err) = wallet.close_wallet().await {
+            debug!("no wallet to close on rpc wallet {}: {:?}", url, err);
+        }
+    }
+    let wallet_dir = match wallet_dir {
+        Some(wallet_dir) => wallet_dir,
+        None => return Ok(false),
+    };
+    for suffix in &["", ".keys", ".address.txt"] {
+        let path = wallet_dir.join(format!("{}{}", wallet_filename, suffix));
+        match std::fs::remove_file(&path) {
+            Ok(()) => trace!("removed {}", path.display()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Remove the wallet files of the retired tasks and keep track of the orphaned ones: the
+/// retired wallets that could not be deleted and the wallet files of the wallet directory not
+/// used by any task
+fn wallet_cleanup(
+    state: Arc<Mutex<SyncerState>>,
+    wallets: MoneroWallets,
+    network: monero::Network,
+    wallet_dir: Option<PathBuf>,
+    orphan_wallets: Arc<std::sync::Mutex<Vec<String>>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut leftovers: HashSet<String> = none!();
+        loop {
+            let mut state_guard = state.lock().await;
+            let retired: Vec<RetiredWallet> = state_guard.retired_wallets.drain(..).collect();
+            let live = live_wallet_filenames(&state_guard, network);
+            drop(state_guard);
+
+            let retired_filenames = retired
+                .iter()
+                .map(|retired_wallet| match retired_wallet {
+                    RetiredWallet::Watch(address) => watch_wallet_filename(address, network),
+                    RetiredWallet::Sweep(sweep) => {
+                        sweep_wallet_filename(sweep.view_key, sweep.spend_key, network)
+                    }
+                })
+                // another task may still use the same wallet
+                .filter(|wallet_filename| !live.contains(wallet_filename))
+                .collect::<HashSet<String>>();
+            for wallet_filename in retired_filenames {
+                // the tasks of the scanner backend never open a watch wallet
+                let opened = wallets.release(&wallet_filename);
+                match remove_wallet(&wallets, &wallet_filename, opened, wallet_dir.as_deref()).await
+                {
+                    Ok(true) => {
+                        info!("Removed monero wallet {}", wallet_filename);
+                        leftovers.remove(&wallet_filename);
+                    }
+                    Ok(false) if opened => {
+                        debug!("closed monero wallet {}", wallet_filename);
+                        leftovers.insert(wallet_filename);
+                    }
+                    Ok(false) => {}
+                    Err(err) => {
+                        warn!(
+                            "failed to remove monero wallet {}: {}",
+                            wallet_filename, err
+                        );
+                        leftovers.insert(wallet_filename);
+                    }
+                }
+            }
+
+            let mut orphans: HashSet<String> = leftovers.difference(&live).cloned().collect();
+            if let Some(wallet_dir) = &wallet_dir {
+                match wallet_dir_filenames(wallet_dir) {
+                    Ok(filenames) => orphans.extend(
+                        filenames
+                            .into_iter()
+                            .filter(|wallet_filename| !live.contains(wallet_filename)),
+                    ),
+                    Err(err) => warn!("failed to list the monero wallet directory: {}", err),
+                }
+            }
+            let mut orphans: Vec<String> = orphans.into_iter().collect();
+            orphans.sort();
+            *orphan_wallets.lock().expect("orphan wallets lock poisoned") = orphans;
+
+            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        }
+    })
+}
+
 #[derive(Default)]
-pub struct MoneroSyncer {}
+pub struct MoneroSyncer {
+    /// Wallet files not used by any task, refreshed by the cleanup task
+    orphan_wallets: Arc<std::sync::Mutex<Vec<String>>>,
+}
 
 impl MoneroSyncer {
     pub fn new() -> Self {
-        Self {}
+        Self::default()
     }
 }
 
@@ -341,6 +542,9 @@ async fn run_syncerd_task_receiver(
                         Task::GetTx(_) => {
                             error!("get tx not implemented for monero syncer");
                         }
+                        Task::EstimateFee(_) => {
+                            error!("estimate fee not implemented for monero syncer");
+                        }
                         Task::SweepAddress(task) => match task.addendum.clone() {
                             SweepAddressAddendum::Monero(sweep) => {
                                 let addr = sweep.address;
@@ -416,39 +620,50 @@ fn address_polling(
     state: Arc<Mutex<SyncerState>>,
     syncer_servers: MoneroSyncerServers,
     network: monero::Network,
-    wallet_mutex: Arc<Mutex<monero_rpc::WalletClient>>,
+    wallets: MoneroWallets,
 ) -> tokio::task::JoinHandle<()> {
     tokio::task::spawn(async move {
-        let mut rpc = MoneroRpc::new(syncer_servers.monero_daemon);
+        let rpc = MoneroRpc::new(syncer_servers.monero_daemon);
         loop {
             let state_guard = state.lock().await;
             let mut addresses = state_guard.addresses.clone();
             drop(state_guard);
-            for (_, watched_address) in addresses.drain() {
-                let address_addendum = match watched_address.task.addendum {
-                    AddressAddendum::Monero(address) => address,
-                    _ => panic!("should never get an invalid address"),
-                };
-                // we cannot parallelize polling here, since we have to open and close the
-                // wallet
-                let address_transactions = match rpc
-                    .check_address(address_addendum.clone(), network, Arc::clone(&wallet_mutex))
-                    .await
-                {
-                    Ok(address_transactions) => Some(address_transactions),
-                    Err(err) => {
-                        error!("error polling addresses: {:?}", err);
-                        None
-                    }
-                };
-                if let Some(address_transactions) = address_transactions {
-                    let mut state_guard = state.lock().await;
-                    state_guard
-                        .change_address(
-                            AddressAddendum::Monero(address_addendum),
-                            create_set(address_transactions.txs.clone()),
-                        )
-                        .await;
+            // each rpc wallet opens one wallet at a time, the addresses on different rpc
+            // wallets are polled concurrently
+            let handles: Vec<_> = addresses
+                .drain()
+                .map(|(_, watched_address)| {
+                    let address_addendum = match watched_address.task.addendum {
+                        AddressAddendum::Monero(address) => address,
+                        _ => panic!("should never get an invalid address"),
+                    };
+                    let mut rpc = rpc.clone();
+                    let state = Arc::clone(&state);
+                    let wallets = wallets.clone();
+                    tokio::task::spawn(async move {
+                        let address_transactions = match rpc
+                            .check_address(address_addendum.clone(), network, wallets)
+                            .await
+                        {
+                            Ok(address_transactions) => address_transactions,
+                            Err(err) => {
+                                error!("error polling addresses: {:?}", err);
+                                return;
+                            }
+                        };
+                        let mut state_guard = state.lock().await;
+                        state_guard
+                            .change_address(
+                                AddressAddendum::Monero(address_addendum),
+                                create_set(address_transactions.txs),
+                            )
+                            .await;
+                    })
+                })
+                .collect();
+            for handle in handles {
+                if let Err(err) = handle.await {
+                    error!("address polling task failed: {}", err);
                 }
             }
             tokio::time::sleep(std::time::Duration::from_millis(500)).await;
@@ -456,6 +671,45 @@ fn address_polling(
     })
 }
 
+/// Watch the addresses scanning the monero daemon with their view key, without the rpc wallet
+fn address_scanning(
+    state: Arc<Mutex<SyncerState>>,
+    syncer_servers: MoneroSyncerServers,
+) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut scanner = MoneroScanner::new(&syncer_servers.monero_daemon);
+        loop {
+            let state_guard = state.lock().await;
+            let watched = state_guard
+                .addresses
+                .values()
+                .filter_map(|watched_address| match &watched_address.task.addendum {
+                    AddressAddendum::Monero(address) => Some(address.clone()),
+                    _ => None,
+                })
+                .collect();
+            drop(state_guard);
+            match scanner.scan(watched).await {
+                Ok(mut address_transactions) => {
+                    let mut state_guard = state.lock().await;
+                    for (address_addendum, txs) in address_transactions.drain() {
+                        state_guard
+                            .change_address(
+                                AddressAddendum::Monero(address_addendum),
+                                create_set(txs),
+                            )
+                            .await;
+                    }
+                }
+                Err(err) => {
+                    error!("error scanning addresses: {:?}", err);
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    })
+}
+
 fn height_polling(
     state: Arc<Mutex<SyncerState>>,
     syncer_servers: MoneroSyncerServers,
@@ -494,7 +748,7 @@ fn height_polling(
                     let mut state_guard = state.lock().await;
                     for tx in polled_transactions.drain(..) {
                         state_guard
-                            .change_transaction(tx.tx_id, tx.block_hash, tx.confirmations, vec![])
+                            .change_transaction(tx.tx_id, tx.block_hash, tx.confirmations, tx.tx)
                             .await;
                     }
                 }
@@ -506,7 +760,7 @@ fn height_polling(
 
 fn sweep_polling(
     state: Arc<Mutex<SyncerState>>,
-    wallet: Arc<Mutex<monero_rpc::WalletClient>>,
+    wallets: MoneroWallets,
     network: monero::Network,
 ) -> tokio::task::JoinHandle<()> {
     tokio::task::spawn(async move {
@@ -514,27 +768,44 @@ fn sweep_polling(
             let state_guard = state.lock().await;
             let sweep_addresses = state_guard.sweep_addresses.clone();
             drop(state_guard);
-            for (id, sweep_address_task) in sweep_addresses.iter() {
-                if let SweepAddressAddendum::Monero(addendum) = sweep_address_task.addendum.clone()
-                {
-                    let sweep_address_txs = sweep_address(
-                        addendum.address,
-                        addendum.view_key,
-                        addendum.spend_key,
-                        &network,
-                        Arc::clone(&wallet),
-                        sweep_address_task.from_height,
-                    )
-                    .await
-                    .unwrap_or_else(|err| {
-                        warn!("error polling sweep address {:?}, retrying", err);
-                        vec![]
-                    });
-                    if !sweep_address_txs.is_empty() {
-                        let mut state_guard = state.lock().await;
-                        state_guard.success_sweep(id, sweep_address_txs).await;
-                        drop(state_guard);
-                    }
+            let handles: Vec<_> = sweep_addresses
+                .into_iter()
+                .filter_map(
+                    |(id, sweep_address_task)| match sweep_address_task.addendum {
+                        SweepAddressAddendum::Monero(addendum) => {
+                            Some((id, addendum, sweep_address_task.from_height))
+                        }
+                        _ => None,
+                    },
+                )
+                .map(|(id, addendum, from_height)| {
+                    let state = Arc::clone(&state);
+                    let wallets = wallets.clone();
+                    tokio::task::spawn(async move {
+                        let sweep_address_txs = sweep_address(
+                            addendum.address,
+                            addendum.view_key,
+                            addendum.spend_key,
+                            &network,
+                            wallets,
+                            from_height,
+                        )
+                        .await
+                        .unwrap_or_else(|err| {
+                            warn!("error polling sweep address {:?}, retrying", err);
+                            vec![]
+                        });
+                        if !sweep_address_txs.is_empty() {
+                            let mut state_guard = state.lock().await;
+                            state_guard.success_sweep(&id, sweep_address_txs).await;
+                            drop(state_guard);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                if let Err(err) = handle.await {
+                    error!("sweep polling task failed: {}", err);
                 }
             }
             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
@@ -574,7 +845,7 @@ fn unseen_transaction_polling(
                 let mut state_guard = state.lock().await;
                 for tx in polled_transactions.drain(..) {
                     state_guard
-                        .change_transaction(tx.tx_id, tx.block_hash, tx.confirmations, vec![])
+                        .change_transaction(tx.tx_id, tx.block_hash, tx.confirmations, tx.tx)
                         .await;
                 }
             }
@@ -614,8 +885,8 @@ pub struct MoneroSyncerServers {
     /// Monero daemon to use
     pub monero_daemon: String,
 
-    /// Monero rpc wallet to use
-    pub monero_rpc_wallet: String,
+    /// Monero rpc wallets to use
+    pub monero_rpc_wallets: Vec<String>,
 }
 
 impl Synclet for MoneroSyncer {
@@ -633,11 +904,22 @@ impl Synclet for MoneroSyncer {
         }
         let network = network.into();
         if let Some(daemon) = &opts.monero_daemon {
-            if let Some(rpc_wallet) = &opts.monero_rpc_wallet {
+            if !opts.monero_rpc_wallet.is_empty() {
                 let syncer_servers = MoneroSyncerServers {
                     monero_daemon: daemon.clone(),
-                    monero_rpc_wallet: rpc_wallet.clone(),
+                    monero_rpc_wallets: opts.monero_rpc_wallet.clone(),
                 };
+                let backend = opts.monero_backend;
+                let wallet_dir = opts
+                    .monero_wallet_dir
+                    .as_ref()
+                    .map(|dir| PathBuf::from(shellexpand::tilde(dir).to_string()));
+                let orphan_wallets = Arc::clone(&self.orphan_wallets);
+                info!(
+                    "Watching monero addresses with the {} backend, {} rpc wallet(s)",
+                    backend,
+                    syncer_servers.monero_rpc_wallets.len()
+                );
 
                 let _handle = std::thread::spawn(move || {
                     use tokio::runtime::Builder;
@@ -647,10 +929,7 @@ impl Synclet for MoneroSyncer {
                         .build()
                         .unwrap();
                     rt.block_on(async {
-                        let wallet_mutex = Arc::new(Mutex::new(
-                            monero_rpc::RpcClient::new(syncer_servers.monero_rpc_wallet.clone())
-                                .wallet(),
-                        ));
+                        let wallets = MoneroWallets::new(syncer_servers.monero_rpc_wallets.clone());
                         let (event_tx, event_rx): (
                             TokioSender<SyncerdBridgeEvent>,
                             TokioReceiver<SyncerdBridgeEvent>,
@@ -665,12 +944,17 @@ impl Synclet for MoneroSyncer {
                         .await;
                         run_syncerd_bridge_event_sender(tx, event_rx, syncer_address).await;
 
-                        let address_handle = address_polling(
-                            Arc::clone(&state),
-                            syncer_servers.clone(),
-                            network,
-                            Arc::clone(&wallet_mutex),
-                        );
+                        let address_handle = match backend {
+                            MoneroBackend::WalletRpc => address_polling(
+                                Arc::clone(&state),
+                                syncer_servers.clone(),
+                                network,
+                                wallets.clone(),
+                            ),
+                            MoneroBackend::Scanner => {
+                                address_scanning(Arc::clone(&state), syncer_servers.clone())
+                            }
+                        };
 
                         // transaction polling is done in the same loop
                         let height_handle =
@@ -680,13 +964,22 @@ impl Synclet for MoneroSyncer {
                             unseen_transaction_polling(Arc::clone(&state), syncer_servers.clone());
 
                         let sweep_handle =
-                            sweep_polling(Arc::clone(&state), Arc::clone(&wallet_mutex), network);
+                            sweep_polling(Arc::clone(&state), wallets.clone(), network);
+
+                        let cleanup_handle = wallet_cleanup(
+                            Arc::clone(&state),
+                            wallets,
+                            network,
+                            wallet_dir,
+                            orphan_wallets,
+                        );
 
                         let res = tokio::try_join!(
                             address_handle,
                             height_handle,
                             unseen_transaction_handle,
-                            sweep_handle
+                            sweep_handle,
+                            cleanup_handle
                         );
                         debug!("exiting monero synclet run routine with: {:?}", res);
                     });
@@ -701,4 +994,11 @@ impl Synclet for MoneroSyncer {
             Err(SyncerError::InvalidConfig.into())
         }
     }
+
+    fn orphan_wallets(&self) -> Vec<String> {
+        self.orphan_wallets
+            .lock()
+            .expect("orphan wallets lock poisoned")
+            .clone()
+    }
 }
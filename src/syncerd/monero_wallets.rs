@@ -0,0 +1,113 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Pool of Monero rpc wallets shared by the tasks of a Monero syncer. A wallet file is always
+//! opened on the rpc wallet picked from the hash of its filename, so it is found again on the
+//! same rpc wallet after a restart, and the wallet files spread over the rpc wallets so the
+//! tasks on different rpc wallets run concurrently.
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use bitcoin::hashes::{sha256, Hash};
+use tokio::sync::Mutex;
+
+/// Index of the rpc wallet holding the wallet file among `size` rpc wallets
+fn rpc_wallet_index(wallet_filename: &str, size: usize) -> usize {
+    let hash = sha256::Hash::hash(wallet_filename.as_bytes()).into_inner();
+    let prefix = u64::from_be_bytes(hash[..8].try_into().expect("8 bytes slice"));
+    (prefix % size.max(1) as u64) as usize
+}
+
+#[derive(Clone, Debug)]
+pub struct MoneroWallets {
+    urls: Vec<String>,
+    wallets: Vec<Arc<Mutex<monero_rpc::WalletClient>>>,
+    /// Wallet files opened by the tasks of the syncer and not removed yet
+    opened: Arc<std::sync::Mutex<HashSet<String>>>,
+}
+
+impl MoneroWallets {
+    pub fn new(urls: Vec<String>) -> Self {
+        let wallets = urls
+            .iter()
+            .map(|url| Arc::new(Mutex::new(monero_rpc::RpcClient::new(url.clone()).wallet())))
+            .collect();
+        Self {
+            urls,
+            wallets,
+            opened: none!(),
+        }
+    }
+
+    /// Rpc wallet holding the wallet file, with its url
+    pub fn get(&self, wallet_filename: &str) -> (&str, Arc<Mutex<monero_rpc::WalletClient>>) {
+        let index = rpc_wallet_index(wallet_filename, self.wallets.len());
+        (&self.urls[index], Arc::clone(&self.wallets[index]))
+    }
+
+    /// Rpc wallet to open the wallet file on, with its url, keeping track of the wallet file
+    pub fn open(&self, wallet_filename: &str) -> (&str, Arc<Mutex<monero_rpc::WalletClient>>) {
+        self.opened
+            .lock()
+            .expect("monero wallets lock poisoned")
+            .insert(wallet_filename.to_string());
+        self.get(wallet_filename)
+    }
+
+    /// Stop keeping track of the wallet file, returns whether it was opened
+    pub fn release(&self, wallet_filename: &str) -> bool {
+        self.opened
+            .lock()
+            .expect("monero wallets lock poisoned")
+            .remove(wallet_filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monero_wallets_affinity() {
+        let filenames: Vec<String> = (0..20).map(|index| format!("watch:{}", index)).collect();
+        let indexes: Vec<usize> = filenames
+            .iter()
+            .map(|filename| rpc_wallet_index(filename, 3))
+            .collect();
+        assert!(indexes.iter().all(|index| *index < 3));
+        // a wallet file always goes to the same rpc wallet
+        assert_eq!(
+            filenames
+                .iter()
+                .map(|filename| rpc_wallet_index(filename, 3))
+                .collect::<Vec<usize>>(),
+            indexes
+        );
+        // the wallet files spread over the rpc wallets
+        assert!((0..3).all(|index| indexes.contains(&index)));
+        assert_eq!(rpc_wallet_index("watch:0", 1), 0);
+    }
+
+    #[test]
+    fn monero_wallets_release_opened() {
+        let wallets = MoneroWallets::new(vec![s!("http://localhost:38083")]);
+        wallets.open("watch:a");
+        assert!(wallets.release("watch:a"));
+        assert!(!wallets.release("watch:a"));
+        // never opened, e.g. watched with the scanner backend
+        assert!(!wallets.release("watch:b"));
+    }
+}
@@ -50,17 +50,63 @@ pub struct Opts {
     )]
     pub network: Network,
 
-    /// Electrum server to use for Bitcoin syncers
+    /// Backend to use for Bitcoin syncers (electrum, bitcoind, esplora)
+    #[clap(
+        long,
+        default_value = "electrum",
+        possible_values = &["electrum", "bitcoind", "esplora"],
+        parse(try_from_str = BitcoinBackend::from_str)
+    )]
+    pub bitcoin_backend: BitcoinBackend,
+
+    /// Electrum server to use for Bitcoin syncers, repeat the argument to list fallback servers
+    /// by decreasing priority
+    #[clap(long, multiple_occurrences = true, number_of_values = 1)]
+    pub electrum_server: Vec<String>,
+
+    /// Esplora REST API url to use for Bitcoin syncers with the esplora backend
+    #[clap(long)]
+    pub esplora_server: Option<String>,
+
+    /// Bitcoind JSON-RPC url to use for Bitcoin syncers with the bitcoind backend
+    #[clap(long)]
+    pub bitcoind_rpc: Option<String>,
+
+    /// Bitcoind JSON-RPC cookie file, takes precedence over user and password
     #[clap(long)]
-    pub electrum_server: Option<String>,
+    pub bitcoind_rpc_cookie: Option<String>,
+
+    /// Bitcoind JSON-RPC user
+    #[clap(long, requires = "bitcoind-rpc-password-file")]
+    pub bitcoind_rpc_user: Option<String>,
+
+    /// File containing the bitcoind JSON-RPC password, which is never passed on the command line
+    #[clap(long, requires = "bitcoind-rpc-user")]
+    pub bitcoind_rpc_password_file: Option<String>,
 
     /// Monero daemon to use for Monero syncers
     #[clap(long)]
     pub monero_daemon: Option<String>,
 
-    /// Monero rpc wallet to use for Monero syncers
+    /// Monero rpc wallet to use for Monero syncers, repeat to spread the watch and sweep tasks
+    /// over several rpc wallets
+    #[clap(long, multiple_occurrences = true, number_of_values = 1)]
+    pub monero_rpc_wallet: Vec<String>,
+
+    /// Backend watching the addresses of Monero syncers (wallet-rpc, scanner), the sweeps always
+    /// go through the Monero rpc wallet
+    #[clap(
+        long,
+        default_value = "wallet-rpc",
+        possible_values = &["wallet-rpc", "scanner"],
+        parse(try_from_str = MoneroBackend::from_str)
+    )]
+    pub monero_backend: MoneroBackend,
+
+    /// Wallet directory of the Monero rpc wallets, the wallet files of the finished tasks are
+    /// deleted from it; without it they are only closed and listed as orphans
     #[clap(long)]
-    pub monero_rpc_wallet: Option<String>,
+    pub monero_wallet_dir: Option<String>,
 }
 
 #[derive(Clap, Display, Copy, Clone, Hash, PartialEq, Eq, Debug, StrictEncode, StrictDecode)]
@@ -89,6 +135,64 @@ impl FromStr for Coin {
     }
 }
 
+#[derive(Display, Copy, Clone, Hash, PartialEq, Eq, Debug)]
+pub enum BitcoinBackend {
+    /// Syncs through an Electrum server
+    #[display("electrum")]
+    Electrum,
+    /// Syncs through the JSON-RPC interface of a bitcoind node
+    #[display("bitcoind")]
+    Bitcoind,
+    /// Syncs through the HTTP REST API of an Esplora block explorer
+    #[display("esplora")]
+    Esplora,
+}
+
+#[derive(Error, Debug, Display)]
+#[display("invalid bitcoin backend")]
+pub enum BitcoinBackendError {
+    InvalidBackend,
+}
+
+impl FromStr for BitcoinBackend {
+    type Err = BitcoinBackendError;
+    fn from_str(input: &str) -> Result<BitcoinBackend, Self::Err> {
+        match input {
+            "Electrum" | "electrum" => Ok(BitcoinBackend::Electrum),
+            "Bitcoind" | "bitcoind" => Ok(BitcoinBackend::Bitcoind),
+            "Esplora" | "esplora" => Ok(BitcoinBackend::Esplora),
+            _ => Err(BitcoinBackendError::InvalidBackend),
+        }
+    }
+}
+
+#[derive(Display, Copy, Clone, Hash, PartialEq, Eq, Debug)]
+pub enum MoneroBackend {
+    /// Watches the addresses with view-only wallets in the Monero rpc wallet
+    #[display("wallet-rpc")]
+    WalletRpc,
+    /// Scans the blocks and the transaction pool of the Monero daemon with the view keys
+    #[display("scanner")]
+    Scanner,
+}
+
+#[derive(Error, Debug, Display)]
+#[display("invalid monero backend")]
+pub enum MoneroBackendError {
+    InvalidBackend,
+}
+
+impl FromStr for MoneroBackend {
+    type Err = MoneroBackendError;
+    fn from_str(input: &str) -> Result<MoneroBackend, Self::Err> {
+        match input {
+            "WalletRpc" | "wallet-rpc" => Ok(MoneroBackend::WalletRpc),
+            "Scanner" | "scanner" => Ok(MoneroBackend::Scanner),
+            _ => Err(MoneroBackendError::InvalidBackend),
+        }
+    }
+}
+
 impl Opts {
     pub fn process(&mut self) {
         self.shared.process();
@@ -13,8 +13,10 @@
 // If not, see <https://opensource.org/licenses/MIT>.
 
 use crate::syncerd::bitcoin_syncer::BitcoinSyncer;
+use crate::syncerd::bitcoind_syncer::BitcoindSyncer;
+use crate::syncerd::esplora_syncer::EsploraSyncer;
 use crate::syncerd::monero_syncer::MoneroSyncer;
-use crate::syncerd::opts::{Coin, Opts};
+use crate::syncerd::opts::{BitcoinBackend, Coin, Opts};
 use amplify::Wrapper;
 use farcaster_core::blockchain::Network;
 use std::collections::{HashMap, HashSet};
@@ -52,6 +54,11 @@ pub trait Synclet {
         network: Network,
         polling: bool,
     ) -> Result<(), Error>;
+
+    /// Wallet files of the backend no longer used by any task
+    fn orphan_wallets(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 pub struct SyncerdTask {
@@ -73,7 +80,11 @@ pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
 
     let syncer: Box<dyn Synclet> = match coin {
         Coin::Monero => Box::new(MoneroSyncer::new()),
-        Coin::Bitcoin => Box::new(BitcoinSyncer::new()),
+        Coin::Bitcoin => match opts.bitcoin_backend {
+            BitcoinBackend::Electrum => Box::new(BitcoinSyncer::new()),
+            BitcoinBackend::Bitcoind => Box::new(BitcoindSyncer::new()),
+            BitcoinBackend::Esplora => Box::new(EsploraSyncer::new()),
+        },
     };
 
     let mut runtime = Runtime {
@@ -211,6 +222,15 @@ impl Runtime {
                 notify_cli = Some((Some(source), resp));
             }
 
+            (Request::ListOrphanWallets(_), ServiceId::Farcasterd) => {
+                senders.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    source.clone(),
+                    Request::WalletList(self.syncer.orphan_wallets().into_iter().collect()),
+                )?;
+            }
+
             (Request::Terminate, _) => {
                 // terminate all runtimes
                 info!("Received terminate on {}", self.identity());
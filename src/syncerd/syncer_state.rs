@@ -6,8 +6,9 @@ use crate::syncerd::{TaskId, TaskTarget};
 use crate::Error;
 use crate::ServiceId;
 use microservices::rpc_connection::Request;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
+use std::future::Future;
 use std::io;
 use std::marker::{Send, Sized};
 use tokio::sync::mpsc::Sender as TokioSender;
@@ -16,6 +17,9 @@ use crate::serde::Deserialize;
 use crate::syncerd::*;
 use hex;
 
+/// Number of blocks below the tip whose hashes are kept to detect reorganizations
+const REORG_WINDOW: u64 = 100;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Hash, Display)]
 #[display(Debug)]
 pub struct InternalId(u32);
@@ -39,6 +43,7 @@ impl TaskCounter {
 pub struct SyncerState {
     block_height: u64,
     block_hash: Vec<u8>,
+    recent_blocks: BTreeMap<u64, Vec<u8>>,
     tasks_sources: HashMap<InternalId, ServiceId>,
     watch_height: HashMap<InternalId, WatchHeight>,
     lifetimes: HashMap<u64, HashSet<InternalId>>,
@@ -46,10 +51,19 @@ pub struct SyncerState {
     pub transactions: HashMap<InternalId, WatchedTransaction>,
     pub unseen_transactions: HashSet<InternalId>,
     pub sweep_addresses: HashMap<InternalId, SweepAddress>,
+    /// Monero addresses and sweeps whose task is gone, their wallet files can be removed
+    pub retired_wallets: Vec<RetiredWallet>,
     tx_event: TokioSender<SyncerdBridgeEvent>,
     task_count: TaskCounter,
 }
 
+/// Monero task holding a wallet in the rpc wallet
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RetiredWallet {
+    Watch(XmrAddressAddendum),
+    Sweep(SweepXmrAddress),
+}
+
 #[derive(Clone, Debug)]
 pub struct WatchedTransaction {
     pub task: WatchTransaction,
@@ -73,11 +87,41 @@ pub fn create_set<T: std::hash::Hash + Eq>(xs: Vec<T>) -> HashSet<T> {
     xs.into_iter().collect()
 }
 
+/// Walk the recent blocks down from the previous tip until one is still part of the best chain
+/// ending at the new tip, as told by `block_hash_at`. Returns the fork height if blocks of the
+/// previous best chain were disconnected. Blocking backends look the hashes up in a
+/// `future::ready`.
+pub async fn find_fork<F, Fut>(
+    recent_blocks: &[(u64, Vec<u8>)],
+    height: u64,
+    block_hash: &[u8],
+    mut block_hash_at: F,
+) -> Result<Option<u64>, Error>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, Error>>,
+{
+    let previous_tip = recent_blocks.first().map(|(height, _)| *height);
+    if recent_blocks.first() == Some(&(height, block_hash.to_vec())) {
+        return Ok(None);
+    }
+    for (known_height, known_hash) in recent_blocks.iter().filter(|(h, _)| *h <= height) {
+        if block_hash_at(*known_height).await? == *known_hash {
+            return Ok(Some(*known_height).filter(|h| Some(*h) != previous_tip));
+        }
+    }
+    // the reorganization is deeper than the recorded blocks
+    Ok(recent_blocks
+        .last()
+        .map(|(height, _)| height.saturating_sub(1)))
+}
+
 impl SyncerState {
     pub fn new(tx_event: TokioSender<SyncerdBridgeEvent>) -> Self {
         Self {
             block_height: 0,
             block_hash: vec![0],
+            recent_blocks: BTreeMap::new(),
             tasks_sources: HashMap::new(),
             watch_height: HashMap::new(),
             lifetimes: HashMap::new(),
@@ -85,6 +129,7 @@ impl SyncerState {
             transactions: HashMap::new(),
             unseen_transactions: HashSet::new(),
             sweep_addresses: HashMap::new(),
+            retired_wallets: vec![],
             tx_event,
             task_count: TaskCounter(0),
         }
@@ -94,6 +139,15 @@ impl SyncerState {
         self.block_height
     }
 
+    /// Recorded blocks of the best chain, from the tip down
+    pub fn recent_blocks(&self) -> Vec<(u64, Vec<u8>)> {
+        self.recent_blocks
+            .iter()
+            .rev()
+            .map(|(height, hash)| (*height, hash.clone()))
+            .collect()
+    }
+
     pub async fn abort(
         &mut self,
         task_task_id_or_all_tasks: TaskTarget,
@@ -329,8 +383,21 @@ impl SyncerState {
 
     pub async fn change_height(&mut self, height: u64, block: Vec<u8>) -> bool {
         if self.block_height != height || self.block_hash != block {
+            // a different block at a known height or a shorter chain means blocks were
+            // disconnected, if the synclet did not already report the reorganization
+            if self.block_height != 0 && height <= self.block_height {
+                let fork_height = match self.recent_blocks.get(&height) {
+                    Some(known) if *known == block => height,
+                    _ => height.saturating_sub(1),
+                };
+                self.reorg(fork_height).await;
+            }
             self.block_height = height;
             self.block_hash = block.clone();
+            self.recent_blocks.insert(height, block.clone());
+            self.recent_blocks = self
+                .recent_blocks
+                .split_off(&height.saturating_sub(REORG_WINDOW));
 
             self.drop_lifetimes();
 
@@ -355,6 +422,43 @@ impl SyncerState {
         }
     }
 
+    /// Roll the tip back to the last block shared with the new best chain and notify the height
+    /// watchers. The synclets then re-query all the watched transactions as the height changes,
+    /// re-emitting their confirmations against the new best chain.
+    pub async fn reorg(&mut self, fork_height: u64) {
+        if fork_height >= self.block_height {
+            return;
+        }
+        let depth = self.block_height - fork_height;
+        warn!(
+            "blockchain reorganization: {} block(s) disconnected above height {}",
+            depth, fork_height
+        );
+        self.recent_blocks
+            .retain(|height, _| *height <= fork_height);
+        self.block_height = fork_height;
+        self.block_hash = self
+            .recent_blocks
+            .get(&fork_height)
+            .cloned()
+            .unwrap_or_else(|| vec![0]);
+
+        for (id, task) in self.watch_height.iter() {
+            send_event(
+                &self.tx_event,
+                &mut vec![(
+                    Event::Reorg(Reorg {
+                        id: task.id,
+                        fork_height,
+                        depth,
+                    }),
+                    self.tasks_sources.get(id).unwrap().clone(),
+                )],
+            )
+            .await;
+        }
+    }
+
     pub async fn change_address(
         &mut self,
         address_addendum: AddressAddendum,
@@ -409,7 +513,6 @@ impl SyncerState {
                             id: addr.task.id,
                             hash: new_tx.tx_id,
                             amount: new_tx.our_amount,
-                            block: vec![], // eventually this should be removed from the event
                             tx: new_tx.tx,
                         };
                         events.push((
@@ -538,14 +641,7 @@ impl SyncerState {
                 )],
             )
             .await;
-            if let Some(ids) = self.lifetimes.get_mut(&sweep_address.lifetime) {
-                ids.remove(id);
-                if ids.is_empty() {
-                    self.lifetimes.remove(&sweep_address.lifetime);
-                }
-            }
-            self.sweep_addresses.remove(id);
-            self.tasks_sources.remove(id);
+            self.remove_sweep_address(id);
         }
     }
 
@@ -577,11 +673,15 @@ impl SyncerState {
     fn drop_lifetime(&mut self, lifetime: u64) {
         if let Some(tasks) = self.lifetimes.remove(&lifetime) {
             for task in &tasks {
-                self.addresses.remove(task);
+                if let Some(address_transactions) = self.addresses.remove(task) {
+                    self.retire_address(address_transactions.task.addendum);
+                }
                 self.transactions.remove(task);
                 self.unseen_transactions.remove(task);
                 self.watch_height.remove(task);
-                self.sweep_addresses.remove(task);
+                if let Some(sweep_address) = self.sweep_addresses.remove(task) {
+                    self.retire_sweep_address(sweep_address.addendum);
+                }
                 self.tasks_sources.remove(task);
             }
         } else {
@@ -612,7 +712,9 @@ impl SyncerState {
                 }
             }
         }
-        self.addresses.remove(id);
+        if let Some(address_transactions) = self.addresses.remove(id) {
+            self.retire_address(address_transactions.task.addendum);
+        }
         self.tasks_sources.remove(id);
     }
 
@@ -638,9 +740,30 @@ impl SyncerState {
                 }
             }
         }
-        self.sweep_addresses.remove(id);
+        if let Some(sweep_address) = self.sweep_addresses.remove(id) {
+            self.retire_sweep_address(sweep_address.addendum);
+        }
         self.tasks_sources.remove(id);
     }
+
+    fn retire_address(&mut self, addendum: AddressAddendum) {
+        // only the Monero syncer keeps a wallet per task
+        if let AddressAddendum::Monero(address) = addendum {
+            let retired = RetiredWallet::Watch(address);
+            if !self.retired_wallets.contains(&retired) {
+                self.retired_wallets.push(retired);
+            }
+        }
+    }
+
+    fn retire_sweep_address(&mut self, addendum: SweepAddressAddendum) {
+        if let SweepAddressAddendum::Monero(sweep) = addendum {
+            let retired = RetiredWallet::Sweep(sweep);
+            if !self.retired_wallets.contains(&retired) {
+                self.retired_wallets.push(retired);
+            }
+        }
+    }
 }
 
 async fn send_event(
@@ -655,409 +778,515 @@ async fn send_event(
     }
 }
 
-#[tokio::test]
-async fn syncer_state_transaction() {
-    use tokio::sync::mpsc::Receiver as TokioReceiver;
-    let (event_tx, mut event_rx): (
-        TokioSender<SyncerdBridgeEvent>,
-        TokioReceiver<SyncerdBridgeEvent>,
-    ) = tokio::sync::mpsc::channel(120);
-    let mut state = SyncerState::new(event_tx.clone());
-
-    let transaction_task_one = WatchTransaction {
-        id: TaskId(0),
-        lifetime: 1,
-        hash: vec![0],
-        confirmation_bound: 4,
-    };
-    let transaction_task_two = WatchTransaction {
-        id: TaskId(0),
-        lifetime: 3,
-        hash: vec![1],
-        confirmation_bound: 4,
-    };
-    let height_task = WatchHeight {
-        id: TaskId(0),
-        lifetime: 4,
-    };
-    let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
-
-    state.watch_transaction(transaction_task_one.clone(), source1.clone());
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
-        .await;
-    assert!(event_rx.try_recv().is_ok());
-
-    state.watch_transaction(transaction_task_one.clone(), source1.clone());
-    state.watch_transaction(transaction_task_two.clone(), source1.clone());
-    state.watch_height(height_task, source1.clone()).await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 2);
-    assert!(event_rx.try_recv().is_err());
-
-    state
-        .change_transaction(vec![0], none!(), none!(), none!())
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 2);
-    assert!(event_rx.try_recv().is_ok());
-
-    state
-        .change_transaction(vec![0], none!(), Some(0), none!())
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state
-        .change_transaction(vec![0], none!(), Some(0), none!())
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 1);
-    assert!(event_rx.try_recv().is_err());
-
-    state
-        .change_transaction(vec![0], Some(vec![1]), Some(1), none!())
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state
-        .change_transaction(vec![0], Some(vec![1]), Some(1), none!())
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 1);
-    assert!(event_rx.try_recv().is_err());
-
-    state
-        .change_transaction(vec![0], none!(), none!(), none!())
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 2);
-    assert!(event_rx.try_recv().is_ok());
-
-    let source2 = ServiceId::Syncer(Coin::Monero, Network::Mainnet);
-    state.watch_transaction(transaction_task_two.clone(), source2.clone());
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source2.clone(), true)
-        .await;
-    assert_eq!(state.lifetimes.len(), 3);
-    assert_eq!(state.transactions.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 3);
-    assert_eq!(state.unseen_transactions.len(), 2);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.change_height(5, vec![0]).await;
-    assert_eq!(state.lifetimes.len(), 0);
-    assert_eq!(state.transactions.len(), 0);
-    assert_eq!(state.tasks_sources.len(), 0);
-    assert_eq!(state.unseen_transactions.len(), 0);
-    assert!(event_rx.try_recv().is_err());
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn syncer_state_transaction() {
+        use tokio::sync::mpsc::Receiver as TokioReceiver;
+        let (event_tx, mut event_rx): (
+            TokioSender<SyncerdBridgeEvent>,
+            TokioReceiver<SyncerdBridgeEvent>,
+        ) = tokio::sync::mpsc::channel(120);
+        let mut state = SyncerState::new(event_tx.clone());
+
+        let transaction_task_one = WatchTransaction {
+            id: TaskId(0),
+            lifetime: 1,
+            hash: vec![0],
+            confirmation_bound: 4,
+        };
+        let transaction_task_two = WatchTransaction {
+            id: TaskId(0),
+            lifetime: 3,
+            hash: vec![1],
+            confirmation_bound: 4,
+        };
+        let height_task = WatchHeight {
+            id: TaskId(0),
+            lifetime: 4,
+        };
+        let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
 
-#[tokio::test]
-async fn syncer_state_addresses() {
-    use std::str::FromStr;
-    use tokio::sync::mpsc::Receiver as TokioReceiver;
-    let (event_tx, mut event_rx): (
-        TokioSender<SyncerdBridgeEvent>,
-        TokioReceiver<SyncerdBridgeEvent>,
-    ) = tokio::sync::mpsc::channel(120);
-    let mut state = SyncerState::new(event_tx.clone());
-    let address = bitcoin::Address::from_str("32BkaQeAVcd65Vn7pjEziohf5bCiryNQov").unwrap();
-    let addendum = AddressAddendum::Bitcoin(BtcAddressAddendum {
-        address: Some(address.clone()),
-        from_height: 0,
-        script_pubkey: address.script_pubkey(),
-    });
-    let address_task = WatchAddress {
-        id: TaskId(0),
-        lifetime: 1,
-        addendum: addendum.clone(),
-        include_tx: Boolean::False,
-    };
-    let address_task_two = WatchAddress {
-        id: TaskId(0),
-        lifetime: 1,
-        addendum: addendum.clone(),
-        include_tx: Boolean::False,
-    };
-    let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
-
-    state
-        .watch_address(address_task_two.clone(), source1.clone())
-        .unwrap();
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
-        .await;
-    assert_eq!(state.lifetimes.len(), 0);
-    assert_eq!(state.tasks_sources.len(), 0);
-    assert_eq!(state.addresses.len(), 0);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.watch_address(address_task, source1.clone()).unwrap();
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    let address_tx_one = AddressTx {
-        our_amount: 1,
-        tx_id: vec![0; 32],
-        tx: vec![0],
-    };
-    let address_tx_two = AddressTx {
-        our_amount: 1,
-        tx_id: vec![1; 32],
-        tx: vec![0],
-    };
-    let address_tx_three = AddressTx {
-        our_amount: 1,
-        tx_id: vec![2; 32],
-        tx: vec![0],
-    };
-    let address_tx_four = AddressTx {
-        our_amount: 1,
-        tx_id: vec![3; 32],
-        tx: vec![0],
-    };
-
-    state
-        .change_address(addendum.clone(), create_set(vec![address_tx_one.clone()]))
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state
-        .change_address(addendum.clone(), create_set(vec![address_tx_one.clone()]))
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_err());
-
-    state
-        .change_address(
-            addendum.clone(),
-            create_set(vec![address_tx_one.clone(), address_tx_one.clone()]),
-        )
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_err());
-
-    state
-        .change_address(
-            addendum.clone(),
-            create_set(vec![address_tx_one.clone(), address_tx_two.clone()]),
-        )
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state
-        .change_address(
-            addendum.clone(),
-            create_set(vec![address_tx_one.clone(), address_tx_two.clone()]),
-        )
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_err());
-
-    state
-        .change_address(
-            addendum.clone(),
-            create_set(vec![address_tx_three.clone(), address_tx_four.clone()]),
-        )
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-    assert!(event_rx.try_recv().is_ok());
-
-    let source2 = ServiceId::Syncer(Coin::Monero, Network::Testnet);
-    state
-        .watch_address(address_task_two.clone(), source2.clone())
-        .unwrap();
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source2.clone(), true)
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.change_height(1, vec![0]).await;
-    let height_task = WatchHeight {
-        id: TaskId(0),
-        lifetime: 3,
-    };
-    state.watch_height(height_task, source1.clone()).await;
-    assert_eq!(state.lifetimes.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 2);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.change_height(2, vec![0]).await;
-    state
-        .change_address(
-            addendum.clone(),
-            create_set(vec![address_tx_one.clone(), address_tx_two.clone()]),
-        )
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.addresses.len(), 0);
-    assert!(event_rx.try_recv().is_ok());
-}
+        state.watch_transaction(transaction_task_one.clone(), source1.clone());
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
+            .await;
+        assert!(event_rx.try_recv().is_ok());
+
+        state.watch_transaction(transaction_task_one.clone(), source1.clone());
+        state.watch_transaction(transaction_task_two.clone(), source1.clone());
+        state.watch_height(height_task, source1.clone()).await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 2);
+        assert!(event_rx.try_recv().is_err());
+
+        state
+            .change_transaction(vec![0], none!(), none!(), none!())
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 2);
+        assert!(event_rx.try_recv().is_ok());
+
+        state
+            .change_transaction(vec![0], none!(), Some(0), none!())
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state
+            .change_transaction(vec![0], none!(), Some(0), none!())
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 1);
+        assert!(event_rx.try_recv().is_err());
+
+        state
+            .change_transaction(vec![0], Some(vec![1]), Some(1), none!())
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state
+            .change_transaction(vec![0], Some(vec![1]), Some(1), none!())
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 1);
+        assert!(event_rx.try_recv().is_err());
+
+        state
+            .change_transaction(vec![0], none!(), none!(), none!())
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 2);
+        assert!(event_rx.try_recv().is_ok());
+
+        let source2 = ServiceId::Syncer(Coin::Monero, Network::Mainnet);
+        state.watch_transaction(transaction_task_two.clone(), source2.clone());
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source2.clone(), true)
+            .await;
+        assert_eq!(state.lifetimes.len(), 3);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 3);
+        assert_eq!(state.unseen_transactions.len(), 2);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.change_height(5, vec![0]).await;
+        assert_eq!(state.lifetimes.len(), 0);
+        assert_eq!(state.transactions.len(), 0);
+        assert_eq!(state.tasks_sources.len(), 0);
+        assert_eq!(state.unseen_transactions.len(), 0);
+        assert!(event_rx.try_recv().is_err());
+    }
 
-#[tokio::test]
-async fn syncer_state_sweep_addresses() {
-    use std::str::FromStr;
-    use tokio::sync::mpsc::Receiver as TokioReceiver;
-    let (event_tx, mut event_rx): (
-        TokioSender<SyncerdBridgeEvent>,
-        TokioReceiver<SyncerdBridgeEvent>,
-    ) = tokio::sync::mpsc::channel(120);
-    let mut state = SyncerState::new(event_tx.clone());
-    let sweep_task = SweepAddress {
-        id: TaskId(0),
-        lifetime: 11,
-        from_height: None,
-        addendum: SweepAddressAddendum::Monero(SweepXmrAddress {
-            view_key: monero::PrivateKey::from_str(
-                "77916d0cd56ed1920aef6ca56d8a41bac915b68e4c46a589e0956e27a7b77404",
+    #[tokio::test]
+    async fn syncer_state_addresses() {
+        use std::str::FromStr;
+        use tokio::sync::mpsc::Receiver as TokioReceiver;
+        let (event_tx, mut event_rx): (
+            TokioSender<SyncerdBridgeEvent>,
+            TokioReceiver<SyncerdBridgeEvent>,
+        ) = tokio::sync::mpsc::channel(120);
+        let mut state = SyncerState::new(event_tx.clone());
+        let address = bitcoin::Address::from_str("32BkaQeAVcd65Vn7pjEziohf5bCiryNQov").unwrap();
+        let addendum = AddressAddendum::Bitcoin(BtcAddressAddendum {
+            address: Some(address.clone()),
+            from_height: 0,
+            script_pubkey: address.script_pubkey(),
+        });
+        let address_task = WatchAddress {
+            id: TaskId(0),
+            lifetime: 1,
+            addendum: addendum.clone(),
+            include_tx: Boolean::False,
+        };
+        let address_task_two = WatchAddress {
+            id: TaskId(0),
+            lifetime: 1,
+            addendum: addendum.clone(),
+            include_tx: Boolean::False,
+        };
+        let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
+
+        state
+            .watch_address(address_task_two.clone(), source1.clone())
+            .unwrap();
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
+            .await;
+        assert_eq!(state.lifetimes.len(), 0);
+        assert_eq!(state.tasks_sources.len(), 0);
+        assert_eq!(state.addresses.len(), 0);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.watch_address(address_task, source1.clone()).unwrap();
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        let address_tx_one = AddressTx {
+            our_amount: 1,
+            tx_id: vec![0; 32],
+            tx: vec![0],
+        };
+        let address_tx_two = AddressTx {
+            our_amount: 1,
+            tx_id: vec![1; 32],
+            tx: vec![0],
+        };
+        let address_tx_three = AddressTx {
+            our_amount: 1,
+            tx_id: vec![2; 32],
+            tx: vec![0],
+        };
+        let address_tx_four = AddressTx {
+            our_amount: 1,
+            tx_id: vec![3; 32],
+            tx: vec![0],
+        };
+
+        state
+            .change_address(addendum.clone(), create_set(vec![address_tx_one.clone()]))
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state
+            .change_address(addendum.clone(), create_set(vec![address_tx_one.clone()]))
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_err());
+
+        state
+            .change_address(
+                addendum.clone(),
+                create_set(vec![address_tx_one.clone(), address_tx_one.clone()]),
             )
-            .unwrap(),
-            spend_key: monero::PrivateKey::from_str(
-                "77916d0cd56ed1920aef6ca56d8a41bac915b68e4c46a589e0956e27a7b77404",
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_err());
+
+        state
+            .change_address(
+                addendum.clone(),
+                create_set(vec![address_tx_one.clone(), address_tx_two.clone()]),
             )
-            .unwrap(),
-            address: monero::Address::from_str(
-                "51qzspbPiQ9Z9Wq3hR8HRhPmVcE3URCK8b8A9ypHHzyvhigWTefCapoG1MXVZQQi7B5t4DpJYrHZyaFjHSb5QqLe8YEaBpo"
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state
+            .change_address(
+                addendum.clone(),
+                create_set(vec![address_tx_one.clone(), address_tx_two.clone()]),
             )
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_err());
+
+        state
+            .change_address(
+                addendum.clone(),
+                create_set(vec![address_tx_three.clone(), address_tx_four.clone()]),
+            )
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+        assert!(event_rx.try_recv().is_ok());
+
+        let source2 = ServiceId::Syncer(Coin::Monero, Network::Testnet);
+        state
+            .watch_address(address_task_two.clone(), source2.clone())
+            .unwrap();
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source2.clone(), true)
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.change_height(1, vec![0]).await;
+        let height_task = WatchHeight {
+            id: TaskId(0),
+            lifetime: 3,
+        };
+        state.watch_height(height_task, source1.clone()).await;
+        assert_eq!(state.lifetimes.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 2);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.change_height(2, vec![0]).await;
+        state
+            .change_address(
+                addendum.clone(),
+                create_set(vec![address_tx_one.clone(), address_tx_two.clone()]),
+            )
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.addresses.len(), 0);
+        assert!(event_rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn syncer_state_sweep_addresses() {
+        use std::str::FromStr;
+        use tokio::sync::mpsc::Receiver as TokioReceiver;
+        let (event_tx, mut event_rx): (
+            TokioSender<SyncerdBridgeEvent>,
+            TokioReceiver<SyncerdBridgeEvent>,
+        ) = tokio::sync::mpsc::channel(120);
+        let mut state = SyncerState::new(event_tx.clone());
+        let sweep_task = SweepAddress {
+            id: TaskId(0),
+            lifetime: 11,
+            from_height: None,
+            addendum: SweepAddressAddendum::Monero(SweepXmrAddress {
+                view_key: monero::PrivateKey::from_str(
+                    "77916d0cd56ed1920aef6ca56d8a41bac915b68e4c46a589e0956e27a7b77404",
+                )
+                .unwrap(),
+                spend_key: monero::PrivateKey::from_str(
+                    "77916d0cd56ed1920aef6ca56d8a41bac915b68e4c46a589e0956e27a7b77404",
+                )
+                .unwrap(),
+                address: monero::Address::from_str(
+                    "51qzspbPiQ9Z9Wq3hR8HRhPmVcE3URCK8b8A9ypHHzyvhigWTefCapoG1MXVZQQi7B5t4DpJYrHZyaFjHSb5QqLe8YEaBpo"
+                )
+                .unwrap(),
+            }),
+        };
+        let source1 = ServiceId::Syncer(Coin::Monero, Network::Mainnet);
+
+        state.sweep_address(sweep_task.clone(), source1.clone());
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.sweep_addresses.len(), 1);
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
+            .await;
+        assert_eq!(state.lifetimes.len(), 0);
+        assert_eq!(state.tasks_sources.len(), 0);
+        assert_eq!(state.sweep_addresses.len(), 0);
+        assert_eq!(state.retired_wallets.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.retired_wallets.clear();
+        state.sweep_address(sweep_task, source1.clone());
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.sweep_addresses.len(), 1);
+        state.success_sweep(&InternalId(2), vec![vec![0]]).await;
+        assert_eq!(state.lifetimes.len(), 0);
+        assert_eq!(state.tasks_sources.len(), 0);
+        assert_eq!(state.sweep_addresses.len(), 0);
+        assert_eq!(state.retired_wallets.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn syncer_state_height() {
+        use tokio::sync::mpsc::Receiver as TokioReceiver;
+        let (event_tx, mut event_rx): (
+            TokioSender<SyncerdBridgeEvent>,
+            TokioReceiver<SyncerdBridgeEvent>,
+        ) = tokio::sync::mpsc::channel(120);
+        let mut state = SyncerState::new(event_tx.clone());
+        let height_task = WatchHeight {
+            id: TaskId(0),
+            lifetime: 0,
+        };
+        let another_height_task = WatchHeight {
+            id: TaskId(0),
+            lifetime: 3,
+        };
+        let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
+
+        state
+            .watch_height(height_task.clone(), source1.clone())
+            .await;
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
+            .await;
+        assert_eq!(state.lifetimes.len(), 0);
+        assert_eq!(state.tasks_sources.len(), 0);
+        assert_eq!(state.watch_height.len(), 0);
+        assert!(event_rx.try_recv().is_ok());
+
+        state
+            .watch_height(height_task.clone(), source1.clone())
+            .await;
+        state
+            .watch_height(another_height_task.clone(), source1.clone())
+            .await;
+        assert_eq!(state.lifetimes.len(), 2);
+        assert_eq!(state.tasks_sources.len(), 2);
+        assert_eq!(state.watch_height.len(), 2);
+        assert!(event_rx.try_recv().is_err());
+
+        state.change_height(1, vec![0]).await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.watch_height.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.change_height(3, vec![0]).await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.watch_height.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+
+        state.change_height(3, vec![0]).await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.watch_height.len(), 1);
+        assert!(event_rx.try_recv().is_err());
+
+        let source2 = ServiceId::Syncer(Coin::Monero, Network::Mainnet);
+        state
+            .watch_height(another_height_task.clone(), source2.clone())
+            .await;
+        state
+            .abort(TaskTarget::TaskId(TaskId(0)), source2.clone(), true)
+            .await;
+        assert_eq!(state.lifetimes.len(), 1);
+        assert_eq!(state.tasks_sources.len(), 1);
+        assert_eq!(state.watch_height.len(), 1);
+        assert!(event_rx.try_recv().is_ok());
+        assert!(event_rx.try_recv().is_ok());
+
+        state.change_height(4, vec![0]).await;
+        assert_eq!(state.lifetimes.len(), 0);
+        assert_eq!(state.tasks_sources.len(), 0);
+        assert_eq!(state.watch_height.len(), 0);
+        assert!(event_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn syncer_state_reorg() {
+        use tokio::sync::mpsc::Receiver as TokioReceiver;
+        let (event_tx, mut event_rx): (
+            TokioSender<SyncerdBridgeEvent>,
+            TokioReceiver<SyncerdBridgeEvent>,
+        ) = tokio::sync::mpsc::channel(120);
+        let mut state = SyncerState::new(event_tx.clone());
+        let height_task = WatchHeight {
+            id: TaskId(0),
+            lifetime: 10,
+        };
+        let transaction_task = WatchTransaction {
+            id: TaskId(1),
+            lifetime: 10,
+            hash: vec![0],
+            confirmation_bound: 4,
+        };
+        let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
+
+        state.watch_height(height_task, source1.clone()).await;
+        state.watch_transaction(transaction_task, source1.clone());
+        state.change_height(1, vec![1]).await;
+        state.change_height(2, vec![2]).await;
+        state.change_height(3, vec![3]).await;
+        state
+            .change_transaction(vec![0], Some(vec![2]), Some(2), none!())
+            .await;
+        while event_rx.try_recv().is_ok() {}
+
+        // the synclet finds the blocks above height 1 replaced
+        let best_chain = |height: u64| {
+            std::future::ready(Ok(if height > 1 {
+                vec![height as u8 + 10]
+            } else {
+                vec![1]
+            }))
+        };
+        let fork = find_fork(&state.recent_blocks(), 4, &[14], best_chain)
+            .await
+            .unwrap();
+        assert_eq!(fork, Some(1));
+        assert_eq!(
+            find_fork(&state.recent_blocks(), 4, &[4], |height| {
+                std::future::ready(Ok(vec![height as u8]))
+            })
+            .await
             .unwrap(),
-        }),
-    };
-    let source1 = ServiceId::Syncer(Coin::Monero, Network::Mainnet);
-
-    state.sweep_address(sweep_task.clone(), source1.clone());
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.sweep_addresses.len(), 1);
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
-        .await;
-    assert_eq!(state.lifetimes.len(), 0);
-    assert_eq!(state.tasks_sources.len(), 0);
-    assert_eq!(state.sweep_addresses.len(), 0);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.sweep_address(sweep_task, source1.clone());
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.sweep_addresses.len(), 1);
-    state.success_sweep(&InternalId(2), vec![vec![0]]).await;
-    assert_eq!(state.lifetimes.len(), 0);
-    assert_eq!(state.tasks_sources.len(), 0);
-    assert_eq!(state.sweep_addresses.len(), 0);
-    assert!(event_rx.try_recv().is_ok());
-}
+            None
+        );
 
-#[tokio::test]
-async fn syncer_state_height() {
-    use tokio::sync::mpsc::Receiver as TokioReceiver;
-    let (event_tx, mut event_rx): (
-        TokioSender<SyncerdBridgeEvent>,
-        TokioReceiver<SyncerdBridgeEvent>,
-    ) = tokio::sync::mpsc::channel(120);
-    let mut state = SyncerState::new(event_tx.clone());
-    let height_task = WatchHeight {
-        id: TaskId(0),
-        lifetime: 0,
-    };
-    let another_height_task = WatchHeight {
-        id: TaskId(0),
-        lifetime: 3,
-    };
-    let source1 = ServiceId::Syncer(Coin::Bitcoin, Network::Mainnet);
-
-    state
-        .watch_height(height_task.clone(), source1.clone())
-        .await;
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source1.clone(), true)
-        .await;
-    assert_eq!(state.lifetimes.len(), 0);
-    assert_eq!(state.tasks_sources.len(), 0);
-    assert_eq!(state.watch_height.len(), 0);
-    assert!(event_rx.try_recv().is_ok());
-
-    state
-        .watch_height(height_task.clone(), source1.clone())
-        .await;
-    state
-        .watch_height(another_height_task.clone(), source1.clone())
-        .await;
-    assert_eq!(state.lifetimes.len(), 2);
-    assert_eq!(state.tasks_sources.len(), 2);
-    assert_eq!(state.watch_height.len(), 2);
-    assert!(event_rx.try_recv().is_err());
-
-    state.change_height(1, vec![0]).await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.watch_height.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.change_height(3, vec![0]).await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.watch_height.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-
-    state.change_height(3, vec![0]).await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.watch_height.len(), 1);
-    assert!(event_rx.try_recv().is_err());
-
-    let source2 = ServiceId::Syncer(Coin::Monero, Network::Mainnet);
-    state
-        .watch_height(another_height_task.clone(), source2.clone())
-        .await;
-    state
-        .abort(TaskTarget::TaskId(TaskId(0)), source2.clone(), true)
-        .await;
-    assert_eq!(state.lifetimes.len(), 1);
-    assert_eq!(state.tasks_sources.len(), 1);
-    assert_eq!(state.watch_height.len(), 1);
-    assert!(event_rx.try_recv().is_ok());
-    assert!(event_rx.try_recv().is_ok());
-
-    state.change_height(4, vec![0]).await;
-    assert_eq!(state.lifetimes.len(), 0);
-    assert_eq!(state.tasks_sources.len(), 0);
-    assert_eq!(state.watch_height.len(), 0);
-    assert!(event_rx.try_recv().is_err());
+        state.reorg(1).await;
+        assert_eq!(state.block_height(), 1);
+        assert_eq!(state.recent_blocks(), vec![(1, vec![1])]);
+        match event_rx.try_recv().unwrap().event {
+            Event::Reorg(Reorg {
+                fork_height, depth, ..
+            }) => {
+                assert_eq!(fork_height, 1);
+                assert_eq!(depth, 2);
+            }
+            event => panic!("expected reorg event, found {}", event),
+        }
+        state.change_height(4, vec![14]).await;
+        assert!(matches!(
+            event_rx.try_recv().unwrap().event,
+            Event::HeightChanged(HeightChanged { height: 4, .. })
+        ));
+
+        // the transaction is back in the mempool of the new best chain
+        state
+            .change_transaction(vec![0], None, Some(0), none!())
+            .await;
+        assert!(matches!(
+            event_rx.try_recv().unwrap().event,
+            Event::TransactionConfirmations(TransactionConfirmations {
+                confirmations: Some(0),
+                ..
+            })
+        ));
+
+        // a different block at the tip height without notice from the synclet is a reorg too
+        state.change_height(4, vec![24]).await;
+        assert!(matches!(
+            event_rx.try_recv().unwrap().event,
+            Event::Reorg(Reorg {
+                fork_height: 3,
+                depth: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            event_rx.try_recv().unwrap().event,
+            Event::HeightChanged(HeightChanged { height: 4, .. })
+        ));
+        assert!(event_rx.try_recv().is_err());
+    }
 }
@@ -204,6 +204,14 @@ pub struct GetTx {
     pub hash: Vec<u8>,
 }
 
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
+#[display(Debug)]
+pub struct EstimateFee {
+    pub id: TaskId,
+    /// The number of blocks within which the transaction should confirm.
+    pub blocks_until_confirmation: u16,
+}
+
 /// Tasks created by the daemon and handle by syncers to process a blockchain
 /// and generate [`Event`] back to the syncer.
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
@@ -216,6 +224,7 @@ pub enum Task {
     BroadcastTransaction(BroadcastTransaction),
     SweepAddress(SweepAddress),
     GetTx(GetTx),
+    EstimateFee(EstimateFee),
 }
 
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
@@ -233,13 +242,15 @@ pub struct HeightChanged {
     pub height: u64,
 }
 
+/// A transaction paying to a watched address, reported once when first seen in the mempool or in
+/// a block. It carries no block: the confirmations of a transaction, and the block including it,
+/// are only reported by a `WatchTransaction` task.
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
 #[display(Debug)]
 pub struct AddressTransaction {
     pub id: TaskId,
     pub hash: Vec<u8>,
     pub amount: u64,
-    pub block: Vec<u8>,
     // for bitcoin with bitcoin::consensus encoding
     pub tx: Vec<u8>,
 }
@@ -277,6 +288,25 @@ pub struct TransactionRetrieved {
     pub tx: Option<bitcoin::Transaction>,
 }
 
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
+#[display(Debug)]
+pub struct Reorg {
+    pub id: TaskId,
+    /// Height of the last block shared by the previous and the new best chain
+    pub fork_height: u64,
+    /// Number of blocks disconnected from the previous best chain
+    pub depth: u64,
+}
+
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
+#[display(Debug)]
+pub struct FeeEstimated {
+    pub id: TaskId,
+    /// Fee rate in satoshi per virtual byte, none if the backend has not enough data to estimate
+    /// it.
+    pub sat_per_vbyte: Option<u64>,
+}
+
 /// Events returned by syncers to the daemon to update the blockchain states.
 /// Events are identified with a unique 32-bits integer that match the [`Task`]
 /// id.
@@ -293,4 +323,8 @@ pub enum Event {
     /// Carries the status for the task abortion.
     TaskAborted(TaskAborted),
     TransactionRetrieved(TransactionRetrieved),
+    /// Notify the daemon blocks were disconnected from the best chain; the confirmations of the
+    /// watched transactions are re-emitted against the new best chain.
+    Reorg(Reorg),
+    FeeEstimated(FeeEstimated),
 }
@@ -0,0 +1,108 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+//! Passphrase based encryption of the node key file.
+//!
+//! An encrypted key file is laid out as `MAGIC | VERSION | salt | nonce | ciphertext`, where the
+//! encryption key is derived from the passphrase with scrypt and the secrets are sealed with
+//! ChaCha20-Poly1305. Files without the magic prefix are legacy plaintext key files.
+
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::Error;
+
+const MAGIC: &[u8; 4] = b"FCKF";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+// scrypt cost parameters: N = 2^15, r = 8, p = 1
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Whether the key file content is encrypted, as opposed to a legacy plaintext file
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && &data[..MAGIC.len()] == MAGIC
+}
+
+/// Seal the strict encoded node secrets with a key derived from the passphrase
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let mut rng = thread_rng();
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| Error::Wallet(s!("Unable to encrypt key file")))?;
+
+    let mut data = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// Open an encrypted key file, failing if the passphrase is wrong or the file was tampered with
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    if !is_encrypted(data) {
+        return Err(Error::Wallet(s!("Key file is not encrypted")));
+    }
+    let version = data[MAGIC.len()];
+    if version != VERSION {
+        return Err(Error::Wallet(format!(
+            "Unsupported key file encryption version {}",
+            version
+        )));
+    }
+    let salt = &data[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = &data[HEADER_LEN - NONCE_LEN..HEADER_LEN];
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), &data[HEADER_LEN..])
+        .map_err(|_| Error::Wallet(s!("Invalid passphrase or corrupted key file")))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, Error> {
+    let params = scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)
+        .map_err(|err| Error::Wallet(err.to_string()))?;
+    let mut key = Key::default();
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|err| Error::Wallet(err.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_file_encryption() {
+        let plaintext = b"node secrets".to_vec();
+        let data = encrypt(&plaintext, "passphrase").unwrap();
+        assert!(is_encrypted(&data));
+        assert!(!is_encrypted(&plaintext));
+        assert_eq!(decrypt(&data, "passphrase").unwrap(), plaintext);
+        assert!(decrypt(&data, "wrong passphrase").is_err());
+    }
+}
@@ -12,10 +12,16 @@
 // along with this software.
 // If not, see <https://opensource.org/licenses/MIT>.
 
+#[cfg(feature = "shell")]
+mod key_file;
+#[cfg(feature = "shell")]
+mod node_secrets;
 #[cfg(feature = "shell")]
 mod opts;
 mod runtime;
 
 #[cfg(feature = "shell")]
-pub use opts::{KeyOpts, NodeSecrets, Opts};
+pub use node_secrets::NodeSecrets;
+#[cfg(feature = "shell")]
+pub use opts::{KeyOpts, Opts, PassphraseOpts};
 pub use runtime::run;
@@ -0,0 +1,231 @@
+// LNP Node: node running lightning network protocol and generalized lightning
+// channels.
+// Written in 2020 by
+//     Dr. Maxim Orlovsky <orlovsky@pandoracore.com>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the MIT License
+// along with this software.
+// If not, see <https://opensource.org/licenses/MIT>.
+
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+use super::key_file;
+use crate::Error;
+use bitcoin::secp256k1::{
+    rand::{rngs::ThreadRng, thread_rng},
+    PublicKey, Secp256k1, SecretKey,
+};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+#[derive(StrictEncode, StrictDecode, Clone, PartialEq, Eq, Debug)]
+pub struct Counter(pub u32);
+impl Counter {
+    fn increment(&mut self) -> u32 {
+        self.0 += 1;
+        self.0
+    }
+}
+
+/// Hold secret keys and seeds
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NodeSecrets {
+    /// local key file
+    pub key_file: String,
+    /// local node private information
+    pub peerd_secret_key: SecretKey,
+    /// seed used for deriving addresses
+    pub wallet_seed: [u8; 32],
+    /// wallet last derivation index
+    pub wallet_counter: Counter,
+    /// passphrase encrypting the key file, never written to the key file itself
+    passphrase: Option<String>,
+}
+
+impl StrictEncode for NodeSecrets {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        let mut len = self.key_file.strict_encode(&mut e)?;
+        len += self.peerd_secret_key.strict_encode(&mut e)?;
+        len += self.wallet_seed.strict_encode(&mut e)?;
+        len += self.wallet_counter.strict_encode(&mut e)?;
+        Ok(len)
+    }
+}
+
+impl StrictDecode for NodeSecrets {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        Ok(Self {
+            key_file: StrictDecode::strict_decode(&mut d)?,
+            peerd_secret_key: StrictDecode::strict_decode(&mut d)?,
+            wallet_seed: StrictDecode::strict_decode(&mut d)?,
+            wallet_counter: StrictDecode::strict_decode(&mut d)?,
+            passphrase: None,
+        })
+    }
+}
+
+impl NodeSecrets {
+    pub fn new(key_file: String, passphrase: Option<String>) -> Self {
+        if PathBuf::from(key_file.clone()).exists() {
+            let data = fs::read(&key_file).unwrap_or_else(|_| {
+                panic!(
+                    "Unable to open key file {}; please check that the user \
+                    running the deamon has necessary permissions",
+                    key_file
+                )
+            });
+            let mut node_secrets = if key_file::is_encrypted(&data) {
+                let passphrase = passphrase.clone().unwrap_or_else(|| {
+                    panic!(
+                        "Key file {} is encrypted; please provide its passphrase",
+                        key_file
+                    )
+                });
+                let plaintext = key_file::decrypt(&data, &passphrase)
+                    .unwrap_or_else(|err| panic!("Unable to decrypt key file: {}", err));
+                NodeSecrets::strict_decode(&plaintext[..])
+                    .expect("Unable to read node code file format")
+            } else {
+                NodeSecrets::strict_decode(&data[..]).expect("Unable to read node code file format")
+            };
+            node_secrets.key_file = key_file.clone();
+            node_secrets.passphrase = passphrase;
+            if !key_file::is_encrypted(&data) {
+                if node_secrets.passphrase.is_some() {
+                    info!("Encrypting legacy plaintext key file {}", key_file);
+                    node_secrets
+                        .save()
+                        .expect("Unable to save encrypted node secrets");
+                } else {
+                    warn!(
+                        "Key file {} is not encrypted; provide a passphrase to encrypt it",
+                        key_file
+                    );
+                }
+            }
+            node_secrets
+        } else {
+            let mut rng = thread_rng();
+            let peer_private_key = SecretKey::new(&mut rng);
+            let wallet_seed = Self::create_seed(&mut rng);
+            let node_secrets = Self {
+                key_file,
+                peerd_secret_key: peer_private_key,
+                wallet_seed,
+                wallet_counter: Counter(0),
+                passphrase,
+            };
+            node_secrets
+                .save()
+                .expect("Unable to save generated node secrets");
+            node_secrets
+        }
+    }
+
+    /// Write the secrets to the key file, encrypted if a passphrase is set
+    fn save(&self) -> Result<(), Error> {
+        let plaintext = strict_encoding::strict_serialize(self)?;
+        let data = match &self.passphrase {
+            Some(passphrase) => key_file::encrypt(&plaintext, passphrase)?,
+            None => plaintext,
+        };
+        // write to a temporary file first so a crash never leaves a truncated key file
+        let tmp = format!("{}.tmp", self.key_file);
+        fs::write(&tmp, data).map_err(|err| {
+            Error::Wallet(format!(
+                "Unable to create key file '{}'; please check that path exists: {}",
+                tmp, err
+            ))
+        })?;
+        fs::rename(&tmp, &self.key_file)?;
+        Ok(())
+    }
+
+    /// Re-encrypt the key file under a new passphrase, an empty one removing the encryption.
+    /// The current passphrase must match, and is empty for a plaintext key file.
+    pub fn change_passphrase(&mut self, current: &str, new: String) -> Result<(), Error> {
+        self.check_passphrase(current)?;
+        self.passphrase = if new.is_empty() { None } else { Some(new) };
+        self.save()
+    }
+
+    fn check_passphrase(&self, passphrase: &str) -> Result<(), Error> {
+        if self.passphrase.as_deref().unwrap_or_default() != passphrase {
+            return Err(Error::Wallet(s!("Invalid passphrase")));
+        }
+        Ok(())
+    }
+
+    /// BIP39 mnemonic encoding the wallet seed, guarded by the key file passphrase
+    pub fn mnemonic(&self, passphrase: &str) -> Result<String, Error> {
+        self.check_passphrase(passphrase)?;
+        let mnemonic = bip39::Mnemonic::from_entropy(&self.wallet_seed)
+            .expect("32 bytes is a valid BIP39 entropy length");
+        Ok(mnemonic.to_string())
+    }
+
+    /// Replace the wallet seed with the one encoded in a BIP39 mnemonic. The wallet counter
+    /// is raised to the given one, so that new swaps do not reuse keys derived under the
+    /// original seed; it is never lowered.
+    pub fn restore_mnemonic(
+        &mut self,
+        passphrase: &str,
+        mnemonic: &str,
+        wallet_counter: u32,
+    ) -> Result<(), Error> {
+        self.check_passphrase(passphrase)?;
+        let entropy = bip39::Mnemonic::parse(mnemonic)
+            .map_err(|err| Error::Wallet(format!("Invalid mnemonic: {}", err)))?
+            .to_entropy();
+        self.wallet_seed = entropy.as_slice().try_into().map_err(|_| {
+            Error::Wallet(format!(
+                "Invalid mnemonic: expected 24 words encoding a 32 bytes seed, got {} bytes",
+                entropy.len()
+            ))
+        })?;
+        self.wallet_counter = Counter(self.wallet_counter.0.max(wallet_counter));
+        self.save()
+    }
+
+    pub fn node_id(&self) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.peerd_secret_key)
+    }
+
+    fn create_seed(rng: &mut ThreadRng) -> [u8; 32] {
+        let mut seed_buf = [0u8; 32];
+        let key = SecretKey::new(rng);
+        let mut key_iter = key[..].iter();
+        let mut reader = std::io::Cursor::new(&mut key_iter);
+        reader
+            .read_exact(&mut seed_buf)
+            .expect("wallet_key has 32 bytes");
+        {
+            let expected_key = SecretKey::from_slice(&seed_buf).expect("wallet_seed has 32 bytes");
+            assert_eq!(
+                expected_key, key,
+                "Cannot go back and forward from bytes to secret key"
+            );
+        }
+        seed_buf
+    }
+
+    pub fn increment_wallet_counter(&mut self) -> u32 {
+        self.wallet_counter.increment();
+        self.save()
+            .expect("Unable to save incremented wallet counter");
+        self.wallet_counter.0
+    }
+
+    pub fn wallet_seed(&self) -> [u8; 32] {
+        self.wallet_seed
+    }
+}
@@ -14,15 +14,12 @@
 
 use crate::opts::FARCASTER_KEY_FILE;
 use clap::{AppSettings, Clap, ValueHint};
-use std::path::PathBuf;
-use std::{fs, io::Read};
+use std::{env, fs, io};
 
 use crate::opts::TokenString;
-use bitcoin::secp256k1::{
-    rand::{rngs::ThreadRng, thread_rng},
-    PublicKey, Secp256k1, SecretKey,
-};
-use strict_encoding::{StrictDecode, StrictEncode};
+
+/// Environment variable holding the passphrase used to encrypt the node key file
+pub const FARCASTER_KEY_PASSPHRASE: &str = "FARCASTER_KEY_PASSPHRASE";
 
 /// Walletd daemon; part of Farcaster Node
 #[derive(Clap, Clone, PartialEq, Eq, Debug)]
@@ -60,8 +57,8 @@ impl Opts {
 pub struct KeyOpts {
     /// Node key file
     ///
-    /// Location for the file containing node private Secp256k1 key
-    /// (unencrypted)
+    /// Location for the file containing node private Secp256k1 key and wallet
+    /// seed, encrypted when a passphrase is provided
     #[clap(
         short,
         long,
@@ -70,102 +67,64 @@ pub struct KeyOpts {
         value_hint = ValueHint::FilePath
     )]
     pub key_file: String,
-}
 
-#[derive(StrictEncode, StrictDecode, Clone, PartialEq, Eq, Debug)]
-pub struct Counter(pub u32);
-impl Counter {
-    fn increment(&mut self) -> u32 {
-        self.0 += 1;
-        self.0
-    }
+    /// Key file passphrase configuration
+    #[clap(flatten)]
+    pub passphrase_opts: PassphraseOpts,
 }
 
-/// Hold secret keys and seeds
-#[derive(StrictEncode, StrictDecode, Clone, PartialEq, Eq, Debug)]
-pub struct NodeSecrets {
-    /// local key file
-    pub key_file: String,
-    /// local node private information
-    pub peerd_secret_key: SecretKey,
-    /// seed used for deriving addresses
-    pub wallet_seed: [u8; 32],
-    /// wallet last derivation index
-    pub wallet_counter: Counter,
+/// Sources for the passphrase encrypting the node key file, in addition to the
+/// `FARCASTER_KEY_PASSPHRASE` environment variable
+#[derive(Clap, Clone, PartialEq, Eq, Debug)]
+pub struct PassphraseOpts {
+    /// File containing the passphrase used to encrypt the node key file
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        conflicts_with = "key-passphrase-stdin"
+    )]
+    pub key_passphrase_file: Option<String>,
+
+    /// Read the passphrase used to encrypt the node key file from stdin
+    #[clap(long)]
+    pub key_passphrase_stdin: bool,
 }
 
-impl NodeSecrets {
-    pub fn new(key_file: String) -> Self {
-        if PathBuf::from(key_file.clone()).exists() {
-            NodeSecrets::strict_decode(fs::File::open(key_file.clone()).unwrap_or_else(|_| {
-                panic!(
-                    "Unable to open key file {}; please check that the user \
-                    running the deamon has necessary permissions",
-                    key_file
-                )
-            }))
-            .expect("Unable to read node code file format")
+impl PassphraseOpts {
+    /// Retrieve the passphrase from the configured source, falling back to the
+    /// `FARCASTER_KEY_PASSPHRASE` environment variable; a trailing newline is not part of the
+    /// passphrase. The passphrase is never accepted as a command-line value, which would leak
+    /// it through the process arguments.
+    pub fn passphrase(&self) -> io::Result<Option<String>> {
+        let passphrase = if let Some(path) = &self.key_passphrase_file {
+            fs::read_to_string(path)?
+        } else if self.key_passphrase_stdin {
+            let mut passphrase = String::new();
+            io::stdin().read_line(&mut passphrase)?;
+            passphrase
+        } else if let Ok(passphrase) = env::var(FARCASTER_KEY_PASSPHRASE) {
+            passphrase
         } else {
-            let mut rng = thread_rng();
-            let peer_private_key = SecretKey::new(&mut rng);
-            let wallet_seed = Self::create_seed(&mut rng);
-            let node_secrets = Self {
-                key_file: key_file.clone(),
-                peerd_secret_key: peer_private_key,
-                wallet_seed,
-                wallet_counter: Counter(0),
-            };
-
-            let key_file_handle = fs::File::create(&key_file).unwrap_or_else(|_| {
-                panic!(
-                    "Unable to create key file '{}'; please check that path exists",
-                    key_file
-                )
-            });
-            node_secrets
-                .strict_encode(key_file_handle)
-                .expect("Unable to save generated node secrets");
-            node_secrets
-        }
+            return Ok(None);
+        };
+        Ok(Some(
+            passphrase.trim_end_matches(&['\r', '\n'][..]).to_string(),
+        ))
     }
 
-    pub fn node_id(&self) -> PublicKey {
-        PublicKey::from_secret_key(&Secp256k1::new(), &self.peerd_secret_key)
-    }
-
-    fn create_seed(rng: &mut ThreadRng) -> [u8; 32] {
-        let mut seed_buf = [0u8; 32];
-        let key = SecretKey::new(rng);
-        let mut key_iter = key[..].iter();
-        let mut reader = std::io::Cursor::new(&mut key_iter);
-        reader
-            .read_exact(&mut seed_buf)
-            .expect("wallet_key has 32 bytes");
-        {
-            let expected_key = SecretKey::from_slice(&seed_buf).expect("wallet_seed has 32 bytes");
-            assert_eq!(
-                expected_key, key,
-                "Cannot go back and forward from bytes to secret key"
-            );
+    /// Command-line arguments forwarding the passphrase source to a launched walletd. The
+    /// passphrase itself is never forwarded on the command line, the environment variable is
+    /// inherited instead.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(path) = &self.key_passphrase_file {
+            args.push("--key-passphrase-file".to_string());
+            args.push(path.clone());
         }
-        seed_buf
-    }
-
-    pub fn increment_wallet_counter(&mut self) -> u32 {
-        self.wallet_counter.increment();
-        let key_file_handle = fs::File::create(&self.key_file).unwrap_or_else(|_| {
-            panic!(
-                "Unable to create key file '{}'; please check that path exists",
-                self.key_file
-            )
-        });
-        self.strict_encode(key_file_handle)
-            .expect("Unable to save incremented wallet counter");
-        self.wallet_counter.0
-    }
-
-    pub fn wallet_seed(&self) -> [u8; 32] {
-        self.wallet_seed
+        if self.key_passphrase_stdin {
+            args.push("--key-passphrase-stdin".to_string());
+        }
+        args
     }
 }
 
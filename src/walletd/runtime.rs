@@ -3,32 +3,40 @@ use std::{
     collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
     io::{self, Write},
+    path::PathBuf,
     ptr::swap_nonoverlapping,
     str::FromStr,
 };
 
 use crate::swapd::get_swap_id;
+use crate::swapd::storage::{self, Driver};
 use crate::walletd::NodeSecrets;
 use crate::LogStyle;
 use crate::Senders;
 use crate::{
     rpc::{
         request::{
-            self, BitcoinAddress, Commit, Keys, MoneroAddress, Msg, Params, Reveal, Token, Tx,
+            self, BitcoinAddress, Commit, IntoSuccessOrFailure, Keys, MoneroAddress, Msg, Params,
+            RecoverableOutput, RecoveryInfo, Reveal, Token, Tx,
         },
         Request, ServiceBus,
     },
-    syncerd::SweepXmrAddress,
+    syncerd::{
+        opts::Coin, AddressAddendum, AddressTransaction, Boolean, BroadcastTransaction,
+        BtcAddressAddendum, Event, HeightChanged, SweepXmrAddress, Task, TaskId,
+        TransactionBroadcasted, WatchAddress, WatchHeight,
+    },
 };
 use crate::{CtlServer, Error, Service, ServiceConfig, ServiceId};
 use bitcoin::{
     hashes::hex::FromHex,
-    secp256k1::{self, Signature},
+    secp256k1::{self, Secp256k1, Signature},
     util::{
+        bip143::SigHashCache,
         bip32::{DerivationPath, ExtendedPrivKey},
         psbt::serialize::Deserialize,
     },
-    Address, PrivateKey, PublicKey,
+    Address, OutPoint, PrivateKey, PublicKey, Script, SigHashType, TxIn, TxOut,
 };
 use colored::Colorize;
 use farcaster_core::{
@@ -37,7 +45,7 @@ use farcaster_core::{
         segwitv0::{LockTx, SegwitV0},
         Bitcoin, BitcoinSegwitV0,
     },
-    blockchain::FeePriority,
+    blockchain::{FeePriority, FeeStrategy, Network},
     bundle::{
         AliceParameters, BobParameters, CoreArbitratingTransactions, FullySignedBuy,
         FullySignedPunish, FullySignedRefund, FundingTransaction, Proof, SignedAdaptorBuy,
@@ -54,29 +62,36 @@ use farcaster_core::{
     role::{Alice, Bob, SwapRole, TradeRole},
     swap::btcxmr::{BtcXmr, KeyManager},
     swap::SwapId,
-    syncer::{AddressTransaction, Boolean, Event},
     transaction::{Broadcastable, Fundable, Transaction, TxLabel, Witnessable},
 };
 use internet2::{LocalNode, ToNodeAddr, TypedEnum, LIGHTNING_P2P_DEFAULT_PORT};
 // use lnp::{ChannelId as SwapId, TempChannelId as TempSwapId};
 use microservices::esb::{self, Handler};
 use request::{LaunchSwap, NodeId};
+use strict_encoding::{StrictDecode, StrictEncode};
 
 pub fn run(
     config: ServiceConfig,
     wallet_token: Token,
     node_secrets: NodeSecrets,
     node_id: bitcoin::secp256k1::PublicKey,
+    data_dir: PathBuf,
 ) -> Result<(), Error> {
     let runtime = Runtime {
         identity: ServiceId::Wallet,
         wallet_token,
         node_secrets,
         node_id,
+        checkpoint_dir: data_dir
+            .join(storage::CHECKPOINT_DIR)
+            .join(storage::WALLET_CHECKPOINT_DIR),
         wallets: none!(),
+        checkpointed: none!(),
         swaps: none!(),
         btc_addrs: none!(),
         xmr_addrs: none!(),
+        wallet_indexes: none!(),
+        recovery: None,
     };
 
     Service::run(config, runtime, false)
@@ -87,10 +102,17 @@ pub struct Runtime {
     wallet_token: Token,
     node_secrets: NodeSecrets,
     node_id: bitcoin::secp256k1::PublicKey,
+    /// Directory of the per-swap wallet checkpoints
+    checkpoint_dir: PathBuf,
     wallets: HashMap<SwapId, Wallet>,
+    /// Last checkpoint written for each swap wallet
+    checkpointed: HashMap<SwapId, Vec<u8>>,
     swaps: HashMap<SwapId, Option<Request>>,
     btc_addrs: HashMap<SwapId, bitcoin::Address>,
     xmr_addrs: HashMap<SwapId, monero::Address>,
+    /// Index of the swap wallet derived for each running swap
+    wallet_indexes: HashMap<SwapId, u32>,
+    recovery: Option<Recovery>,
 }
 
 impl Runtime {
@@ -99,6 +121,269 @@ impl Runtime {
         self.btc_addrs.remove(swapid);
         self.xmr_addrs.remove(swapid);
         self.swaps.remove(swapid);
+        self.wallet_indexes.remove(swapid);
+        self.checkpointed.remove(swapid);
+        if let Err(err) = self
+            .wallet_storage(*swapid)
+            .and_then(|mut driver| driver.remove())
+        {
+            warn!(
+                "Failed to remove wallet checkpoint of swap {}: {}",
+                swapid.bright_blue_italic(),
+                err
+            );
+        }
+    }
+
+    fn wallet_storage(&self, swap_id: SwapId) -> Result<storage::DiskDriver, Error> {
+        storage::DiskDriver::init(
+            swap_id,
+            Box::new(storage::DiskConfig {
+                path: self.checkpoint_dir.clone(),
+            }),
+        )
+    }
+
+    /// Write the checkpoint of the swap wallets changed since their last checkpoint
+    fn checkpoint_wallets(&mut self) {
+        let mut changed = vec![];
+        for (swap_id, wallet) in self.wallets.iter() {
+            let wallet_index = match self.wallet_indexes.get(swap_id) {
+                Some(wallet_index) => *wallet_index,
+                None => continue,
+            };
+            let checkpoint = CheckpointWallet {
+                wallet_index,
+                monero_address: self.xmr_addrs.get(swap_id).map(|addr| addr.to_string()),
+                state: wallet.into(),
+            };
+            match strict_encoding::strict_serialize(&checkpoint) {
+                Ok(bytes) if self.checkpointed.get(swap_id) != Some(&bytes) => {
+                    changed.push((*swap_id, bytes))
+                }
+                Ok(_) => {}
+                Err(err) => error!(
+                    "Failed to encode the wallet checkpoint of swap {}: {}",
+                    swap_id, err
+                ),
+            }
+        }
+        for (swap_id, bytes) in changed {
+            match self
+                .wallet_storage(swap_id)
+                .and_then(|mut driver| driver.store(&bytes))
+            {
+                Ok(()) => {
+                    self.checkpointed.insert(swap_id, bytes);
+                }
+                Err(err) => error!(
+                    "Failed to checkpoint the wallet of swap {}: {}",
+                    swap_id.bright_blue_italic(),
+                    err
+                ),
+            }
+        }
+    }
+
+    /// Reload the wallet of a swap restored from its checkpoint by swapd
+    fn restore_wallet(&mut self, swap_id: SwapId) -> Result<(), Error> {
+        if self.wallets.contains_key(&swap_id) {
+            return Ok(());
+        }
+        let bytes = match self.wallet_storage(swap_id)?.load()? {
+            Some(bytes) => bytes,
+            None => {
+                return Err(Error::Farcaster(format!(
+                    "no wallet checkpoint found for swap {}",
+                    swap_id
+                )))
+            }
+        };
+        let CheckpointWallet {
+            wallet_index,
+            monero_address,
+            state,
+        } = strict_encoding::strict_deserialize(&bytes)?;
+        // the key manager is derived again from the wallet seed, as when the swap started
+        let key_manager = KeyManager::new(self.node_secrets.wallet_seed, wallet_index)?;
+        let wallet = state.into_wallet(key_manager)?;
+        if let Some(monero_address) = monero_address {
+            self.xmr_addrs
+                .insert(swap_id, monero::Address::from_str(&monero_address)?);
+        }
+        self.wallet_indexes.insert(swap_id, wallet_index);
+        self.swaps.insert(swap_id, None);
+        self.wallets.insert(swap_id, wallet);
+        self.checkpointed.insert(swap_id, bytes);
+        info!(
+            "Restored wallet of swap {} from its checkpoint",
+            swap_id.bright_blue_italic()
+        );
+        Ok(())
+    }
+}
+
+/// Below this value a sweep output would not be relayed by the bitcoin network
+const SWEEP_DUST_LIMIT: u64 = 546;
+
+/// Number of blocks the funding addresses stay watched for late funding after the scan started
+const RECOVERY_LIFETIME: u64 = 1008;
+
+/// Scan of the swap wallets derived from the wallet seed, looking for funds left on their
+/// bitcoin funding addresses, e.g. after losing the swap checkpoints.
+///
+/// Monero locked during a swap sits on an address shared with the counterparty and cannot be
+/// recovered from the wallet seed alone, only swaps restored from their checkpoint can claim it.
+pub struct Recovery {
+    network: Network,
+    /// Block height the address scans start from
+    from_height: u64,
+    /// Height of the bitcoin chain, zero until the syncer reports it
+    height: u64,
+    scanned_wallets: u32,
+    /// Funding addresses waiting for the chain height to bound their watch tasks lifetime
+    pending: Vec<(u32, bitcoin::Address)>,
+    /// Watch address tasks, mapped to the wallet index and its funding address
+    watched: HashMap<TaskId, (u32, bitcoin::Address)>,
+    /// Sweep broadcast tasks, mapped to the swept output
+    sweeps: HashMap<TaskId, OutPoint>,
+    outputs: Vec<RecoverableOutput>,
+    task_counter: u32,
+}
+
+impl Recovery {
+    fn new(network: Network, from_height: u64) -> Self {
+        Self {
+            network,
+            from_height,
+            height: 0,
+            scanned_wallets: 0,
+            pending: vec![],
+            watched: none!(),
+            sweeps: none!(),
+            outputs: vec![],
+            task_counter: 0,
+        }
+    }
+
+    fn syncer(&self) -> ServiceId {
+        ServiceId::Syncer(Coin::Bitcoin, self.network)
+    }
+
+    fn new_taskid(&mut self) -> TaskId {
+        self.task_counter += 1;
+        TaskId(self.task_counter)
+    }
+
+    fn watch_height(&mut self) -> Task {
+        Task::WatchHeight(WatchHeight {
+            id: self.new_taskid(),
+            lifetime: u64::MAX,
+        })
+    }
+
+    /// Queue the funding address of a swap wallet, watched once the chain height is known
+    fn watch_address(&mut self, wallet_index: u32, address: bitcoin::Address) {
+        self.pending.push((wallet_index, address));
+    }
+
+    /// Watch tasks of the queued funding addresses, none until the chain height is known
+    fn pending_tasks(&mut self) -> Vec<Task> {
+        if self.height == 0 {
+            return vec![];
+        }
+        let lifetime = self.height + RECOVERY_LIFETIME;
+        let from_height = self.from_height;
+        let pending: Vec<_> = self.pending.drain(..).collect();
+        pending
+            .into_iter()
+            .map(|(wallet_index, address)| {
+                let id = self.new_taskid();
+                let addendum = BtcAddressAddendum {
+                    address: Some(address.clone()),
+                    from_height,
+                    script_pubkey: address.script_pubkey(),
+                };
+                self.watched.insert(id, (wallet_index, address));
+                Task::WatchAddress(WatchAddress {
+                    id,
+                    lifetime,
+                    addendum: AddressAddendum::Bitcoin(addendum),
+                    include_tx: Boolean::True,
+                })
+            })
+            .collect()
+    }
+
+    fn info(&self) -> RecoveryInfo {
+        RecoveryInfo {
+            scanned_wallets: self.scanned_wallets,
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    /// Handle a bitcoin syncer event, returning the watch tasks it unblocked
+    fn handle_event(&mut self, event: Event) -> Result<Vec<Task>, Error> {
+        match event {
+            Event::HeightChanged(HeightChanged { height, .. }) => {
+                self.height = height;
+                return Ok(self.pending_tasks());
+            }
+            Event::AddressTransaction(AddressTransaction { id, tx, .. }) => {
+                let (wallet_index, address) = match self.watched.get(&id) {
+                    Some(watched) => watched.clone(),
+                    None => return Ok(vec![]),
+                };
+                let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&tx)?;
+                let txid = tx.txid();
+                for (vout, output) in tx.output.iter().enumerate() {
+                    let outpoint = OutPoint::new(txid, vout as u32);
+                    if output.script_pubkey != address.script_pubkey()
+                        || self.outputs.iter().any(|known| known.outpoint == outpoint)
+                    {
+                        continue;
+                    }
+                    info!(
+                        "{} {} sat in {} of swap wallet {}",
+                        "Found".bright_green_bold(),
+                        output.value.bright_white_bold(),
+                        outpoint.bright_blue_italic(),
+                        wallet_index.bright_white_bold()
+                    );
+                    self.outputs.push(RecoverableOutput {
+                        wallet_index,
+                        address: address.clone(),
+                        outpoint,
+                        amount: output.value,
+                        sweep_txid: None,
+                        sweep_error: None,
+                    });
+                }
+            }
+            Event::TransactionBroadcasted(TransactionBroadcasted { id, error, .. }) => {
+                let outpoint = match self.sweeps.remove(&id) {
+                    Some(outpoint) => outpoint,
+                    None => return Ok(vec![]),
+                };
+                if let Some(output) = self.outputs.iter_mut().find(|o| o.outpoint == outpoint) {
+                    match error {
+                        // most likely the output was already spent by a swap transaction
+                        Some(err) => {
+                            warn!("Failed to sweep {}: {}", outpoint, err.err());
+                            output.sweep_txid = None;
+                            output.sweep_error = Some(err);
+                        }
+                        None => info!(
+                            "{} {}",
+                            "Swept".bright_green_bold(),
+                            outpoint.bright_blue_italic()
+                        ),
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(vec![])
     }
 }
 
@@ -153,6 +438,8 @@ pub struct BobState {
     key_manager: KeyManager,
     pub_offer: PublicOffer<BtcXmr>,
     funding_tx: Option<FundingTx>,
+    /// Funding transaction seen on chain, kept to rebuild `funding_tx` on restore
+    funding_seen_tx: Option<bitcoin::Transaction>,
     remote_commit_params: Option<CommitAliceParameters<BtcXmr>>,
     remote_params: Option<AliceParameters<BtcXmr>>,
     remote_proof: Option<Proof<BtcXmr>>,
@@ -177,6 +464,7 @@ impl BobState {
             key_manager,
             pub_offer,
             funding_tx,
+            funding_seen_tx: None,
             remote_commit_params,
             remote_params: None,
             remote_proof: None,
@@ -186,6 +474,144 @@ impl BobState {
     }
 }
 
+/// Per-swap wallet state written next to the swap checkpoint, so a restored swap can still
+/// sign its transactions and recover the counterparty's Monero key
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+struct CheckpointWallet {
+    /// Index of the swap wallet, the key manager being derived again from the wallet seed
+    wallet_index: u32,
+    /// Monero address receiving the swept funds
+    monero_address: Option<String>,
+    state: CheckpointWalletState,
+}
+
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+enum CheckpointWalletState {
+    Alice(CheckpointAlice),
+    Bob(CheckpointBob),
+}
+
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+struct CheckpointAlice {
+    destination_address: bitcoin::Address,
+    high_fee_priority: bool,
+    local_params: AliceParameters<BtcXmr>,
+    local_proof: Proof<BtcXmr>,
+    pub_offer: PublicOffer<BtcXmr>,
+    remote_commit: Option<CommitBobParameters<BtcXmr>>,
+    remote_params: Option<BobParameters<BtcXmr>>,
+    remote_proof: Option<Proof<BtcXmr>>,
+    core_arb_setup: Option<CoreArbitratingSetup<BtcXmr>>,
+    /// Compact serialization of the cancel signature
+    alice_cancel_signature: Option<Vec<u8>>,
+    adaptor_refund: Option<SignedAdaptorRefund<farcaster_core::bitcoin::BitcoinSegwitV0>>,
+}
+
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+struct CheckpointBob {
+    refund_address: bitcoin::Address,
+    high_fee_priority: bool,
+    local_params: BobParameters<BtcXmr>,
+    local_proof: Proof<BtcXmr>,
+    pub_offer: PublicOffer<BtcXmr>,
+    funding_seen_tx: Option<bitcoin::Transaction>,
+    remote_commit_params: Option<CommitAliceParameters<BtcXmr>>,
+    remote_params: Option<AliceParameters<BtcXmr>>,
+    remote_proof: Option<Proof<BtcXmr>>,
+    core_arb_setup: Option<CoreArbitratingSetup<BtcXmr>>,
+    adaptor_buy: Option<SignedAdaptorBuy<Bitcoin<SegwitV0>>>,
+}
+
+fn fee_priority(high_fee_priority: bool) -> FeePriority {
+    if high_fee_priority {
+        FeePriority::High
+    } else {
+        FeePriority::Low
+    }
+}
+
+impl From<&Wallet> for CheckpointWalletState {
+    fn from(wallet: &Wallet) -> Self {
+        match wallet {
+            Wallet::Alice(state) => CheckpointWalletState::Alice(CheckpointAlice {
+                destination_address: state.alice.destination_address.clone(),
+                high_fee_priority: state.alice.fee_politic == FeePriority::High,
+                local_params: state.local_params.clone(),
+                local_proof: state.local_proof.clone(),
+                pub_offer: state.pub_offer.clone(),
+                remote_commit: state.remote_commit.clone(),
+                remote_params: state.remote_params.clone(),
+                remote_proof: state.remote_proof.clone(),
+                core_arb_setup: state.core_arb_setup.clone(),
+                alice_cancel_signature: state
+                    .alice_cancel_signature
+                    .map(|sig| sig.serialize_compact().to_vec()),
+                adaptor_refund: state.adaptor_refund.clone(),
+            }),
+            Wallet::Bob(state) => CheckpointWalletState::Bob(CheckpointBob {
+                refund_address: state.bob.refund_address.clone(),
+                high_fee_priority: state.bob.fee_politic == FeePriority::High,
+                local_params: state.local_params.clone(),
+                local_proof: state.local_proof.clone(),
+                pub_offer: state.pub_offer.clone(),
+                funding_seen_tx: state.funding_seen_tx.clone(),
+                remote_commit_params: state.remote_commit_params.clone(),
+                remote_params: state.remote_params.clone(),
+                remote_proof: state.remote_proof.clone(),
+                core_arb_setup: state.core_arb_setup.clone(),
+                adaptor_buy: state.adaptor_buy.clone(),
+            }),
+        }
+    }
+}
+
+impl CheckpointWalletState {
+    fn into_wallet(self, mut key_manager: KeyManager) -> Result<Wallet, Error> {
+        Ok(match self {
+            CheckpointWalletState::Alice(state) => Wallet::Alice(AliceState {
+                alice: Alice::new(
+                    state.destination_address,
+                    fee_priority(state.high_fee_priority),
+                ),
+                local_params: state.local_params,
+                local_proof: state.local_proof,
+                key_manager,
+                pub_offer: state.pub_offer,
+                remote_commit: state.remote_commit,
+                remote_params: state.remote_params,
+                remote_proof: state.remote_proof,
+                core_arb_setup: state.core_arb_setup,
+                alice_cancel_signature: state
+                    .alice_cancel_signature
+                    .map(|sig| Signature::from_compact(&sig))
+                    .transpose()
+                    .map_err(|err| Error::Farcaster(err.to_string()))?,
+                adaptor_refund: state.adaptor_refund,
+            }),
+            CheckpointWalletState::Bob(state) => {
+                let mut funding = create_funding(&mut key_manager, state.pub_offer.offer.network)?;
+                if let Some(tx) = state.funding_seen_tx.clone() {
+                    funding_update(&mut funding, tx)?;
+                }
+                Wallet::Bob(BobState {
+                    bob: Bob::new(state.refund_address, fee_priority(state.high_fee_priority)),
+                    local_params: state.local_params,
+                    local_proof: state.local_proof,
+                    key_manager,
+                    pub_offer: state.pub_offer,
+                    funding_tx: Some(funding),
+                    funding_seen_tx: state.funding_seen_tx,
+                    remote_commit_params: state.remote_commit_params,
+                    remote_params: state.remote_params,
+                    remote_proof: state.remote_proof,
+                    core_arb_setup: state.core_arb_setup,
+                    adaptor_buy: state.adaptor_buy,
+                })
+            }
+        })
+    }
+}
+
 impl CtlServer for Runtime {}
 
 impl esb::Handler<ServiceBus> for Runtime {
@@ -204,11 +630,13 @@ impl esb::Handler<ServiceBus> for Runtime {
         source: ServiceId,
         request: Request,
     ) -> Result<(), Self::Error> {
-        match bus {
+        let res = match bus {
             ServiceBus::Msg => self.handle_rpc_msg(senders, source, request),
             ServiceBus::Ctl => self.handle_rpc_ctl(senders, source, request),
             _ => Err(Error::NotSupported(ServiceBus::Bridge, request.get_type())),
-        }
+        };
+        self.checkpoint_wallets();
+        res
     }
 
     fn handle_err(&mut self, _: esb::Error) -> Result<(), esb::Error> {
@@ -296,6 +724,7 @@ impl Runtime {
                     SwapRole::Bob => {
                         let bob = Bob::<BtcXmr>::new(external_address, FeePriority::Low);
                         let wallet_index = self.node_secrets.increment_wallet_counter();
+                        self.wallet_indexes.insert(swap_id, wallet_index);
                         let mut key_manager =
                             KeyManager::new(self.node_secrets.wallet_seed, wallet_index)?;
                         let (local_params, local_proof) =
@@ -351,6 +780,7 @@ impl Runtime {
                         let alice: Alice<BtcXmr> = Alice::new(external_address, FeePriority::Low);
                         let wallet_seed = self.node_secrets.wallet_seed;
                         let wallet_index = self.node_secrets.increment_wallet_counter();
+                        self.wallet_indexes.insert(swap_id, wallet_index);
                         let mut key_manager = KeyManager::new(wallet_seed, wallet_index)?;
                         let (local_params, local_proof) =
                             alice.generate_parameters(&mut key_manager, &pub_offer)?;
@@ -985,6 +1415,7 @@ impl Runtime {
                 // since we're takers, we are on the other side of the trade
                 let taker_role = offer.maker_role.other();
                 let wallet_index = self.node_secrets.increment_wallet_counter();
+                self.wallet_indexes.insert(swap_id, wallet_index);
                 let mut key_manager = KeyManager::new(self.node_secrets.wallet_seed, wallet_index)?;
                 match taker_role {
                     SwapRole::Bob => {
@@ -1076,6 +1507,7 @@ impl Runtime {
             Request::Tx(Tx::Funding(tx)) => {
                 if let Some(Wallet::Bob(BobState {
                     funding_tx: Some(funding),
+                    funding_seen_tx,
                     ..
                 })) = self.wallets.get_mut(&get_swap_id(&source)?)
                 {
@@ -1083,6 +1515,7 @@ impl Runtime {
                         warn!("funding was previously updated, ignoring");
                         return Ok(());
                     }
+                    *funding_seen_tx = Some(tx.clone());
                     funding_update(funding, tx)?;
                     debug!("bob's wallet informs swapd that funding was successfully updated");
                     senders.send_to(
@@ -1147,9 +1580,11 @@ impl Runtime {
                     let keypair = monero::KeyPair { view, spend };
                     let corresponding_address = monero::Address::from_keypair(network, &keypair);
                     info!("Corresponding address: {}", corresponding_address);
+                    // kept until the swap ends, a restored swap sees the buy again
                     let address = self
                         .xmr_addrs
-                        .remove(&get_swap_id(&source)?)
+                        .get(&get_swap_id(&source)?)
+                        .cloned()
                         .expect("checked at the start of a swap");
                     let sweep_keys = SweepXmrAddress {
                         view_key: view,
@@ -1219,9 +1654,11 @@ impl Runtime {
                     let keypair = monero::KeyPair { view, spend };
                     let corresponding_address = monero::Address::from_keypair(network, &keypair);
                     info!("Corresponding address: {}", corresponding_address);
+                    // kept until the swap ends, a restored swap sees the refund again
                     let address = self
                         .xmr_addrs
-                        .remove(&get_swap_id(&source)?)
+                        .get(&get_swap_id(&source)?)
+                        .cloned()
                         .expect("checked at the start of a swap");
                     let sweep_keys = SweepXmrAddress {
                         view_key: view,
@@ -1250,6 +1687,163 @@ impl Runtime {
                     )),
                 )?
             }
+            Request::ChangePassphrase(request::ChangePassphrase { current, new })
+                if matches!(source, ServiceId::Client(_)) =>
+            {
+                let res = self.node_secrets.change_passphrase(&current, new);
+                if res.is_ok() {
+                    info!("{}", "Key file passphrase changed".bright_green_bold());
+                }
+                self.send_ctl(senders, source, res.into_success_or_failure())?;
+            }
+            Request::ExportSeed(request::ExportSeed { passphrase })
+                if matches!(source, ServiceId::Client(_)) =>
+            {
+                let reply = match self.node_secrets.mnemonic(&passphrase) {
+                    Ok(mnemonic) => Request::SeedMnemonic(request::SeedMnemonic {
+                        mnemonic,
+                        wallet_counter: self.node_secrets.wallet_counter.0,
+                    }),
+                    Err(err) => Request::from(err),
+                };
+                self.send_ctl(senders, source, reply)?;
+            }
+            Request::RestoreSeed(request::RestoreSeed {
+                passphrase,
+                mnemonic,
+                wallet_counter,
+            }) if matches!(source, ServiceId::Client(_)) => {
+                let res = if !self.wallets.is_empty() {
+                    Err(Error::Wallet(s!(
+                        "Cannot restore the wallet seed while swaps are running"
+                    )))
+                } else {
+                    self.node_secrets
+                        .restore_mnemonic(&passphrase, &mnemonic, wallet_counter)
+                };
+                if res.is_ok() {
+                    info!(
+                        "{} with wallet counter {}",
+                        "Wallet seed restored from mnemonic".bright_green_bold(),
+                        self.node_secrets.wallet_counter.0
+                    );
+                }
+                self.send_ctl(senders, source, res.into_success_or_failure())?;
+            }
+            Request::RecoverFunds(request::RecoverFunds {
+                network,
+                from_height,
+            }) if source == ServiceId::Farcasterd => {
+                self.recover_funds(senders, network, from_height)?;
+            }
+            Request::GetRecoveryInfo if matches!(source, ServiceId::Client(_)) => {
+                let reply = match &self.recovery {
+                    Some(recovery) => Request::RecoveryInfo(recovery.info()),
+                    None => Request::from(Error::Wallet(s!(
+                        "No recovery scan was started, run recover first"
+                    ))),
+                };
+                self.send_ctl(senders, source, reply)?;
+            }
+            Request::SweepRecovered(request::SweepRecovered { address, fee_rate })
+                if matches!(source, ServiceId::Client(_)) =>
+            {
+                let res = self.sweep_recovered(senders, address, fee_rate);
+                self.send_ctl(senders, source, res.into_success_or_failure())?;
+            }
+            Request::SyncerEvent(event) => match self.recovery.as_mut() {
+                Some(recovery) if source == recovery.syncer() => {
+                    let syncer = recovery.syncer();
+                    for task in recovery.handle_event(event)? {
+                        senders.send_to(
+                            ServiceBus::Ctl,
+                            self.identity(),
+                            syncer.clone(),
+                            Request::SyncerTask(task),
+                        )?;
+                    }
+                }
+                _ => debug!("Ignoring syncer event {} from {}", event, source),
+            },
+            Request::BumpFee(TxLabel::Punish) => {
+                // the punish transaction is the only one signed by a single party, alice re-signs
+                // it at the high end of the offer fee range so it replaces the stuck one
+                let swap_id = get_swap_id(&source)?;
+                let my_id = self.identity();
+                if let Some(Wallet::Alice(AliceState {
+                    alice,
+                    local_params,
+                    key_manager,
+                    pub_offer,
+                    remote_params: Some(bob_parameters),
+                    core_arb_setup: Some(core_arb_setup),
+                    ..
+                })) = self.wallets.get_mut(&swap_id)
+                {
+                    if alice.fee_politic == FeePriority::High
+                        || matches!(pub_offer.offer.fee_strategy, FeeStrategy::Fixed(_))
+                    {
+                        warn!(
+                            "Punish transaction of swap {} already pays the highest fee of the offer",
+                            swap_id.bright_blue_italic()
+                        );
+                        return Ok(());
+                    }
+                    alice.fee_politic = FeePriority::High;
+                    let core_arb_txs: CoreArbitratingTransactions<Bitcoin<SegwitV0>> =
+                        core_arb_setup.clone().into();
+                    let FullySignedPunish { punish, punish_sig } = alice.fully_sign_punish(
+                        key_manager,
+                        local_params,
+                        bob_parameters,
+                        &core_arb_txs,
+                        pub_offer,
+                    )?;
+                    let mut punish_tx = PunishTx::from_partial(punish);
+                    punish_tx.add_witness(local_params.punish, punish_sig)?;
+                    let tx =
+                        Broadcastable::<BitcoinSegwitV0>::finalize_and_extract(&mut punish_tx)?;
+                    info!(
+                        "Re-signed punish transaction of swap {} with a higher fee",
+                        swap_id.bright_blue_italic()
+                    );
+                    senders.send_to(ServiceBus::Ctl, my_id, source, Request::Tx(Tx::Punish(tx)))?;
+                } else {
+                    error!(
+                        "Cannot re-sign the punish transaction of swap {}, missing Alice wallet or \
+                         arbitrating setup",
+                        swap_id
+                    );
+                }
+            }
+            Request::BumpFee(tx_label) => {
+                error!(
+                    "{} transaction is co-signed with the counterparty, walletd cannot bump its fee",
+                    tx_label
+                );
+            }
+            Request::SetFeePriority(request::SetFeePriority { swap_id, priority })
+                if source == ServiceId::Swap(swap_id) =>
+            {
+                // only affects the transactions created from now on, the ones already signed
+                // keep their fee: the cancel, refund and buy are co-signed with the counterparty
+                // and cannot be re-signed alone, the punish is bumped through `BumpFee`
+                match self.wallets.get_mut(&swap_id) {
+                    Some(Wallet::Alice(AliceState { alice, .. })) => alice.fee_politic = priority,
+                    Some(Wallet::Bob(BobState { bob, .. })) => bob.fee_politic = priority,
+                    None => debug!("No wallet for swap {} to set the fee priority", swap_id),
+                }
+            }
+            Request::RestoreSwap => {
+                let swap_id = get_swap_id(&source)?;
+                if let Err(err) = self.restore_wallet(swap_id) {
+                    error!(
+                        "Cannot restore the wallet of swap {}, it cannot sign or recover keys: {}",
+                        swap_id.bright_blue_italic(),
+                        err
+                    );
+                }
+            }
             Request::SwapOutcome(success) => {
                 let swapid = get_swap_id(&source)?;
                 let success = match success {
@@ -1275,6 +1869,183 @@ impl Runtime {
     }
 }
 
+impl Runtime {
+    /// Watch the funding address of every swap wallet derived so far from `from_height`;
+    /// scanning again on the same network and height only adds the wallets derived since the
+    /// last scan
+    fn recover_funds(
+        &mut self,
+        senders: &mut Senders,
+        network: Network,
+        from_height: u64,
+    ) -> Result<(), Error> {
+        let mut recovery = match self.recovery.take() {
+            Some(recovery)
+                if recovery.network == network && recovery.from_height == from_height =>
+            {
+                recovery
+            }
+            _ => {
+                let mut recovery = Recovery::new(network, from_height);
+                let task = recovery.watch_height();
+                senders.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    recovery.syncer(),
+                    Request::SyncerTask(task),
+                )?;
+                recovery
+            }
+        };
+        let wallet_counter = self.node_secrets.wallet_counter.0;
+        for wallet_index in recovery.scanned_wallets + 1..=wallet_counter {
+            let mut key_manager = KeyManager::new(self.node_secrets.wallet_seed, wallet_index)?;
+            let address = create_funding(&mut key_manager, network)?.get_address()?;
+            debug!(
+                "Scanning funding address {} of swap wallet {}",
+                address, wallet_index
+            );
+            recovery.watch_address(wallet_index, address);
+        }
+        for task in recovery.pending_tasks() {
+            senders.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                recovery.syncer(),
+                Request::SyncerTask(task),
+            )?;
+        }
+        info!(
+            "{} {} swap wallets on {}",
+            "Scanning".bright_blue_bold(),
+            wallet_counter.bright_white_bold(),
+            network.bright_white_bold()
+        );
+        recovery.scanned_wallets = wallet_counter;
+        self.recovery = Some(recovery);
+        Ok(())
+    }
+
+    /// Broadcast one transaction per recovered output not swept yet, each sending the output
+    /// value minus fees to `address`. Outputs funding a running swap are left to that swap.
+    fn sweep_recovered(
+        &mut self,
+        senders: &mut Senders,
+        address: bitcoin::Address,
+        fee_rate: u64,
+    ) -> Result<String, Error> {
+        let identity = self.identity();
+        let wallet_seed = self.node_secrets.wallet_seed;
+        let running: HashSet<u32> = self.wallet_indexes.values().cloned().collect();
+        let recovery = self
+            .recovery
+            .as_mut()
+            .ok_or_else(|| Error::Wallet(s!("No recovery scan was started, run recover first")))?;
+        let syncer = recovery.syncer();
+        let mut broadcasted = 0;
+        for output in recovery
+            .outputs
+            .iter_mut()
+            .filter(|output| output.sweep_txid.is_none())
+        {
+            if running.contains(&output.wallet_index) {
+                debug!("Not sweeping {}, it funds a running swap", output.outpoint);
+                continue;
+            }
+            match sweep_funding_output(wallet_seed, output, &address, fee_rate) {
+                Ok(tx) => {
+                    recovery.task_counter += 1;
+                    let id = TaskId(recovery.task_counter);
+                    recovery.sweeps.insert(id, output.outpoint);
+                    output.sweep_txid = Some(tx.txid());
+                    output.sweep_error = None;
+                    senders.send_to(
+                        ServiceBus::Ctl,
+                        identity.clone(),
+                        syncer.clone(),
+                        Request::SyncerTask(Task::BroadcastTransaction(BroadcastTransaction {
+                            id,
+                            tx: bitcoin::consensus::serialize(&tx),
+                        })),
+                    )?;
+                    broadcasted += 1;
+                }
+                Err(err) => {
+                    warn!("Cannot sweep {}: {}", output.outpoint, err.err());
+                    output.sweep_error = Some(err.to_string());
+                }
+            }
+        }
+        if broadcasted == 0 {
+            return Err(Error::Wallet(s!("No recovered output left to sweep")));
+        }
+        Ok(format!(
+            "Broadcasting {} sweep transaction(s) to {}, check their status with recovery-info",
+            broadcasted, address
+        ))
+    }
+}
+
+/// Sign a transaction spending a funding output of a swap wallet to `address`
+fn sweep_funding_output(
+    wallet_seed: [u8; 32],
+    output: &RecoverableOutput,
+    address: &bitcoin::Address,
+    fee_rate: u64,
+) -> Result<bitcoin::Transaction, Error> {
+    if address.network != output.address.network {
+        return Err(Error::Wallet(format!(
+            "Sweep address {} is not on the {} network",
+            address, output.address.network
+        )));
+    }
+    let mut key_manager = KeyManager::new(wallet_seed, output.wallet_index)?;
+    let secret_key = key_manager.get_or_derive_bitcoin_key(ArbitratingKeyId::Lock)?;
+    let secp = Secp256k1::new();
+    let public_key = PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(&secp, &secret_key),
+    };
+
+    let mut tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: output.outpoint,
+            script_sig: Script::new(),
+            sequence: 0xFFFF_FFFF,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: address.script_pubkey(),
+        }],
+    };
+    // segwit marker and flag, witness items count, a signature of at most 73 bytes and a
+    // compressed public key, each prefixed with their length
+    let witness_weight = 2 + 1 + 1 + 73 + 1 + 33;
+    let vsize = (tx.get_weight() as u64 + witness_weight + 3) / 4;
+    let fee = fee_rate * vsize;
+    if output.amount < fee + SWEEP_DUST_LIMIT {
+        return Err(Error::Wallet(format!(
+            "{} sat are not enough to pay the {} sat sweep fee",
+            output.amount, fee
+        )));
+    }
+    tx.output[0].value = output.amount - fee;
+
+    // funding outputs are P2WPKH, their script code is the corresponding P2PKH script
+    let script_code = Address::p2pkh(&public_key, address.network).script_pubkey();
+    let sighash =
+        SigHashCache::new(&tx).signature_hash(0, &script_code, output.amount, SigHashType::All);
+    let message = secp256k1::Message::from_slice(&sighash[..])
+        .map_err(|err| Error::Wallet(err.to_string()))?;
+    let mut signature = secp.sign(&message, &secret_key).serialize_der().to_vec();
+    signature.push(SigHashType::All.as_u32() as u8);
+    tx.input[0].witness = vec![signature, public_key.to_bytes()];
+    Ok(tx)
+}
+
 pub fn create_funding(
     key_manager: &mut KeyManager,
     net: farcaster_core::blockchain::Network,
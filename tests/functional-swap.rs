@@ -171,6 +171,45 @@ async fn swap_bob_maker_punish_kill_bob() {
     cleanup_processes(vec![farcasterd_taker]);
 }
 
+#[tokio::test]
+#[timeout(600000)]
+#[ignore]
+async fn swap_bob_maker_restore_kill_bob_after_lock() {
+    let execution_mutex = Arc::new(Mutex::new(0));
+    let bitcoin_rpc = Arc::new(bitcoin_setup());
+    let (monero_regtest, monero_wallet) = monero_setup().await;
+
+    let (farcasterd_maker, data_dir_maker, farcasterd_taker, data_dir_taker) =
+        setup_farcaster_clients().await;
+
+    let (xmr_dest_wallet_name, bitcoin_address, swap_id) = make_and_take_offer(
+        data_dir_maker.clone(),
+        data_dir_taker.clone(),
+        "Bob".to_string(),
+        Arc::clone(&bitcoin_rpc),
+        Arc::clone(&monero_wallet),
+        "1 BTC".to_string(),
+        "1 XMR".to_string(),
+    )
+    .await;
+
+    let farcasterd_maker = run_restore_swap_kill_bob_after_lock(
+        swap_id,
+        data_dir_taker,
+        data_dir_maker,
+        Arc::clone(&bitcoin_rpc),
+        bitcoin_address,
+        monero_regtest,
+        Arc::clone(&monero_wallet),
+        xmr_dest_wallet_name,
+        execution_mutex,
+        farcasterd_maker,
+    )
+    .await;
+
+    cleanup_processes(vec![farcasterd_maker, farcasterd_taker]);
+}
+
 #[tokio::test]
 #[timeout(600000)]
 #[ignore]
@@ -326,11 +365,7 @@ async fn setup_farcaster_clients() -> (process::Child, Vec<String>, process::Chi
     let ctx = env::var("CI").unwrap_or("false".into());
     let ext = if ctx == "false" { ".toml" } else { ".ci.toml" };
 
-    let farcasterd_maker_args = farcasterd_args(
-        data_dir_maker.clone(),
-        vec!["-vvv", "--config", &format!("tests/.farcasterd_1{}", ext)],
-        vec!["2>&1", "|", "tee", "-a", "tests/farcasterd_1.log"],
-    );
+    let farcasterd_maker_args = farcasterd_maker_args(data_dir_maker.clone());
     let farcasterd_taker_args = farcasterd_args(
         data_dir_taker.clone(),
         vec!["-vvv", "--config", &format!("tests/.farcasterd_2{}", ext)],
@@ -488,6 +523,129 @@ async fn run_refund_swap_alice_does_not_fund(
     drop(lock);
 }
 
+fn farcasterd_maker_args(data_dir_maker: Vec<String>) -> Vec<String> {
+    // If we are in CI we use .ci.toml files, otherwise .toml
+    let ctx = env::var("CI").unwrap_or("false".into());
+    let ext = if ctx == "false" { ".toml" } else { ".ci.toml" };
+
+    farcasterd_args(
+        data_dir_maker,
+        vec!["-vvv", "--config", &format!("tests/.farcasterd_1{}", ext)],
+        vec!["2>&1", "|", "tee", "-a", "tests/farcasterd_1.log"],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_restore_swap_kill_bob_after_lock(
+    swap_id: String,
+    data_dir_alice: Vec<String>,
+    data_dir_bob: Vec<String>,
+    bitcoin_rpc: Arc<bitcoincore_rpc::Client>,
+    funding_btc_address: bitcoin::Address,
+    monero_regtest: monero_rpc::RegtestDaemonClient,
+    monero_wallet: Arc<Mutex<monero_rpc::WalletClient>>,
+    monero_dest_wallet_name: String,
+    execution_mutex: Arc<Mutex<u8>>,
+    bob_farcasterd: std::process::Child,
+) -> std::process::Child {
+    let cli_alice_progress_args: Vec<String> = progress_args(data_dir_alice, swap_id.clone());
+    let cli_bob_progress_args: Vec<String> = progress_args(data_dir_bob.clone(), swap_id.clone());
+
+    bitcoin_rpc
+        .generate_to_address(1, &reusable_btc_address())
+        .unwrap();
+
+    // run until bob has the btc funding address
+    let address = retry_until_bitcoin_funding_address(cli_bob_progress_args.clone()).await;
+
+    // fund the bitcoin address
+    let lock = execution_mutex.lock().await;
+    let amount = bitcoin::Amount::ONE_SAT * 100000150;
+    bitcoin_rpc
+        .send_to_address(&address, amount, None, None, None, None, None, None)
+        .unwrap();
+    monero_regtest
+        .generate_blocks(11, reusable_xmr_address())
+        .await
+        .unwrap();
+
+    // run until the alice has the monero funding address
+    let monero_address = retry_until_monero_funding_address(cli_alice_progress_args.clone()).await;
+    send_monero(Arc::clone(&monero_wallet), monero_address, 1000000000000).await;
+
+    // generate some bitcoin blocks for confirmations
+    bitcoin_rpc
+        .generate_to_address(5, &reusable_btc_address())
+        .unwrap();
+
+    // run until the AliceState(Finish) is received, both locks are done by now
+    retry_until_finish_state_transition(
+        cli_alice_progress_args.clone(),
+        "AliceState(Finish(Success(Swapped)))".to_string(),
+    )
+    .await;
+
+    // kill bob before he sweeps the monero, then restart him from his checkpoints
+    cleanup_processes(vec![bob_farcasterd]);
+    let bob_farcasterd = launch("../farcasterd", farcasterd_maker_args(data_dir_bob)).unwrap();
+
+    // generate some blocks on bitcoin's side
+    bitcoin_rpc
+        .generate_to_address(1, &reusable_btc_address())
+        .unwrap();
+
+    // check that btc was received in the destination address
+    let balance = bitcoin_rpc
+        .get_received_by_address(&funding_btc_address, None)
+        .unwrap();
+    assert!(balance.as_sat() > 90000000);
+    drop(lock);
+
+    // cache the monero balance before sweeping
+    let monero_wallet_lock = monero_wallet.lock().await;
+    monero_wallet_lock
+        .open_wallet(monero_dest_wallet_name.clone(), None)
+        .await
+        .unwrap();
+    let before_balance = monero_wallet_lock.get_balance(0, None).await.unwrap();
+    drop(monero_wallet_lock);
+
+    // give the restored swap time to register its tasks with the syncers
+    tokio::time::sleep(time::Duration::from_secs(20)).await;
+
+    // generate some blocks on monero's side
+    monero_regtest
+        .generate_blocks(10, reusable_xmr_address())
+        .await
+        .unwrap();
+
+    // run until the restored BobState(Finish) is received
+    retry_until_bob_finish_state_transition(
+        cli_bob_progress_args.clone(),
+        "BobState(Finish(Success(Swapped)))".to_string(),
+        monero_regtest.clone(),
+    )
+    .await;
+
+    monero_regtest
+        .generate_blocks(1, reusable_xmr_address())
+        .await
+        .unwrap();
+
+    let monero_wallet_lock = monero_wallet.lock().await;
+    monero_wallet_lock
+        .open_wallet(monero_dest_wallet_name, None)
+        .await
+        .unwrap();
+    monero_wallet_lock.refresh(Some(1)).await.unwrap();
+    let after_balance = monero_wallet_lock.get_balance(0, None).await.unwrap();
+    drop(monero_wallet_lock);
+    let delta_balance = after_balance.balance - before_balance.balance;
+    assert!(delta_balance > 999660000000);
+
+    bob_farcasterd
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn run_punish_swap_kill_bob_before_monero_funding(
     swap_id: String,
@@ -837,7 +995,11 @@ fn reusable_xmr_address() -> monero::Address {
 fn info_args(data_dir: Vec<String>) -> Vec<String> {
     data_dir
         .into_iter()
-        .chain(vec!["info".to_string()])
+        .chain(vec![
+            "--format".to_string(),
+            "json".to_string(),
+            "info".to_string(),
+        ])
         .collect()
 }
 
@@ -922,13 +1084,7 @@ fn progress_args(data_dir: Vec<String>, swap_id: String) -> Vec<String> {
 }
 
 fn cli_output_to_node_info(stdout: Vec<String>) -> NodeInfo {
-    serde_yaml::from_str(
-        &stdout
-            .iter()
-            .map(|line| format!("{}{}", line, "\n"))
-            .collect::<String>(),
-    )
-    .unwrap()
+    serde_json::from_str(&stdout.join("\n")).unwrap()
 }
 
 async fn retry_until_offer(args: Vec<String>) -> Vec<String> {
@@ -3,6 +3,8 @@ use bitcoincore_rpc::{Auth, Client, RpcApi};
 use clap::Clap;
 use farcaster_node::rpc::Request;
 use farcaster_node::syncerd::bitcoin_syncer::BitcoinSyncer;
+use farcaster_node::syncerd::bitcoind_syncer::BitcoindSyncer;
+use farcaster_node::syncerd::esplora_syncer::EsploraSyncer;
 use farcaster_node::syncerd::monero_syncer::MoneroSyncer;
 use farcaster_node::syncerd::opts::{Coin, Opts};
 use farcaster_node::syncerd::runtime::SyncerdTask;
@@ -20,8 +22,10 @@ use monero::Address;
 use monero_rpc::GetBlockHeaderSelector;
 use paste::paste;
 use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
 
 use ntest::timeout;
 
@@ -33,8 +37,8 @@ use std::str::FromStr;
 
 use farcaster_core::blockchain::Network;
 use farcaster_node::syncerd::types::{
-    Abort, AddressAddendum, Boolean, BroadcastTransaction, BtcAddressAddendum, Event, GetTx, Task,
-    WatchAddress, WatchHeight, WatchTransaction,
+    Abort, AddressAddendum, Boolean, BroadcastTransaction, BtcAddressAddendum, EstimateFee, Event,
+    GetTx, Task, WatchAddress, WatchHeight, WatchTransaction,
 };
 
 const SOURCE1: ServiceId = ServiceId::Syncer(Coin::Bitcoin, Network::Local);
@@ -112,6 +116,18 @@ fn bicoin_syncer_retrieve_transaction_test() {
     println!("received request: {:?}", request);
 }
 
+fn assert_fee_estimated(request: Request, expected_sat_per_vbyte: Option<u64>) {
+    match request {
+        Request::SyncerdBridgeEvent(event) => match event.event {
+            Event::FeeEstimated(fee_estimated) => {
+                assert_eq!(fee_estimated.sat_per_vbyte, expected_sat_per_vbyte);
+            }
+            _ => panic!("expected fee estimated event"),
+        },
+        _ => panic!("expected syncerd bridge event"),
+    }
+}
+
 fn assert_transaction_received(request: Request, expected_txid: bitcoin::Txid) {
     match request {
         Request::SyncerdBridgeEvent(event) => match event.event {
@@ -905,6 +921,360 @@ fn create_bitcoin_syncer(
     (tx, rx_event)
 }
 
+/*
+We test that the bitcoind backend reports a transaction paying to a watched
+address as soon as it enters the mempool, without loading a wallet on the node
+*/
+#[test]
+#[timeout(300000)]
+#[ignore]
+fn bitcoind_syncer_address_test() {
+    let bitcoin_rpc = bitcoin_setup();
+    let address = bitcoin_rpc.get_new_address(None, None).unwrap();
+    let amount = bitcoin::Amount::ONE_SAT * 294;
+    bitcoin_rpc.generate_to_address(110, &address).unwrap();
+
+    // start a bitcoind syncer
+    let (tx, rx_event) = create_bitcoind_syncer("bitcoind_address");
+
+    let blocks = bitcoin_rpc.get_block_count().unwrap();
+    let address1 = bitcoin_rpc.get_new_address(None, None).unwrap();
+    let watch_address_task = SyncerdTask {
+        task: Task::WatchAddress(WatchAddress {
+            id: TaskId(1),
+            lifetime: blocks + 10,
+            addendum: AddressAddendum::Bitcoin(BtcAddressAddendum {
+                address: Some(address1.clone()),
+                from_height: blocks,
+                script_pubkey: address1.script_pubkey(),
+            }),
+            include_tx: Boolean::True,
+        }),
+        source: SOURCE1.clone(),
+    };
+    tx.send(watch_address_task).unwrap();
+
+    let txid = bitcoin_rpc
+        .send_to_address(&address1, amount, None, None, None, None, None, None)
+        .unwrap();
+    println!("waiting for address transaction message");
+    let message = rx_event.recv_multipart(0).unwrap();
+    println!("received address transaction message");
+    let request = get_request_from_message(message);
+    assert_address_transaction(request, amount.as_sat(), vec![txid.to_vec()]);
+}
+
+fn create_bitcoind_syncer(
+    socket_name: &str,
+) -> (std::sync::mpsc::Sender<SyncerdTask>, zmq::Socket) {
+    let addr = format!("inproc://testbitcoindbridge-{}", socket_name);
+
+    let (tx, rx): (Sender<SyncerdTask>, Receiver<SyncerdTask>) = std::sync::mpsc::channel();
+    let tx_event = ZMQ_CONTEXT.socket(zmq::PAIR).unwrap();
+    let rx_event = ZMQ_CONTEXT.socket(zmq::PAIR).unwrap();
+    tx_event.connect(&addr).unwrap();
+    rx_event.bind(&addr).unwrap();
+    let mut syncer = BitcoindSyncer::new();
+
+    let cookie = env::var("BITCOIN_COOKIE").unwrap_or("tests/data_dir/regtest/.cookie".into());
+    let host = env::var("BITCOIN_HOST").unwrap_or("localhost".into());
+    let opts = Opts::parse_from(vec!["syncerd"].into_iter().chain(vec![
+        "--coin",
+        "Bitcoin",
+        "--bitcoin-backend",
+        "bitcoind",
+        "--bitcoind-rpc",
+        &format!("http://{}:18443", host),
+        "--bitcoind-rpc-cookie",
+        &cookie,
+    ]));
+
+    syncer
+        .run(
+            rx,
+            tx_event,
+            SOURCE1.clone().into(),
+            &opts,
+            Network::Local,
+            true,
+        )
+        .expect("Valid bitcoind syncer");
+    (tx, rx_event)
+}
+
+/*
+The esplora syncer runs against a local stand-in HTTP server serving canned
+responses, hence this test does not need the docker setup and is not ignored.
+
+We test for the following scenarios:
+
+- Submit a WatchHeight task and receive the tip served by the stand-in
+
+- Submit a WatchAddress task and receive the mempool transaction paying to it
+
+- Submit a WatchTransaction task and receive its confirmations
+
+- Broadcast a transaction, successfully then with an error
+
+- Retrieve a transaction
+*/
+#[test]
+#[timeout(300000)]
+fn esplora_syncer_test() {
+    let script = bitcoin::Script::new_v0_wpkh(&bitcoin::WPubkeyHash::hash(&[1; 33]));
+    let amount = 10_000;
+    let tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint::null(),
+            script_sig: bitcoin::Script::new(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        }],
+        output: vec![bitcoin::TxOut {
+            value: amount,
+            script_pubkey: script.clone(),
+        }],
+    };
+    let txid = tx.txid();
+    let block_hash = bitcoin::BlockHash::hash(&[0]);
+
+    let routes = Arc::new(Mutex::new(HashMap::new()));
+    {
+        let mut routes = routes.lock().unwrap();
+        routes.insert(
+            "GET /blocks/tip/hash".to_string(),
+            (200, block_hash.to_string()),
+        );
+        routes.insert(
+            format!("GET /block/{}", block_hash),
+            (200, r#"{"height":100}"#.to_string()),
+        );
+        routes.insert(
+            format!(
+                "GET /scripthash/{}/txs",
+                bitcoin::hashes::sha256::Hash::hash(script.as_bytes())
+            ),
+            (
+                200,
+                format!(
+                    r#"[{{"txid":"{}","vout":[{{"scriptpubkey":"{:x}","value":{}}}],"status":{{"confirmed":false}}}}]"#,
+                    txid, script, amount
+                ),
+            ),
+        );
+        routes.insert(
+            format!("GET /tx/{}/hex", txid),
+            (200, hex::encode(bitcoin::consensus::serialize(&tx))),
+        );
+        routes.insert(
+            format!("GET /tx/{}/status", txid),
+            (
+                200,
+                format!(
+                    r#"{{"confirmed":true,"block_height":100,"block_hash":"{}"}}"#,
+                    block_hash
+                ),
+            ),
+        );
+        routes.insert("POST /tx".to_string(), (200, txid.to_string()));
+    }
+    let url = esplora_stand_in(Arc::clone(&routes));
+
+    let (tx_task, rx_event) = create_esplora_syncer(&url, "esplora");
+
+    tx_task
+        .send(SyncerdTask {
+            task: Task::WatchHeight(WatchHeight {
+                id: TaskId(0),
+                lifetime: 110,
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_received_height_changed(request, 100);
+
+    tx_task
+        .send(SyncerdTask {
+            task: Task::WatchAddress(WatchAddress {
+                id: TaskId(1),
+                lifetime: 110,
+                addendum: AddressAddendum::Bitcoin(BtcAddressAddendum {
+                    address: None,
+                    from_height: 0,
+                    script_pubkey: script.clone(),
+                }),
+                include_tx: Boolean::True,
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_address_transaction(request, amount, vec![txid.to_vec()]);
+
+    tx_task
+        .send(SyncerdTask {
+            task: Task::WatchTransaction(WatchTransaction {
+                id: TaskId(2),
+                lifetime: 110,
+                hash: txid.to_vec(),
+                confirmation_bound: 2,
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_transaction_confirmations(request, Some(1), block_hash.to_vec());
+
+    tx_task
+        .send(SyncerdTask {
+            task: Task::BroadcastTransaction(BroadcastTransaction {
+                id: TaskId(3),
+                tx: bitcoin::consensus::serialize(&tx),
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_transaction_broadcasted(request, false, None);
+
+    routes.lock().unwrap().insert(
+        "POST /tx".to_string(),
+        (400, "bad-txns-inputs-missingorspent".to_string()),
+    );
+    tx_task
+        .send(SyncerdTask {
+            task: Task::BroadcastTransaction(BroadcastTransaction {
+                id: TaskId(4),
+                tx: bitcoin::consensus::serialize(&tx),
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_transaction_broadcasted(
+        request,
+        true,
+        Some("failed to broadcast tx: 400 Bad Request: bad-txns-inputs-missingorspent".to_string()),
+    );
+
+    tx_task
+        .send(SyncerdTask {
+            task: Task::GetTx(GetTx {
+                id: TaskId(5),
+                hash: txid.to_vec(),
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_transaction_received(request, txid);
+
+    routes.lock().unwrap().insert(
+        "GET /fee-estimates".to_string(),
+        (200, r#"{"1":20.5,"2":12.1,"6":4.2,"144":1.0}"#.to_string()),
+    );
+    tx_task
+        .send(SyncerdTask {
+            task: Task::EstimateFee(EstimateFee {
+                id: TaskId(6),
+                blocks_until_confirmation: 3,
+            }),
+            source: SOURCE1.clone(),
+        })
+        .unwrap();
+    let request = get_request_from_message(rx_event.recv_multipart(0).unwrap());
+    assert_fee_estimated(request, Some(13));
+}
+
+/// Minimal HTTP server answering each `<METHOD> <path>` route with a canned status and body, and
+/// 404 otherwise
+fn esplora_stand_in(routes: Arc<Mutex<HashMap<String, (u16, String)>>>) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // the syncer retries on failed requests, ignore broken connections
+            let _ = serve_canned_response(stream, &routes);
+        }
+    });
+    url
+}
+
+fn serve_canned_response(
+    mut stream: std::net::TcpStream,
+    routes: &Mutex<HashMap<String, (u16, String)>>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some(len) = header.to_lowercase().strip_prefix("content-length:") {
+            content_length = len.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let route: Vec<&str> = request_line.split_whitespace().take(2).collect();
+    let (status, body) = routes
+        .lock()
+        .unwrap()
+        .get(&route.join(" "))
+        .cloned()
+        .unwrap_or((404, "not found".to_string()));
+    write!(
+        stream,
+        "HTTP/1.1 {} Canned\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn create_esplora_syncer(
+    url: &str,
+    socket_name: &str,
+) -> (std::sync::mpsc::Sender<SyncerdTask>, zmq::Socket) {
+    let addr = format!("inproc://testesplorabridge-{}", socket_name);
+
+    let (tx, rx): (Sender<SyncerdTask>, Receiver<SyncerdTask>) = std::sync::mpsc::channel();
+    let tx_event = ZMQ_CONTEXT.socket(zmq::PAIR).unwrap();
+    let rx_event = ZMQ_CONTEXT.socket(zmq::PAIR).unwrap();
+    tx_event.connect(&addr).unwrap();
+    rx_event.bind(&addr).unwrap();
+    let mut syncer = EsploraSyncer::new();
+
+    let opts = Opts::parse_from(vec!["syncerd"].into_iter().chain(vec![
+        "--coin",
+        "Bitcoin",
+        "--bitcoin-backend",
+        "esplora",
+        "--esplora-server",
+        url,
+    ]));
+
+    syncer
+        .run(
+            rx,
+            tx_event,
+            SOURCE1.clone().into(),
+            &opts,
+            Network::Local,
+            true,
+        )
+        .expect("Valid esplora syncer");
+    (tx, rx_event)
+}
+
 fn find_coinbase_transaction_id(txs: Vec<bitcoin::Transaction>) -> bitcoin::Txid {
     for transaction in txs {
         if transaction.input[0].previous_output.txid == bitcoin::Txid::from_slice(&[0; 32]).unwrap()